@@ -1,6 +1,8 @@
 use anyhow::{Result, anyhow};
 #[cfg(any(feature = "testnet", feature = "mainnet"))]
 use seda_sdk_rs::{HttpFetchOptions, Process, elog, log, proxy_http_fetch};
+#[cfg(any(feature = "testnet", feature = "mainnet"))]
+use shared::UrlBuilder;
 
 #[cfg(feature = "testnet")]
 const API_URL: &str = "https://seda-proxy.blocksize.dev/proxy/bidask/";
@@ -28,20 +30,34 @@ pub fn execution_phase() -> Result<()> {
 //   "ts": 1756156227634385
 // }
 
-const VALID_FIELDS: &[&str] = &[
+/// The fields that can be requested, the reveal refers to them by their index in this list.
+/// The API fields come first, followed by the fields derived from the bid and ask prices:
+/// - `mid_from_bidask`: the midpoint of the bid and ask prices.
+/// - `spread_bps`: the spread between the bid and ask prices, in basis points of `mid_from_bidask`.
+pub const VALID_FIELDS: &[&str] = &[
     "agg_bid_price",
     "agg_bid_size",
     "agg_ask_price",
     "agg_ask_size",
     "agg_mid_price",
     "ts",
+    "mid_from_bidask",
+    "spread_bps",
 ];
 
+/// The number of fields from the API at the start of `VALID_FIELDS`, used when no fields are requested.
+#[cfg(any(feature = "testnet", feature = "mainnet"))]
+const API_FIELD_COUNT: usize = 6;
+
+/// The decimals the prices and derived fields are reported with.
+#[cfg(any(feature = "testnet", feature = "mainnet"))]
+const DECIMALS: u32 = 6;
+
 // do all fields by default
 // return a uint256[] abi encoded in tally
 #[cfg(any(feature = "testnet", feature = "mainnet"))]
 pub fn execution_phase() -> Result<()> {
-    // Expected to be in the format "symbol" (e.g., "ETHUSD" or "BTCUSD").
+    // Expected to be in the format "symbol" (e.g., "ETHUSD" or "BTCUSD"), several symbols are separated by a comma(,).
     // Optionally followed by the field names (e.g., "agg_ask_price,agg_mid_price") separated by a hyphen(-).
     let dr_inputs_raw = String::from_utf8(Process::get_inputs())?;
     if dr_inputs_raw.is_empty() {
@@ -52,22 +68,10 @@ pub fn execution_phase() -> Result<()> {
     }
 
     let parts: Vec<&str> = dr_inputs_raw.split('-').collect();
-    let (pair, fields) = match parts.as_slice() {
-        // validate that each field is in the valid fields list
-        [pair, fields] => (
-            pair,
-            fields
-                .split(',')
-                .filter(|field| {
-                    let in_map = VALID_FIELDS.contains(field);
-                    if !in_map {
-                        elog!("Invalid field: {field}");
-                    }
-                    in_map
-                })
-                .collect::<Vec<_>>(),
-        ),
-        [pair] => (pair, VALID_FIELDS.to_vec()),
+    let (pairs, fields) = match parts.as_slice() {
+        // The fields keep the requested order, duplicates included.
+        [pairs, fields] => (pairs, fields.split(',').collect::<Vec<_>>()),
+        [pairs] => (pairs, VALID_FIELDS[..API_FIELD_COUNT].to_vec()),
         _ => {
             elog!("Invalid input format");
             Process::error("Invalid input format".as_bytes());
@@ -75,13 +79,70 @@ pub fn execution_phase() -> Result<()> {
         }
     };
 
-    if fields.is_empty() {
-        Process::error("No valid fields requested".as_bytes());
+    let pairs: Vec<&str> = pairs.split(',').collect();
+    if pairs.iter().any(|pair| pair.is_empty()) {
+        elog!("Invalid input format: empty symbol in {pairs:?}");
+        Process::error("Invalid input format".as_bytes());
+        return Ok(());
+    }
+
+    // Validate that each field is in the valid fields list, rather than silently dropping the invalid ones.
+    let invalid_fields: Vec<&str> = fields
+        .iter()
+        .filter(|field| !VALID_FIELDS.contains(field))
+        .copied()
+        .collect();
+    if !invalid_fields.is_empty() {
+        elog!("Invalid fields: {invalid_fields:?}, expected any of {VALID_FIELDS:?}");
+        Process::error(format!("Invalid fields: {invalid_fields:?}").as_bytes());
+        return Ok(());
+    }
+
+    // The reveal starts with the number of fields per pair and the index of every field, so the tally
+    // can check the bid and ask prices, followed by one row of fields per pair and the `ts` of every pair,
+    // so the tally can discard stale prices.
+    let mut parsed_field_values: Vec<u128> =
+        Vec::with_capacity(1 + fields.len() + pairs.len() * (fields.len() + 1));
+    parsed_field_values.push(fields.len() as u128);
+    parsed_field_values.extend(fields.iter().map(|field| {
+        VALID_FIELDS
+            .iter()
+            .position(|valid_field| valid_field == field)
+            .unwrap_or_default() as u128
+    }));
+    let mut timestamps: Vec<u128> = Vec::with_capacity(pairs.len());
+
+    for pair in pairs {
+        log!("Fetching price for: {pair}, and using {fields:?}");
+        match fetch_pair_fields(pair, &fields) {
+            Ok((values, timestamp)) => {
+                parsed_field_values.extend(values);
+                timestamps.push(timestamp);
+            }
+            Err(error) => {
+                elog!("Failed to fetch {pair}: {error}");
+                Process::error("Error while fetching equity price".as_bytes());
+                return Ok(());
+            }
+        }
     }
 
-    log!("Fetching price for: {pair}, and using {fields:?}");
+    parsed_field_values.extend(timestamps);
 
-    let url = [API_URL, pair].concat();
+    // Report the successful result back to the SEDA network.
+    let result: Vec<u8> = parsed_field_values
+        .into_iter()
+        .flat_map(u128::to_le_bytes)
+        .collect();
+    Process::success(&result);
+
+    Ok(())
+}
+
+/// Fetches the bid/ask of a single pair and returns the requested fields in order, along with its `ts`.
+#[cfg(any(feature = "testnet", feature = "mainnet"))]
+fn fetch_pair_fields(pair: &str, fields: &[&str]) -> Result<(Vec<u128>, u128)> {
+    let url = UrlBuilder::new(API_URL).segment(pair).build();
     let response = proxy_http_fetch(
         url,
         Some(PROXY_PUBLIC_KEY.to_string()),
@@ -95,13 +156,11 @@ pub fn execution_phase() -> Result<()> {
 
     // Handle the case where the HTTP request failed or was rejected.
     if !response.is_ok() {
-        elog!(
+        return Err(anyhow!(
             "HTTP Response was rejected: {} - {} ProxyPublicKey {PROXY_PUBLIC_KEY}",
             response.status,
             String::from_utf8(response.bytes)?
-        );
-        Process::error("Error while fetching equity price".as_bytes());
-        return Ok(());
+        ));
     }
 
     // Parse the API response as defined earlier.
@@ -109,34 +168,51 @@ pub fn execution_phase() -> Result<()> {
         &response.bytes,
     )?;
 
-    let parsed_field_values: Vec<u8> = fields
-        .into_iter()
-        .flat_map(|field| {
-            let value = if field != "ts" {
-                let price = response_data
-                    .get(field)
-                    .and_then(|price| price.as_str())
-                    .ok_or_else(|| anyhow!("{field} not found in response or is invalid"))?;
-                let price_lossless = make_price(price, 6)?;
-                log!("Fetched {field}: {price_lossless:?}");
-                price_lossless.to_le_bytes()
-            } else {
-                let timestamp = response_data
-                    .get(field)
-                    .and_then(|ts| ts.as_u64())
-                    .ok_or_else(|| anyhow!("{field} not found in response or is invalid"))?;
-                log!("Fetched {field}: {timestamp:?}");
-                (timestamp as u128).to_le_bytes()
+    let timestamp = response_data
+        .get("ts")
+        .and_then(|ts| ts.as_u64())
+        .ok_or_else(|| anyhow!("ts not found in response or is invalid"))?;
+    let price_field = |field: &str| {
+        let price = response_data
+            .get(field)
+            .and_then(|price| price.as_str())
+            .ok_or_else(|| anyhow!("{field} not found in response or is invalid"))?;
+        make_price(price, DECIMALS)
+    };
+
+    // A crossed book means the API data can't be trusted, whichever fields were requested.
+    let bid_price = price_field("agg_bid_price")?;
+    let ask_price = price_field("agg_ask_price")?;
+    if bid_price > ask_price {
+        return Err(anyhow!(
+            "Crossed book: bid {bid_price} is above ask {ask_price}"
+        ));
+    }
+
+    let mid_from_bidask = bid_price.midpoint(ask_price);
+    let spread_bps = (ask_price - bid_price)
+        .checked_mul(10_000 * 10_u128.pow(DECIMALS))
+        .and_then(|spread| spread.checked_div(mid_from_bidask))
+        .ok_or_else(|| {
+            anyhow!("Failed to calculate the spread of bid {bid_price} and ask {ask_price}")
+        })?;
+
+    // Every field must be present, otherwise the rows of the reveal would not line up.
+    let values = fields
+        .iter()
+        .map(|&field| {
+            let value = match field {
+                "ts" => timestamp as u128,
+                "mid_from_bidask" => mid_from_bidask,
+                "spread_bps" => spread_bps,
+                field => price_field(field)?,
             };
-            Ok::<_, anyhow::Error>(value)
+            log!("Fetched {field}: {value:?}");
+            Ok(value)
         })
-        .flatten()
-        .collect();
+        .collect::<Result<Vec<_>>>()?;
 
-    // Report the successful result back to the SEDA network.
-    Process::success(&parsed_field_values);
-
-    Ok(())
+    Ok((values, timestamp as u128))
 }
 
 /// Convert a decimal price string (e.g., "1234.5678") into a u128 with `decimals` precision.
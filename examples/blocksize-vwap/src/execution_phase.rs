@@ -1,6 +1,8 @@
 use anyhow::{Result, anyhow};
 #[cfg(any(feature = "testnet", feature = "mainnet"))]
 use seda_sdk_rs::{Process, elog, log, proxy_http_fetch};
+#[cfg(any(feature = "testnet", feature = "mainnet"))]
+use shared::UrlBuilder;
 
 #[cfg(feature = "testnet")]
 const API_URL: &str = "https://seda-proxy.blocksize.dev/proxy/vwap/";
@@ -30,7 +32,7 @@ const VALID_FIELDS: &[&str] = &["price", "size", "volume", "ts"];
 
 #[cfg(any(feature = "testnet", feature = "mainnet"))]
 pub fn execution_phase() -> Result<()> {
-    // Expected to be in the format "symbol" (e.g., "ETHUSD" or "BTCUSD").
+    // Expected to be in the format "symbol" (e.g., "ETHUSD" or "BTCUSD"), several symbols are separated by a comma(,).
     // Optionally followed by the field names (e.g., "price,size") separated by a hyphen(-).
     let dr_inputs_raw = String::from_utf8(Process::get_inputs())?;
     if dr_inputs_raw.is_empty() {
@@ -41,22 +43,10 @@ pub fn execution_phase() -> Result<()> {
     }
 
     let parts: Vec<&str> = dr_inputs_raw.split('-').collect();
-    let (pair, fields) = match parts.as_slice() {
-        // validate that each field is in the valid fields list
-        [pair, fields] => (
-            pair,
-            fields
-                .split(',')
-                .filter(|field| {
-                    let in_map = VALID_FIELDS.contains(field);
-                    if !in_map {
-                        elog!("Invalid field: {field}");
-                    }
-                    in_map
-                })
-                .collect::<Vec<_>>(),
-        ),
-        [pair] => (pair, VALID_FIELDS.to_vec()),
+    let (pairs, fields) = match parts.as_slice() {
+        // The fields keep the requested order, duplicates included.
+        [pairs, fields] => (pairs, fields.split(',').collect::<Vec<_>>()),
+        [pairs] => (pairs, VALID_FIELDS.to_vec()),
         _ => {
             elog!("Invalid input format");
             Process::error("Invalid input format".as_bytes());
@@ -64,25 +54,73 @@ pub fn execution_phase() -> Result<()> {
         }
     };
 
-    if fields.is_empty() {
-        Process::error("No valid fields requested".as_bytes());
+    let pairs: Vec<&str> = pairs.split(',').collect();
+    if pairs.iter().any(|pair| pair.is_empty()) {
+        elog!("Invalid input format: empty symbol in {pairs:?}");
+        Process::error("Invalid input format".as_bytes());
+        return Ok(());
     }
 
-    log!("Fetching price for: {pair}, and using {fields:?}");
+    // Validate that each field is in the valid fields list, rather than silently dropping the invalid ones.
+    let invalid_fields: Vec<&str> = fields
+        .iter()
+        .filter(|field| !VALID_FIELDS.contains(field))
+        .copied()
+        .collect();
+    if !invalid_fields.is_empty() {
+        elog!("Invalid fields: {invalid_fields:?}, expected any of {VALID_FIELDS:?}");
+        Process::error(format!("Invalid fields: {invalid_fields:?}").as_bytes());
+        return Ok(());
+    }
+
+    // The reveal starts with the number of fields per pair, followed by one row of fields per pair
+    // and the `ts` of every pair, so the tally can discard stale prices.
+    let mut parsed_field_values: Vec<u128> =
+        Vec::with_capacity(1 + pairs.len() * (fields.len() + 1));
+    parsed_field_values.push(fields.len() as u128);
+    let mut timestamps: Vec<u128> = Vec::with_capacity(pairs.len());
+
+    for pair in pairs {
+        log!("Fetching price for: {pair}, and using {fields:?}");
+        match fetch_pair_fields(pair, &fields) {
+            Ok((values, timestamp)) => {
+                parsed_field_values.extend(values);
+                timestamps.push(timestamp);
+            }
+            Err(error) => {
+                elog!("Failed to fetch {pair}: {error}");
+                Process::error("Error while fetching price pair".as_bytes());
+                return Ok(());
+            }
+        }
+    }
+
+    parsed_field_values.extend(timestamps);
+
+    // Report the successful result back to the SEDA network.
+    let result: Vec<u8> = parsed_field_values
+        .into_iter()
+        .flat_map(u128::to_le_bytes)
+        .collect();
+    Process::success(&result);
+
+    Ok(())
+}
 
-    let url = [API_URL, &dr_inputs_raw].concat();
+/// Fetches the VWAP of a single pair and returns the requested fields in order, along with its `ts`.
+#[cfg(any(feature = "testnet", feature = "mainnet"))]
+fn fetch_pair_fields(pair: &str, fields: &[&str]) -> Result<(Vec<u128>, u128)> {
+    let url = UrlBuilder::new(API_URL).segment(pair).build();
     log!("Fetching URL: {url}");
     let response = proxy_http_fetch(url, Some(PROXY_PUBLIC_KEY.to_string()), None);
 
     // Handle the case where the HTTP request failed or was rejected.
     if !response.is_ok() {
-        elog!(
+        return Err(anyhow!(
             "HTTP Response was rejected: {} - {} ProxyPublicKey {PROXY_PUBLIC_KEY}",
             response.status,
             String::from_utf8(response.bytes)?
-        );
-        Process::error("Error while fetching price pair".as_bytes());
-        return Ok(());
+        ));
     }
 
     // Parse the API response as defined earlier.
@@ -90,62 +128,85 @@ pub fn execution_phase() -> Result<()> {
         &response.bytes,
     )?;
 
-    let parsed_field_values: Vec<u8> = fields
-        .into_iter()
-        .flat_map(|field| {
+    let timestamp = response_data
+        .get("ts")
+        .and_then(|ts| ts.as_u64())
+        .ok_or_else(|| anyhow!("ts not found in response or is invalid"))?;
+
+    // Every field must be present, otherwise the rows of the reveal would not line up.
+    let values = fields
+        .iter()
+        .map(|&field| {
             log!("Processing field: {field}");
             let value = if field != "ts" {
-                let price = response_data
-                    .get(field)
-                    .and_then(|price| price.as_f64())
-                    .ok_or_else(|| anyhow!("{field} not found in response or is invalid"))?;
-                let price_lossless = make_price(&price.to_string(), 6)?;
-                log!("Fetched {field}: {price_lossless:?}");
-                price_lossless.to_le_bytes()
+                // Numbers keep their raw JSON text, so they convert to fixed point without a float round trip.
+                let price = match response_data.get(field) {
+                    Some(serde_json::Value::Number(price)) => price.to_string(),
+                    _ => return Err(anyhow!("{field} not found in response or is invalid")),
+                };
+                make_price(&price, 6)?
             } else {
                 let timestamp = response_data
                     .get(field)
                     .and_then(|ts| ts.as_u64())
                     .ok_or_else(|| anyhow!("{field} not found in response or is invalid"))?;
-                log!("Fetched {field}: {timestamp:?}");
-                (timestamp as u128).to_le_bytes()
+                timestamp as u128
             };
-            Ok::<_, anyhow::Error>(value)
+            log!("Fetched {field}: {value:?}");
+            Ok(value)
         })
-        .flatten()
-        .collect();
-
-    // Report the successful result back to the SEDA network.
-    Process::success(&parsed_field_values);
+        .collect::<Result<Vec<_>>>()?;
 
-    Ok(())
+    Ok((values, timestamp as u128))
 }
 
-/// Convert a decimal price string (e.g., "1234.5678") into a u128 with `decimals` precision.
+/// Convert a decimal price string (e.g., "1234.5678" or "1.5e-7") into a u128 with `decimals` precision.
 /// - Truncates extra precision if the input has more decimals than requested.
 /// - Multiplies (with overflow checks) if the input has fewer decimals than requested.
 fn make_price(price_str: &str, decimals: u32) -> Result<u128> {
-    let (int_part, frac_part) = match price_str.split_once('.') {
+    let (mantissa, exponent) = match price_str.split_once(['e', 'E']) {
+        Some((mantissa, exponent)) => (
+            mantissa,
+            exponent
+                .parse::<i64>()
+                .map_err(|_| anyhow!("Failed to parse price: {price_str}"))?,
+        ),
+        None => (price_str, 0),
+    };
+
+    let (int_part, frac_part) = match mantissa.split_once('.') {
         Some((i, f)) => (i, f),
-        None => (price_str, ""),
+        None => (mantissa, ""),
     };
 
     let digits = format!("{int_part}{frac_part}");
-    if digits.is_empty() {
-        return Err(anyhow!("Empty price string"));
+    if digits.is_empty() || !digits.bytes().all(|digit| digit.is_ascii_digit()) {
+        return Err(anyhow!("Failed to parse price: {price_str}"));
     }
 
-    let price_int = digits
-        .parse::<u128>()
-        .map_err(|_| anyhow!("Failed to parse price: {price_str}"))?;
-
-    let decimal_places = frac_part.len() as u32;
-
-    let result = if decimal_places >= decimals {
-        price_int / 10_u128.pow(decimal_places - decimals)
+    // How many of the digits are past the requested decimals once the exponent is applied.
+    let extra_places = (frac_part.len() as i64)
+        .checked_sub(exponent)
+        .and_then(|places| places.checked_sub(decimals.into()))
+        .ok_or_else(|| anyhow!("Price conversion overflow"))?;
+
+    let result = if extra_places >= 0 {
+        // Truncate the extra digits before parsing, so long inputs don't overflow.
+        let kept_digits = digits.len().saturating_sub(extra_places as usize);
+        if kept_digits == 0 {
+            0
+        } else {
+            digits[..kept_digits]
+                .parse::<u128>()
+                .map_err(|_| anyhow!("Price conversion overflow"))?
+        }
     } else {
-        let mul = 10_u128
-            .checked_pow(decimals - decimal_places)
+        let price_int = digits
+            .parse::<u128>()
+            .map_err(|_| anyhow!("Price conversion overflow"))?;
+        let mul = u32::try_from(extra_places.unsigned_abs())
+            .ok()
+            .and_then(|places| 10_u128.checked_pow(places))
             .ok_or_else(|| anyhow!("Price conversion overflow"))?;
         price_int
             .checked_mul(mul)
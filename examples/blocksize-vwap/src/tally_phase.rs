@@ -1,75 +1,237 @@
 use anyhow::Result;
 use ethabi::{Token, ethereum_types::U256};
-use seda_sdk_rs::{Process, get_reveals, log};
+use seda_sdk_rs::{Process, elog, get_reveals, log};
+
+/// How many units of the Blocksize `ts` field (milliseconds) make up a millisecond.
+const TS_UNITS_PER_MS: u128 = 1;
+
+/// The ABI layout the tally result is encoded with, selected by the tally inputs.
+enum OutputLayout {
+    /// `uint256[]` with the fields of every pair one after the other.
+    Flat,
+    /// `uint256[][]` with one row of fields per pair.
+    Nested,
+    /// `(uint256 stride, uint256[] values)`, the flat layout along with the number of fields per pair.
+    Strided,
+}
+
+/// A reveal split into one row of fields per pair, along with the oldest `ts` of its pairs.
+struct PairRows {
+    rows: Vec<Vec<u128>>,
+    observed_at: u128,
+}
 
 pub fn tally_phase() -> Result<()> {
+    // Tally inputs are a comma-separated list of options (e.g., "nested,maxAge=5000,now=1756147348689"):
+    // - "nested" or "strided" select those layouts, the flat one is used otherwise.
+    // - "maxAge=<ms>" discards the reveals whose prices are older than that many milliseconds.
+    // - "now=<unix ms>" is the time the age is measured from, the median `ts` of the reveals otherwise.
+    let tally_inputs = String::from_utf8(Process::get_inputs()).unwrap_or_default();
+    let mut layout = OutputLayout::Flat;
+    let mut max_age_ms: Option<u128> = None;
+    let mut reference_ms: Option<u128> = None;
+    for option in tally_inputs.split(',') {
+        let (target, value) = match option.split_once('=') {
+            Some(("maxAge", value)) => (&mut max_age_ms, value),
+            Some(("now", value)) => (&mut reference_ms, value),
+            _ if option == "nested" => {
+                layout = OutputLayout::Nested;
+                continue;
+            }
+            _ if option == "strided" => {
+                layout = OutputLayout::Strided;
+                continue;
+            }
+            _ => continue,
+        };
+        // A typo must not silently turn the staleness check off.
+        let Ok(ms) = value.parse() else {
+            elog!("Invalid number in the tally inputs: {option}");
+            Process::error("Invalid tally inputs".as_bytes());
+            return Ok(());
+        };
+        *target = Some(ms);
+    }
+
     // Retrieve consensus reveals from the tally phase.
     let reveals = get_reveals()?;
-    let mut revealed_fields: Vec<Vec<u128>> = Vec::with_capacity(reveals.len());
+    let mut parsed: Vec<(usize, PairRows)> = Vec::with_capacity(reveals.len());
 
-    // Iterate over each reveal, parse its content as an unsigned integer (u128), and store it in the prices array.
+    // Iterate over each reveal, parse its content as unsigned integers (u128): the number of fields
+    // per pair, the fields of every pair and the `ts` of every pair, and store it as one row per pair.
     for reveal in reveals {
-        revealed_fields.push(
-            reveal
-                .body
-                .reveal
-                .as_slice()
-                .chunks_exact(size_of::<u128>())
-                .map(|chunk| {
-                    let arr: [u8; 16] = chunk
-                        .try_into()
-                        .map_err(|_| anyhow::anyhow!("invalid u128 bytes"))?;
-                    Ok::<_, anyhow::Error>(u128::from_le_bytes(arr))
-                })
-                .collect::<Result<Vec<_>, _>>()?,
-        );
+        let values = reveal
+            .body
+            .reveal
+            .as_slice()
+            .chunks_exact(size_of::<u128>())
+            .map(|chunk| {
+                let arr: [u8; 16] = chunk
+                    .try_into()
+                    .map_err(|_| anyhow::anyhow!("invalid u128 bytes"))?;
+                Ok::<_, anyhow::Error>(u128::from_le_bytes(arr))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let Some((&reveal_stride, values)) = values.split_first() else {
+            elog!("Ignoring empty reveal");
+            continue;
+        };
+        let reveal_stride = reveal_stride as usize;
+        // Every pair takes up its fields and its `ts`.
+        if reveal_stride == 0
+            || values.is_empty()
+            || !values.len().is_multiple_of(reveal_stride + 1)
+        {
+            elog!(
+                "Ignoring reveal with {} values for a stride of {reveal_stride}",
+                values.len()
+            );
+            continue;
+        }
+
+        let (fields, timestamps) =
+            values.split_at(values.len() / (reveal_stride + 1) * reveal_stride);
+        parsed.push((
+            reveal_stride,
+            PairRows {
+                rows: fields.chunks(reveal_stride).map(<[u128]>::to_vec).collect(),
+                // The reveal is only as fresh as its oldest pair.
+                observed_at: timestamps.iter().copied().min().unwrap_or_default(),
+            },
+        ));
+    }
+
+    // All executors ran with the same inputs, so the number of fields per pair and the number of
+    // pairs should match. Keep the shape most reveals agree on, so a single executor can't decide
+    // which reveals are discarded, and drop the others rather than failing the whole tally.
+    let shape = most_common(
+        &parsed
+            .iter()
+            .map(|(stride, pairs)| (*stride, pairs.rows.len()))
+            .collect::<Vec<_>>(),
+    );
+    let mut revealed_pairs: Vec<PairRows> = Vec::with_capacity(parsed.len());
+    for (reveal_stride, pairs) in parsed {
+        if shape != Some((reveal_stride, pairs.rows.len())) {
+            elog!(
+                "Ignoring reveal with mismatching shape: {} pairs of {reveal_stride} fields",
+                pairs.rows.len()
+            );
+            continue;
+        }
+        revealed_pairs.push(pairs);
+    }
+
+    // Discard the reveals that are older than the maximum age.
+    if let Some(max_age_ms) = max_age_ms
+        && !revealed_pairs.is_empty()
+    {
+        let reference = match reference_ms {
+            Some(reference_ms) => reference_ms.saturating_mul(TS_UNITS_PER_MS),
+            None => {
+                let mut timestamps: Vec<u128> = revealed_pairs
+                    .iter()
+                    .map(|pairs| pairs.observed_at)
+                    .collect();
+                timestamps.sort();
+                median_sorted(&timestamps)
+            }
+        };
+        let max_age = max_age_ms.saturating_mul(TS_UNITS_PER_MS);
+
+        revealed_pairs.retain(|pairs| {
+            let age = reference.saturating_sub(pairs.observed_at);
+            if age > max_age {
+                elog!(
+                    "Ignoring stale reveal observed at {}, {age} older than {reference}",
+                    pairs.observed_at
+                );
+            }
+            age <= max_age
+        });
+
+        if revealed_pairs.is_empty() {
+            elog!("Every reveal is older than {max_age_ms}ms");
+            Process::error("All revealed prices are stale".as_bytes());
+            return Ok(());
+        }
     }
 
     // If no valid prices were revealed, report an error indicating no consensus.
-    if revealed_fields.is_empty() {
+    let Some((stride, _)) = shape.filter(|_| !revealed_pairs.is_empty()) else {
         Process::error("No consensus among revealed results".as_bytes());
         return Ok(());
-    }
+    };
 
     // If there are valid prices revealed, calculate the median price from price reports.
-    let final_prices = median_each_field(&revealed_fields);
+    let revealed_rows: Vec<Vec<Vec<u128>>> =
+        revealed_pairs.into_iter().map(|pairs| pairs.rows).collect();
+    let final_rows = median_each_field(&revealed_rows);
+    log!("Final median prices: {final_rows:?}");
 
-    log!("Final median prices: {final_prices:?}");
-    // Encode the final median price as a EVM `uint256`.
-    let result = ethabi::encode(&[final_prices]);
+    // Encode the final median prices as EVM `uint256` values in the requested layout.
+    let to_tokens = |row: &[u128]| -> Vec<Token> {
+        row.iter()
+            .map(|value| Token::Uint(U256::from(*value)))
+            .collect()
+    };
+    let flat_values: Vec<u128> = final_rows.iter().flatten().copied().collect();
+    let result = match layout {
+        OutputLayout::Flat => ethabi::encode(&[Token::Array(to_tokens(&flat_values))]),
+        OutputLayout::Nested => ethabi::encode(&[Token::Array(
+            final_rows
+                .iter()
+                .map(|row| Token::Array(to_tokens(row)))
+                .collect(),
+        )]),
+        OutputLayout::Strided => ethabi::encode(&[
+            Token::Uint(U256::from(stride)),
+            Token::Array(to_tokens(&flat_values)),
+        ]),
+    };
     // Report the successful result in the tally phase.
     Process::success(&result);
 
     Ok(())
 }
 
-/// Finds the median of a list preserving the order of the fields
-fn median_each_field(data: &[Vec<u128>]) -> Token {
-    let m = data[0].len();
+/// Finds the value most reveals agree on, the earliest one on a tie.
+fn most_common<T: Clone + PartialEq>(values: &[T]) -> Option<T> {
+    values
+        .iter()
+        .rev()
+        .max_by_key(|value| values.iter().filter(|other| other == value).count())
+        .cloned()
+}
 
-    if !data.iter().all(|row| row.len() == m) {
-        Process::error("Inconsistent row lengths in data reveals".as_bytes());
-    }
+/// Calculates the median of a sorted list of values.
+fn median_sorted(vals: &[u128]) -> u128 {
+    let mid = vals.len() / 2;
 
-    let mut result = Vec::with_capacity(m);
-    (0..m).for_each(|col| {
-        // collect the col-th value from each reveal
-        let mut vals = Vec::with_capacity(data.len());
-        data.iter().for_each(|row| vals.push(row[col]));
-        vals.sort();
-        let mid = vals.len() / 2;
-
-        let median_price = if vals.len() % 2 == 0 {
-            // safe average of two u128s without overflow
-            let a = vals[mid - 1];
-            let b = vals[mid];
-            a.midpoint(b)
-        } else {
-            vals[mid]
-        };
+    if vals.len().is_multiple_of(2) {
+        // safe average of two u128s without overflow
+        vals[mid - 1].midpoint(vals[mid])
+    } else {
+        vals[mid]
+    }
+}
 
-        // convert to Token::Uint for encoding
-        result.push(Token::Uint(U256::from(median_price)));
-    });
-    Token::Array(result)
+/// Finds the median of every field of every pair, preserving the order of the pairs and fields.
+/// Every reveal must have the same number of pairs and fields.
+fn median_each_field(data: &[Vec<Vec<u128>>]) -> Vec<Vec<u128>> {
+    let pairs = data[0].len();
+    let stride = data[0][0].len();
+    (0..pairs)
+        .map(|pair| {
+            (0..stride)
+                .map(|field| {
+                    // collect the field of the pair from each reveal
+                    let mut vals: Vec<u128> = data.iter().map(|rows| rows[pair][field]).collect();
+                    vals.sort();
+                    median_sorted(&vals)
+                })
+                .collect()
+        })
+        .collect()
 }
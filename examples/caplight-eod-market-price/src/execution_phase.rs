@@ -1,14 +1,19 @@
 use anyhow::Result;
 #[cfg(any(feature = "testnet", feature = "mainnet"))]
-use seda_sdk_rs::{Process, elog, log, proxy_http_fetch};
+use seda_sdk_rs::{HttpFetchMethod, HttpFetchOptions, Process, elog, log, proxy_http_fetch};
+#[cfg(any(feature = "testnet", feature = "mainnet"))]
+use shared::UrlBuilder;
+
+#[cfg(any(feature = "testnet", feature = "mainnet"))]
+use crate::BatchReveal;
 
 #[cfg(feature = "testnet")]
-const API_URL: &str = "http://104.155.34.32:5384/proxy/market-price-fixed-eod?";
+const API_URL: &str = "http://104.155.34.32:5384/proxy/market-price-fixed-eod";
 #[cfg(feature = "testnet")]
 const PROXY_PUBLIC_KEY: &str = "0306346975352e34719df41928048482b285d24cd27f8e5fc2df7e4095f9cc14cf";
 
 #[cfg(feature = "mainnet")]
-const API_URL: &str = "http://34.14.120.47:5384/proxy/market-price-fixed-eod?";
+const API_URL: &str = "http://34.14.120.47:5384/proxy/market-price-fixed-eod";
 #[cfg(feature = "mainnet")]
 const PROXY_PUBLIC_KEY: &str = "02088452cd5025f33d7ce95ee8eb7ba34b94b518ea23b1897665e1afdbcae2ca18";
 
@@ -33,11 +38,34 @@ pub fn execution_phase() -> Result<()> {
 // 	}
 // }
 
+/// The decimals the price, valuation, standard error and order imbalance are revealed with.
 #[cfg(any(feature = "testnet", feature = "mainnet"))]
-pub fn execution_phase() -> Result<()> {
-    use seda_sdk_rs::{HttpFetchMethod, HttpFetchOptions};
+const FIXED_POINT_SCALE: f64 = 10_000.0;
+
+/// The exit code of a price that doesn't meet the requested data quality, so it can be told apart
+/// from a failed request.
+#[cfg(any(feature = "testnet", feature = "mainnet"))]
+const DATA_QUALITY_EXIT_CODE: u8 = 2;
+
+/// Optional limits the quality of the data behind a price has to be within for it to be reported.
+#[cfg(any(feature = "testnet", feature = "mainnet"))]
+#[derive(Debug, Default)]
+struct Thresholds {
+    /// The maximum `daysSinceLastDataPoint`.
+    max_days_since: Option<u64>,
+    /// The minimum `numberOfPoints6mo`.
+    min_points_6mo: Option<u64>,
+    /// The maximum `priceStandardError / price` ratio.
+    max_std_err_ratio: Option<f64>,
+}
 
+#[cfg(any(feature = "testnet", feature = "mainnet"))]
+pub fn execution_phase() -> Result<()> {
     // Expected to be a valid project Id and optionally a comma separated valid value for utcHour(0, 4, 8, 12, 16, 20) for the Caplight API.
+    // Optionally followed by "valuation" (e.g., "54782-29,4,valuation") to reveal the full valuation payload instead of the price,
+    // the thresholds "maxDaysSince=<days>", "minPoints6mo=<count>" and "maxStdErrRatio=<ratio>", and the historical
+    // date "date=<YYYY-MM-DD>" to get the price as of, each separated by a comma.
+    // Several project Ids can be separated by a semicolon (e.g., "54782-29;12345-67,4") to get the price of each.
     let dr_inputs_raw = String::from_utf8(Process::get_inputs())?;
     // If no input is provided, log an error and return.
     if dr_inputs_raw.is_empty() {
@@ -46,33 +74,233 @@ pub fn execution_phase() -> Result<()> {
         return Ok(());
     }
 
-    let parts: Vec<&str> = dr_inputs_raw.split(',').collect();
-    let (pitchbook_id, utc_hour) = match parts.as_slice() {
-        // validate that each field is in the valid fields list
-        [pitchbook_id, utc_hour] => match *utc_hour {
-            "0" | "4" | "8" | "12" | "16" | "20" => (pitchbook_id, Some(utc_hour)),
-            _ => {
-                elog!("Invalid UTC hour format: should be one of 0,4,8,12,16,20");
-                Process::error("Invalid UTC hour format".as_bytes());
-                return Ok(());
+    let mut parts = dr_inputs_raw.split(',');
+    let pitchbook_ids: Vec<&str> = parts.next().unwrap_or_default().split(';').collect();
+    let mut utc_hour: Option<&str> = None;
+    let mut full_valuation = false;
+    let mut thresholds = Thresholds::default();
+    let mut requested_date: Option<(&str, u32)> = None;
+    for option in parts {
+        let parsed = match option.split_once('=') {
+            Some(("date", value)) => {
+                parse_iso_date(value).map(|date| requested_date = Some((value, date)))
             }
-        },
-        [pitchbook_id] => (pitchbook_id, None),
-        _ => {
-            elog!("Invalid input format");
+            Some(("maxDaysSince", value)) => value
+                .parse::<u64>()
+                .ok()
+                .map(|value| thresholds.max_days_since = Some(value)),
+            Some(("minPoints6mo", value)) => value
+                .parse::<u64>()
+                .ok()
+                .map(|value| thresholds.min_points_6mo = Some(value)),
+            Some(("maxStdErrRatio", value)) => value
+                .parse::<f64>()
+                .ok()
+                .filter(|value| value.is_finite() && *value >= 0.0)
+                .map(|value| thresholds.max_std_err_ratio = Some(value)),
+            Some(_) => None,
+            None if option == "valuation" => {
+                full_valuation = true;
+                Some(())
+            }
+            None => match option {
+                "0" | "4" | "8" | "12" | "16" | "20" => {
+                    utc_hour = Some(option);
+                    Some(())
+                }
+                _ => {
+                    elog!("Invalid UTC hour format: should be one of 0,4,8,12,16,20");
+                    Process::error("Invalid UTC hour format".as_bytes());
+                    return Ok(());
+                }
+            },
+        };
+
+        if parsed.is_none() {
+            elog!(
+                "Invalid option: {option}. Expected maxDaysSince=<days>, minPoints6mo=<count>, maxStdErrRatio=<ratio> or date=<YYYY-MM-DD>"
+            );
+            Process::error("Invalid input format".as_bytes());
+            return Ok(());
+        }
+    }
+
+    if pitchbook_ids.len() > 1 {
+        // The full valuation payload is only available for a single project Id.
+        if full_valuation
+            || pitchbook_ids
+                .iter()
+                .any(|pitchbook_id| pitchbook_id.is_empty())
+        {
+            elog!("Invalid input format: {pitchbook_ids:?} with full valuation: {full_valuation}");
             Process::error("Invalid input format".as_bytes());
             return Ok(());
         }
-    };
+
+        return batch_execution(&pitchbook_ids, utc_hour, requested_date, &thresholds);
+    }
+    let pitchbook_id = pitchbook_ids[0];
 
     // Log the asset being fetched as part of the Execution Standard Out.
-    log!("Fetching price for: {pitchbook_id}, utc_hour: {utc_hour:?}");
+    log!(
+        "Fetching price for: {pitchbook_id}, utc_hour: {utc_hour:?}, date: {requested_date:?}, full valuation: {full_valuation}, {thresholds:?}"
+    );
+
+    let response_data = match fetch_market_data(pitchbook_id, utc_hour, requested_date) {
+        Ok(response_data) => response_data,
+        Err(error) => {
+            elog!("{error}");
+            Process::error("Error while fetching equity price".as_bytes());
+            return Ok(());
+        }
+    };
+
+    let price = response_data
+        .get("price")
+        .and_then(|price| price.as_f64())
+        .ok_or_else(|| anyhow::anyhow!("Price not found in response"))?;
+
+    // Refuse to report a price built from too few, too old or too uncertain data points.
+    if let Err(error) = check_data_quality(&response_data, price, &thresholds) {
+        elog!("Price of {pitchbook_id} doesn't meet the data quality thresholds: {error}");
+        Process::exit_with_result(
+            DATA_QUALITY_EXIT_CODE,
+            "Price data quality below threshold".as_bytes(),
+        );
+        return Ok(());
+    }
+
+    let price_lossless = (price * FIXED_POINT_SCALE) as u128;
+    log!("Fetched price: {price_lossless:?}");
+
+    let mut result: Vec<u8> = price_lossless.to_le_bytes().to_vec();
+
+    if full_valuation {
+        // The full valuation payload is revealed as six little-endian 128-bit values, in the order of
+        // the tally's `(price, valuation, stdErr, daysSince, points6mo, imbalance)` tuple.
+        let valuation = match valuation_fields(&response_data) {
+            Ok(valuation) => valuation,
+            Err(error) => {
+                elog!("Invalid valuation payload for {pitchbook_id}: {error}");
+                Process::error("Valuation field not found".as_bytes());
+                return Ok(());
+            }
+        };
+        log!("Fetched valuation: {valuation:?}");
+        result.extend(valuation.into_iter().flatten());
+    }
+
+    // A historical request is followed by the requested date and the `date` of the response as
+    // `YYYYMMDD` numbers, so the tally can make sure the price is as of the requested date.
+    if let Some((_, requested_date)) = requested_date {
+        let response_date = response_data["date"]
+            .as_str()
+            .and_then(parse_iso_date)
+            .unwrap_or_default();
+        log!("Requested date: {requested_date}, response date: {response_date}");
+        result.extend((requested_date as u128).to_le_bytes());
+        result.extend((response_date as u128).to_le_bytes());
+    }
+
+    // Report the successful result back to the SEDA network.
+    Process::success(&result);
+
+    Ok(())
+}
+
+/// Fetches the price of every project Id one after the other and reveals them as a JSON [`BatchReveal`],
+/// a project Id that fails to fetch or doesn't meet the thresholds is revealed as `null` instead of
+/// failing the whole request.
+#[cfg(any(feature = "testnet", feature = "mainnet"))]
+fn batch_execution(
+    pitchbook_ids: &[&str],
+    utc_hour: Option<&str>,
+    requested_date: Option<(&str, u32)>,
+    thresholds: &Thresholds,
+) -> Result<()> {
+    log!(
+        "Fetching prices for: {pitchbook_ids:?}, utc_hour: {utc_hour:?}, date: {requested_date:?}, {thresholds:?}"
+    );
+
+    let (prices, response_dates): (Vec<Option<u128>>, Vec<Option<u32>>) = pitchbook_ids
+        .iter()
+        .map(|pitchbook_id| {
+            match fetch_batch_price(pitchbook_id, utc_hour, requested_date, thresholds) {
+                Ok((price, response_date)) => {
+                    log!(
+                        "Fetched price of {pitchbook_id}: {price}, response date: {response_date:?}"
+                    );
+                    (Some(price), response_date)
+                }
+                Err(error) => {
+                    elog!("Failed to fetch the price of {pitchbook_id}: {error}");
+                    (None, None)
+                }
+            }
+        })
+        .unzip();
 
-    let url = if let Some(utc_hour) = utc_hour {
-        [API_URL, "pitchbookId=", pitchbook_id, "?utcHour=", utc_hour].concat()
-    } else {
-        [API_URL, "pitchbookId=", pitchbook_id].concat()
+    if prices.iter().all(Option::is_none) {
+        Process::error("Error while fetching equity price".as_bytes());
+        return Ok(());
+    }
+
+    // A historical request reveals the date of every response, so the tally can make sure the prices
+    // are as of the requested date.
+    let reveal = BatchReveal {
+        ids: pitchbook_ids.iter().map(ToString::to_string).collect(),
+        prices,
+        dates: if requested_date.is_some() {
+            response_dates
+        } else {
+            Vec::new()
+        },
     };
+
+    // Report the successful result back to the SEDA network.
+    Process::success(&serde_json::to_vec(&reveal)?);
+
+    Ok(())
+}
+
+/// Fetches the price of a single project Id of a batch, which must meet the thresholds, along with the
+/// `date` of the response as a `YYYYMMDD` number, if it has a valid one.
+#[cfg(any(feature = "testnet", feature = "mainnet"))]
+fn fetch_batch_price(
+    pitchbook_id: &str,
+    utc_hour: Option<&str>,
+    requested_date: Option<(&str, u32)>,
+    thresholds: &Thresholds,
+) -> Result<(u128, Option<u32>)> {
+    let response_data = fetch_market_data(pitchbook_id, utc_hour, requested_date)?;
+
+    let price = response_data
+        .get("price")
+        .and_then(|price| price.as_f64())
+        .ok_or_else(|| anyhow::anyhow!("Price not found in response"))?;
+
+    check_data_quality(&response_data, price, thresholds)?;
+
+    let response_date = response_data["date"].as_str().and_then(parse_iso_date);
+
+    Ok(((price * FIXED_POINT_SCALE) as u128, response_date))
+}
+
+/// Fetches the market price response of a project Id through the Data Proxy.
+#[cfg(any(feature = "testnet", feature = "mainnet"))]
+fn fetch_market_data(
+    pitchbook_id: &str,
+    utc_hour: Option<&str>,
+    requested_date: Option<(&str, u32)>,
+) -> Result<serde_json::Value> {
+    let mut url = UrlBuilder::new(API_URL).query("pitchbookId", pitchbook_id);
+    if let Some(utc_hour) = utc_hour {
+        url = url.query("utcHour", utc_hour);
+    }
+    if let Some((date, _)) = requested_date {
+        url = url.query("date", date);
+    }
+    let url = url.build();
     let response = proxy_http_fetch(
         url,
         Some(PROXY_PUBLIC_KEY.to_string()),
@@ -86,28 +314,127 @@ pub fn execution_phase() -> Result<()> {
 
     // Check if the HTTP request was successfully fulfilled or not.
     if !response.is_ok() {
-        elog!(
+        return Err(anyhow::anyhow!(
             "HTTP Response was rejected: {} - {}",
             response.status,
             String::from_utf8(response.bytes)?
-        );
-        Process::error("Error while fetching equity price".as_bytes());
-        return Ok(());
+        ));
     }
 
     // Parse the API response as defined earlier.
-    let response_data = serde_json::from_slice::<serde_json::Value>(&response.bytes)?;
+    Ok(serde_json::from_slice::<serde_json::Value>(
+        &response.bytes,
+    )?)
+}
 
-    let price = response_data
-        .get("price")
-        .and_then(|price| price.as_f64())
-        .ok_or_else(|| anyhow::anyhow!("Price not found in response"))?;
+/// Checks the data behind a price against the thresholds, a field needed by a threshold must be present.
+#[cfg(any(feature = "testnet", feature = "mainnet"))]
+fn check_data_quality(
+    response_data: &serde_json::Value,
+    price: f64,
+    thresholds: &Thresholds,
+) -> Result<()> {
+    if let Some(max_days_since) = thresholds.max_days_since {
+        let days_since = response_data["daysSinceLastDataPoint"].as_u64();
+        if days_since.is_none_or(|days_since| days_since > max_days_since) {
+            return Err(anyhow::anyhow!(
+                "last data point was {days_since:?} days ago, more than {max_days_since}"
+            ));
+        }
+    }
 
-    let price_lossless = (price * 10_000.0) as u128;
-    log!("Fetched price: {price_lossless:?}");
+    if let Some(min_points_6mo) = thresholds.min_points_6mo {
+        let points_6mo = response_data["numberOfPoints6mo"].as_u64();
+        if points_6mo.is_none_or(|points_6mo| points_6mo < min_points_6mo) {
+            return Err(anyhow::anyhow!(
+                "{points_6mo:?} data points in 6 months, fewer than {min_points_6mo}"
+            ));
+        }
+    }
 
-    // Report the successful result back to the SEDA network.
-    Process::success(&price_lossless.to_le_bytes());
+    if let Some(max_std_err_ratio) = thresholds.max_std_err_ratio {
+        let std_err_ratio = response_data["priceStandardError"]
+            .as_f64()
+            .map(|std_err| std_err / price)
+            .filter(|ratio| ratio.is_finite());
+        if !std_err_ratio.is_some_and(|ratio| ratio <= max_std_err_ratio) {
+            return Err(anyhow::anyhow!(
+                "standard error is {std_err_ratio:?} of the price, more than {max_std_err_ratio}"
+            ));
+        }
+    }
 
     Ok(())
 }
+
+/// Extracts the valuation, price standard error, days since the last data point, number of data
+/// points in the last 6 months and order imbalance (`bidContribution - offerContribution`) from the
+/// response, each as the little-endian bytes of a 128-bit value. All of them are required.
+#[cfg(any(feature = "testnet", feature = "mainnet"))]
+fn valuation_fields(response_data: &serde_json::Value) -> Result<[[u8; 16]; 5]> {
+    let number = |pointer: &str| {
+        response_data
+            .pointer(pointer)
+            .and_then(|value| value.as_f64())
+            .filter(|value| value.is_finite())
+            .ok_or_else(|| anyhow::anyhow!("{pointer} not found in response or is invalid"))
+    };
+    let count = |pointer: &str| {
+        response_data
+            .pointer(pointer)
+            .and_then(|value| value.as_u64())
+            .filter(|value| *value <= u32::MAX.into())
+            .ok_or_else(|| anyhow::anyhow!("{pointer} not found in response or is invalid"))
+    };
+
+    let valuation = (number("/estimatedValuation")? * FIXED_POINT_SCALE) as u128;
+    let std_err = (number("/priceStandardError")? * FIXED_POINT_SCALE) as u128;
+    let days_since = count("/daysSinceLastDataPoint")? as u128;
+    let points_6mo = count("/numberOfPoints6mo")? as u128;
+    let imbalance = ((number("/orderImbalance/bidContribution")?
+        - number("/orderImbalance/offerContribution")?)
+        * FIXED_POINT_SCALE) as i128;
+
+    Ok([
+        valuation.to_le_bytes(),
+        std_err.to_le_bytes(),
+        days_since.to_le_bytes(),
+        points_6mo.to_le_bytes(),
+        imbalance.to_le_bytes(),
+    ])
+}
+
+/// Parses a strict ISO 8601 calendar date (`YYYY-MM-DD`) into a `YYYYMMDD` number, rejecting any
+/// other format and dates that don't exist (e.g., `2023-02-29`).
+pub fn parse_iso_date(date: &str) -> Option<u32> {
+    let (year, month_day) = date.split_once('-')?;
+    let (month, day) = month_day.split_once('-')?;
+    if year.len() != 4
+        || month.len() != 2
+        || day.len() != 2
+        || ![year, month, day]
+            .iter()
+            .all(|part| part.bytes().all(|digit| digit.is_ascii_digit()))
+    {
+        return None;
+    }
+
+    let (year, month, day) = (
+        year.parse::<u32>().ok()?,
+        month.parse::<u32>().ok()?,
+        day.parse::<u32>().ok()?,
+    );
+    let is_leap_year =
+        year.is_multiple_of(4) && (!year.is_multiple_of(100) || year.is_multiple_of(400));
+    let days_in_month = match month {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        2 if is_leap_year => 29,
+        2 => 28,
+        _ => return None,
+    };
+
+    (1..=days_in_month)
+        .contains(&day)
+        .then_some(year * 10_000 + month * 100 + day)
+}
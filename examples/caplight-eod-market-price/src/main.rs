@@ -5,6 +5,19 @@ use tally_phase::tally_phase;
 mod execution_phase;
 mod tally_phase;
 
+/// The reveal of a batch of project Ids.
+#[derive(serde::Serialize, serde::Deserialize)]
+struct BatchReveal {
+    /// The project Ids in input order.
+    ids: Vec<String>,
+    /// The prices in input order with 4 decimals, `None` for a project Id that failed.
+    prices: Vec<Option<u128>>,
+    /// For a historical request, the `date` of the response of every project Id as a `YYYYMMDD` number,
+    /// `None` for a project Id that failed or whose response has no valid date. Empty otherwise.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    dates: Vec<Option<u32>>,
+}
+
 #[oracle_program]
 impl CaplightEodMarketPrice {
     fn execute() {
@@ -3,22 +3,164 @@ use anyhow::Result;
 use ethabi::{Token, ethereum_types::U256};
 use seda_sdk_rs::{Process, elog, get_reveals, log};
 
+use crate::{BatchReveal, execution_phase::parse_iso_date};
+
+/// The size of a reveal with the full valuation payload: six little-endian 128-bit values.
+const VALUATION_REVEAL_SIZE: usize = 6 * size_of::<u128>();
+
+/// The size of the requested and response dates that follow the payload of a historical request.
+const DATES_SIZE: usize = 2 * size_of::<u128>();
+
+/// The full valuation payload of a company, with the price, valuation, standard error and order
+/// imbalance in 4 decimal fixed point.
+#[derive(Debug, Clone, Copy)]
+struct Valuation {
+    price: u128,
+    valuation: u128,
+    std_err: u128,
+    days_since: u128,
+    points_6mo: u128,
+    imbalance: i128,
+}
+
+/// What an executor revealed, depending on whether the full valuation payload or a batch was requested.
+enum Reveal {
+    Price(u128),
+    Valuation(Valuation),
+    Batch(BatchReveal),
+}
+
+/// What a reveal was made for, along with the project Ids of a batch.
+#[derive(Debug, Clone, PartialEq)]
+enum RevealKind {
+    Price,
+    Valuation,
+    Batch(Vec<String>),
+}
+
+impl Reveal {
+    fn kind(&self) -> RevealKind {
+        match self {
+            Reveal::Price(_) => RevealKind::Price,
+            Reveal::Valuation(_) => RevealKind::Valuation,
+            Reveal::Batch(batch) => RevealKind::Batch(batch.ids.clone()),
+        }
+    }
+}
+
+/// The dates of a historical request as `YYYYMMDD` numbers: the requested one and the `date` of the response.
+#[derive(Debug, Clone, Copy)]
+struct RevealedDates {
+    requested: u128,
+    response: u128,
+}
+
 pub fn tally_phase() -> Result<()> {
+    // Tally inputs are a comma-separated list of options, "date=<YYYY-MM-DD>" being the historical date
+    // given to the execution phase, which the prices have to be as of.
+    let tally_inputs = String::from_utf8(Process::get_inputs()).unwrap_or_default();
+    let mut requested_date: Option<u128> = None;
+    for option in tally_inputs.split(',') {
+        if let Some(("date", value)) = option.split_once('=') {
+            let Some(date) = parse_iso_date(value) else {
+                elog!("Invalid date in the tally inputs: {value}");
+                Process::error("Invalid tally inputs".as_bytes());
+                return Ok(());
+            };
+            requested_date = Some(date.into());
+        }
+    }
+
     // Retrieve consensus reveals from the tally phase.
     let reveals = get_reveals()?;
-    let mut revealed_prices = Vec::with_capacity(reveals.len());
+    let mut parsed: Vec<Reveal> = Vec::with_capacity(reveals.len());
+    let mut mismatching_dates = false;
 
-    // Iterate over each reveal, parse its content as an unsigned integer (u128), and store it in the prices array.
+    // Iterate over each reveal, parse its content as an unsigned integer (u128), as the full valuation
+    // payload or as a batch, and keep the ones as of the requested date.
     for reveal in reveals {
-        let price = match reveal.body.reveal.as_slice().try_into() {
-            Ok(price) => u128::from_le_bytes(price),
+        // A batch of project Ids is revealed as JSON rather than little-endian values.
+        if let Ok(mut batch) = serde_json::from_slice::<BatchReveal>(&reveal.body.reveal) {
+            // A price of a batch can only be used if it is as of the date in the tally inputs, any other
+            // price of the reveal still counts.
+            if let Some(requested_date) = requested_date {
+                for (index, (id, price)) in batch.ids.iter().zip(&mut batch.prices).enumerate() {
+                    let response_date = batch.dates.get(index).copied().flatten();
+                    if price.is_some() && response_date.map(u128::from) != Some(requested_date) {
+                        elog!(
+                            "Ignoring the price of {id} as of {response_date:?} rather than {requested_date}"
+                        );
+                        *price = None;
+                    }
+                }
+            }
+
+            if requested_date.is_none() && !batch.dates.is_empty() {
+                elog!("Ignoring historical batch reveal without a date in the tally inputs");
+            } else if batch.prices.len() != batch.ids.len() {
+                elog!(
+                    "Ignoring batch reveal with {} ids but {} prices",
+                    batch.ids.len(),
+                    batch.prices.len()
+                );
+            } else {
+                parsed.push(Reveal::Batch(batch));
+            }
+            continue;
+        }
+
+        let (reveal, dates) = match parse_reveal(&reveal.body.reveal) {
+            Ok(reveal) => reveal,
             Err(err) => {
                 elog!("Failed to parse revealed prices: {err}");
                 continue;
             }
         };
 
-        revealed_prices.push(price);
+        // A price as of another date than the one in the tally inputs can't be used to settle anything,
+        // so such a reveal is dropped and the others still make up the result.
+        let reveal_dates = dates.map(|dates| (dates.requested, dates.response));
+        if reveal_dates != requested_date.map(|date| (date, date)) {
+            elog!("Ignoring reveal with dates {dates:?} for the requested date {requested_date:?}");
+            mismatching_dates = true;
+            continue;
+        }
+
+        parsed.push(reveal);
+    }
+
+    // A price, a valuation or a batch of the same project Ids is expected from every executor. Settle on the
+    // payload the majority revealed, as any executor could be the one whose reveal is read first.
+    let expected = most_common(&parsed.iter().map(Reveal::kind).collect::<Vec<_>>());
+    let mut revealed_prices = Vec::with_capacity(parsed.len());
+    let mut revealed_valuations = Vec::with_capacity(parsed.len());
+    let mut revealed_batches: Vec<BatchReveal> = Vec::with_capacity(parsed.len());
+    for reveal in parsed {
+        let kind = reveal.kind();
+        if expected.as_ref() != Some(&kind) {
+            elog!("Ignoring reveal with a mismatching payload: {kind:?}");
+            continue;
+        }
+
+        match reveal {
+            Reveal::Price(price) => revealed_prices.push(price),
+            Reveal::Valuation(valuation) => revealed_valuations.push(valuation),
+            Reveal::Batch(batch) => revealed_batches.push(batch),
+        }
+    }
+
+    if !revealed_valuations.is_empty() {
+        return tally_valuations(&revealed_valuations);
+    }
+
+    if !revealed_batches.is_empty() {
+        return tally_batches(&revealed_batches);
+    }
+
+    // Every reveal was dropped for not being as of the requested date.
+    if revealed_prices.is_empty() && mismatching_dates {
+        Process::error("Response date doesn't match the requested date".as_bytes());
+        return Ok(());
     }
 
     // If no valid prices were revealed, report an error indicating no consensus.
@@ -50,6 +192,190 @@ pub fn tally_phase() -> Result<()> {
     Ok(())
 }
 
+/// Reports the median of every field of the revealed valuation payloads.
+fn tally_valuations(valuations: &[Valuation]) -> Result<()> {
+    let field =
+        |get: fn(&Valuation) -> u128| median(&valuations.iter().map(get).collect::<Vec<_>>());
+    let final_valuation = Valuation {
+        price: field(|valuation| valuation.price),
+        valuation: field(|valuation| valuation.valuation),
+        std_err: field(|valuation| valuation.std_err),
+        // Both counts are at most a u32 in every reveal, so their medians are as well.
+        days_since: field(|valuation| valuation.days_since),
+        points_6mo: field(|valuation| valuation.points_6mo),
+        imbalance: median_signed(
+            &valuations
+                .iter()
+                .map(|valuation| valuation.imbalance)
+                .collect::<Vec<_>>(),
+        ),
+    };
+    log!("Final median valuation: {final_valuation:?}");
+
+    #[cfg(feature = "eth-result")]
+    {
+        // Encode the final median valuation as the EVM tuple
+        // `(uint256 price, uint256 valuation, uint256 stdErr, uint32 daysSince, uint32 points6mo, int256 imbalance)`.
+        let result = ethabi::encode(&[
+            Token::Uint(U256::from(final_valuation.price)),
+            Token::Uint(U256::from(final_valuation.valuation)),
+            Token::Uint(U256::from(final_valuation.std_err)),
+            Token::Uint(U256::from(final_valuation.days_since)),
+            Token::Uint(U256::from(final_valuation.points_6mo)),
+            Token::Int(to_int256(final_valuation.imbalance)),
+        ]);
+        // Report the successful result in the tally phase.
+        Process::success(&result);
+    }
+
+    #[cfg(feature = "str-result")]
+    {
+        // Convert the fixed point values back to f64 for string representation.
+        let result = serde_json::json!({
+            "price": final_valuation.price as f64 / 10_000.0,
+            "valuation": final_valuation.valuation as f64 / 10_000.0,
+            "stdErr": final_valuation.std_err as f64 / 10_000.0,
+            "daysSince": final_valuation.days_since,
+            "points6mo": final_valuation.points_6mo,
+            "imbalance": final_valuation.imbalance as f64 / 10_000.0,
+        });
+        // Report the successful result in the tally phase.
+        Process::success(result.to_string().as_bytes());
+    }
+
+    Ok(())
+}
+
+/// Reports the median price of every project Id of a batch, over the reveals that have a price for it.
+fn tally_batches(batches: &[BatchReveal]) -> Result<()> {
+    let ids = &batches[0].ids;
+    let final_prices: Vec<Option<u128>> = (0..ids.len())
+        .map(|index| {
+            let prices: Vec<u128> = batches
+                .iter()
+                .filter_map(|batch| batch.prices[index])
+                .collect();
+            (!prices.is_empty()).then(|| median(&prices))
+        })
+        .collect();
+    log!("Final median prices: {final_prices:?}");
+
+    if final_prices.iter().all(Option::is_none) {
+        Process::error("No consensus among revealed results".as_bytes());
+        return Ok(());
+    }
+
+    for (id, _) in ids
+        .iter()
+        .zip(&final_prices)
+        .filter(|(_, price)| price.is_none())
+    {
+        elog!("No price revealed for {id}");
+    }
+
+    #[cfg(feature = "eth-result")]
+    {
+        // Encode the final median prices as the EVM tuple `(uint256[] prices, bool[] ok)` in input order,
+        // with a price of 0 and `ok` false for a project Id no executor got a price for.
+        let result = ethabi::encode(&[
+            Token::Array(
+                final_prices
+                    .iter()
+                    .map(|price| Token::Uint(U256::from(price.unwrap_or_default())))
+                    .collect(),
+            ),
+            Token::Array(
+                final_prices
+                    .iter()
+                    .map(|price| Token::Bool(price.is_some()))
+                    .collect(),
+            ),
+        ]);
+        // Report the successful result in the tally phase.
+        Process::success(&result);
+    }
+
+    #[cfg(feature = "str-result")]
+    {
+        // Map every project Id to its price as f64, or `null` if no executor got a price for it.
+        let result: serde_json::Map<String, serde_json::Value> = ids
+            .iter()
+            .zip(&final_prices)
+            .map(|(id, price)| {
+                let price = price.map(|price| price as f64 / 10_000.0);
+                (id.clone(), serde_json::json!(price))
+            })
+            .collect();
+        // Report the successful result in the tally phase.
+        Process::success(serde_json::Value::Object(result).to_string().as_bytes());
+    }
+
+    Ok(())
+}
+
+/// Parses a reveal as either a single price or the full valuation payload, followed by the dates of a
+/// historical request, if any.
+fn parse_reveal(reveal: &[u8]) -> Result<(Reveal, Option<RevealedDates>)> {
+    let has_dates = reveal.len() == size_of::<u128>() + DATES_SIZE
+        || reveal.len() == VALUATION_REVEAL_SIZE + DATES_SIZE;
+    if !has_dates {
+        return Ok((parse_payload(reveal)?, None));
+    }
+
+    let (payload, dates) = reveal.split_at(reveal.len() - DATES_SIZE);
+    let (requested, response) = dates.split_at(size_of::<u128>());
+    let dates = RevealedDates {
+        requested: u128::from_le_bytes(requested.try_into()?),
+        response: u128::from_le_bytes(response.try_into()?),
+    };
+
+    Ok((parse_payload(payload)?, Some(dates)))
+}
+
+/// Parses a payload as either a single price or the full valuation payload.
+fn parse_payload(reveal: &[u8]) -> Result<Reveal> {
+    if let Ok(price) = reveal.try_into() {
+        return Ok(Reveal::Price(u128::from_le_bytes(price)));
+    }
+
+    if reveal.len() != VALUATION_REVEAL_SIZE {
+        return Err(anyhow::anyhow!(
+            "expected {} or {VALUATION_REVEAL_SIZE} bytes, got {}",
+            size_of::<u128>(),
+            reveal.len()
+        ));
+    }
+
+    let mut values = reveal
+        .chunks_exact(size_of::<u128>())
+        .map(|chunk| <[u8; 16]>::try_from(chunk).expect("chunk is 16 bytes"));
+    let mut next = || values.next().unwrap_or_default();
+    let valuation = Valuation {
+        price: u128::from_le_bytes(next()),
+        valuation: u128::from_le_bytes(next()),
+        std_err: u128::from_le_bytes(next()),
+        days_since: u128::from_le_bytes(next()),
+        points_6mo: u128::from_le_bytes(next()),
+        imbalance: i128::from_le_bytes(next()),
+    };
+
+    // The counts are encoded as `uint32`.
+    if valuation.days_since > u32::MAX.into() || valuation.points_6mo > u32::MAX.into() {
+        return Err(anyhow::anyhow!("counts out of range in {valuation:?}"));
+    }
+
+    Ok(Reveal::Valuation(valuation))
+}
+
+/// Finds the value most reveals agree on, the earliest one on a tie.
+fn most_common<T: Clone + PartialEq>(values: &[T]) -> Option<T> {
+    values
+        .iter()
+        .rev()
+        .max_by_key(|value| values.iter().filter(|other| other == value).count())
+        .cloned()
+}
+
 /// Finds the median of a list of prices per price report.
 fn median(data: &[u128]) -> u128 {
     let m = data.len();
@@ -62,7 +388,7 @@ fn median(data: &[u128]) -> u128 {
     let mut sorted_data = data.to_vec();
     sorted_data.sort_unstable();
 
-    if m % 2 == 0 {
+    if m.is_multiple_of(2) {
         // safe average of two u128s without overflow
         let a = sorted_data[m / 2 - 1];
         let b = sorted_data[m / 2];
@@ -75,3 +401,28 @@ fn median(data: &[u128]) -> u128 {
     // convert to Token::Uint for encoding
     //
 }
+
+/// Finds the median of a list of signed values, such as the order imbalances.
+fn median_signed(data: &[i128]) -> i128 {
+    let m = data.len();
+
+    let mut sorted_data = data.to_vec();
+    sorted_data.sort_unstable();
+
+    if m.is_multiple_of(2) {
+        sorted_data[m / 2 - 1].midpoint(sorted_data[m / 2])
+    } else {
+        sorted_data[m / 2]
+    }
+}
+
+/// Converts a signed integer into its two's complement `int256` representation.
+#[cfg(feature = "eth-result")]
+fn to_int256(value: i128) -> U256 {
+    if value >= 0 {
+        U256::from(value as u128)
+    } else {
+        // -x == !x + 1 in two's complement, `!` on a U256 flips all 256 bits.
+        !U256::from(value.unsigned_abs()) + U256::one()
+    }
+}
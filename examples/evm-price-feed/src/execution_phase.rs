@@ -1,12 +1,15 @@
 use anyhow::Result;
 use seda_sdk_rs::{Process, elog, log};
 
+use crate::PriceReveal;
+
 pub fn execution_phase() -> Result<()> {
     // Retrieve the input parameters for the data request (DR).
-    // Expected to be in the format "symbolA-symbolB,SymbolC-symboldD,..." (e.g., "BTC-USD,ETH-USD").
+    // Expected to be an ABI-encoded `(string pair, uint8 decimals)[]`,
+    // where each pair is in the format "symbolA-symbolB" (e.g., [("BTC-USD", 6), ("ETH-USD", 8)]).
     let dr_inputs = ethabi::decode(
         &[ethabi::ParamType::Array(Box::new(
-            ethabi::ParamType::String,
+            ethabi::ParamType::Tuple(vec![ethabi::ParamType::String, ethabi::ParamType::Uint(8)]),
         ))],
         &Process::get_inputs(),
     );
@@ -25,19 +28,40 @@ pub fn execution_phase() -> Result<()> {
     let dr_inputs = match &dr_inputs[0] {
         ethabi::Token::Array(tokens) => tokens,
         _ => {
-            elog!("Expected array of strings");
+            elog!("Expected array of (string, uint8) tuples");
             Process::error("Invalid input format".as_bytes());
             return Ok(());
         }
     };
 
-    // One-pass: validate pair and fetch immediately; exit on first error
-    let mut prices = Vec::new();
+    // One-pass: validate pair and fetch immediately.
+    // A pair that fails to fetch is reported as `null` so the other pairs can still be used.
+    let mut prices: Vec<Option<i128>> = Vec::with_capacity(dr_inputs.len());
+    let mut decimals_per_pair: Vec<u8> = Vec::with_capacity(dr_inputs.len());
+    let mut observed_at: Option<u64> = None;
     for token in dr_inputs {
-        let pair = match token {
-            ethabi::Token::String(s) => s,
+        let (pair, decimals) = match token {
+            ethabi::Token::Tuple(fields) => match fields.as_slice() {
+                [ethabi::Token::String(pair), ethabi::Token::Uint(decimals)] => {
+                    // The ABI decoder doesn't bound a `uint8`, so larger values are rejected rather than wrapped.
+                    let Ok(decimals) = u8::try_from(*decimals) else {
+                        elog!(
+                            "Invalid decimals for {pair}: {decimals}. Expected at most {}",
+                            u8::MAX
+                        );
+                        Process::error(format!("Invalid decimals for {pair}").as_bytes());
+                        return Ok(());
+                    };
+                    (pair, decimals)
+                }
+                _ => {
+                    elog!("Expected (string, uint8) tuple, got: {:?}", fields);
+                    Process::error("Invalid token type".as_bytes());
+                    return Ok(());
+                }
+            },
             _ => {
-                elog!("Expected string token, got: {:?}", token);
+                elog!("Expected tuple token, got: {:?}", token);
                 Process::error("Invalid token type".as_bytes());
                 return Ok(());
             }
@@ -50,8 +74,14 @@ pub fn execution_phase() -> Result<()> {
             return Ok(());
         }
 
-        match crate::feeds::binance::fetch_token_price(parts[0], parts[1], 6) {
-            Ok(price) => prices.push(price),
+        decimals_per_pair.push(decimals);
+        match crate::feeds::binance::fetch_token_price(parts[0], parts[1], decimals.into()) {
+            Ok((price, timestamp_ms)) => {
+                prices.push(Some(price));
+                // The batch is only as fresh as its oldest price.
+                let timestamp = timestamp_ms / 1_000;
+                observed_at = Some(observed_at.map_or(timestamp, |oldest| oldest.min(timestamp)));
+            }
             Err(error) => {
                 elog!(
                     "Failed to fetch price for {}-{}: {}",
@@ -59,15 +89,28 @@ pub fn execution_phase() -> Result<()> {
                     parts[1],
                     error
                 );
-                Process::error("Failed to fetch prices".as_bytes());
-                return Ok(());
+                prices.push(None);
             }
         }
     }
 
+    // If every pair failed there is nothing worth reporting.
+    let Some(observed_at) = observed_at else {
+        Process::error("Failed to fetch prices".as_bytes());
+        return Ok(());
+    };
+
     // Report the successful result back to the SEDA network
-    log!("Successfully fetched {} prices: {:?}", prices.len(), prices);
-    let result = serde_json::to_vec(&prices)?;
+    log!(
+        "Successfully fetched {} prices: {:?}, observed at: {observed_at}",
+        prices.len(),
+        prices
+    );
+    let result = serde_json::to_vec(&PriceReveal {
+        prices,
+        decimals: decimals_per_pair,
+        observed_at,
+    })?;
     Process::success(&result);
 
     Ok(())
@@ -1,11 +1,15 @@
 use anyhow::{Result, anyhow};
 use serde::Deserialize;
+use shared::UrlBuilder;
 
-const API_URL: &str = "https://api.binance.com/api/v3/ticker/price?symbol=";
+const API_URL: &str = "https://api.binance.com/api/v3/ticker/24hr";
 
 #[derive(Deserialize)]
-struct StringPriceResponse {
-    price: String,
+#[serde(rename_all = "camelCase")]
+struct MiniTickerResponse {
+    last_price: String,
+    /// The epoch timestamp in milliseconds the ticker was last updated at.
+    close_time: u64,
 }
 
 /// Fetch the current price for a trading pair from the Binance API.
@@ -14,13 +18,17 @@ struct StringPriceResponse {
 /// - symbol_b: quote asset (e.g., "USDT")
 /// - decimals: desired decimal precision for the returned integer
 ///
-/// Returns the price as u128 with the specified decimal precision.
-pub fn fetch_token_price(symbol_a: &str, symbol_b: &str, decimals: u32) -> Result<u128> {
+/// Returns the price as a signed i128 with the specified decimal precision,
+/// along with the epoch timestamp in milliseconds Binance observed it at.
+pub fn fetch_token_price(symbol_a: &str, symbol_b: &str, decimals: u32) -> Result<(i128, u64)> {
     if symbol_a.is_empty() || symbol_b.is_empty() {
         return Err(anyhow!("Invalid symbols: '{symbol_a}' and '{symbol_b}'"));
     }
 
-    let url = format!("{API_URL}{symbol_a}{symbol_b}");
+    let url = UrlBuilder::new(API_URL)
+        .query("type", "MINI")
+        .query("symbol", &[symbol_a, symbol_b].concat())
+        .build();
     let response = seda_sdk_rs::http_fetch(url, None);
 
     if !response.is_ok() {
@@ -31,34 +39,46 @@ pub fn fetch_token_price(symbol_a: &str, symbol_b: &str, decimals: u32) -> Resul
         ));
     }
 
-    let data: StringPriceResponse = serde_json::from_slice(&response.bytes)?;
-    make_price(&data.price, decimals)
+    let data: MiniTickerResponse = serde_json::from_slice(&response.bytes)?;
+    Ok((make_price(&data.last_price, decimals)?, data.close_time))
 }
 
-/// Convert a decimal price string (e.g., "1234.5678") into a u128 with `decimals` precision.
-/// - Truncates extra precision if the input has more decimals than requested.
+/// Convert a signed decimal price string (e.g., "1234.5678" or "-0.0001") into an i128 with `decimals` precision.
+/// - Truncates extra precision (towards zero) if the input has more decimals than requested.
 /// - Multiplies (with overflow checks) if the input has fewer decimals than requested.
-fn make_price(price_str: &str, decimals: u32) -> Result<u128> {
-    let (int_part, frac_part) = match price_str.split_once('.') {
+fn make_price(price_str: &str, decimals: u32) -> Result<i128> {
+    let (negative, unsigned_str) = match price_str.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, price_str),
+    };
+
+    let (int_part, frac_part) = match unsigned_str.split_once('.') {
         Some((i, f)) => (i, f),
-        None => (price_str, ""),
+        None => (unsigned_str, ""),
     };
 
     let digits = format!("{int_part}{frac_part}");
     if digits.is_empty() {
         return Err(anyhow!("Empty price string"));
     }
+    // The sign was already stripped, so any other sign is malformed (e.g., "--1").
+    if digits.starts_with(['-', '+']) {
+        return Err(anyhow!("Failed to parse price: {price_str}"));
+    }
 
     let price_int = digits
-        .parse::<u128>()
+        .parse::<i128>()
         .map_err(|_| anyhow!("Failed to parse price: {price_str}"))?;
 
     let decimal_places = frac_part.len() as u32;
 
     let result = if decimal_places >= decimals {
-        price_int / 10_u128.pow(decimal_places - decimals)
+        let div = 10_i128
+            .checked_pow(decimal_places - decimals)
+            .ok_or_else(|| anyhow!("Price conversion overflow"))?;
+        price_int / div
     } else {
-        let mul = 10_u128
+        let mul = 10_i128
             .checked_pow(decimals - decimal_places)
             .ok_or_else(|| anyhow!("Price conversion overflow"))?;
         price_int
@@ -66,5 +86,5 @@ fn make_price(price_str: &str, decimals: u32) -> Result<u128> {
             .ok_or_else(|| anyhow!("Price conversion overflow"))?
     };
 
-    Ok(result)
+    Ok(if negative { -result } else { result })
 }
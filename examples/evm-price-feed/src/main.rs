@@ -6,6 +6,16 @@ mod execution_phase;
 mod feeds;
 mod tally_phase;
 
+#[derive(serde::Serialize, serde::Deserialize)]
+struct PriceReveal {
+    /// The prices in input order, `None` for a pair that failed to fetch.
+    prices: Vec<Option<i128>>,
+    /// The decimals each price is reported with.
+    decimals: Vec<u8>,
+    /// The oldest exchange timestamp, in epoch seconds, among the fetched prices.
+    observed_at: u64,
+}
+
 #[oracle_program]
 impl PriceFeed {
     fn execute() {
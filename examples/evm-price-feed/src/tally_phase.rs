@@ -2,26 +2,74 @@ use anyhow::Result;
 use ethabi::{Token, ethereum_types::U256};
 use seda_sdk_rs::{Process, elog, get_reveals, log};
 
+use crate::PriceReveal;
+
+/// The ABI layout the tally result is encoded with, selected by the tally inputs.
+enum OutputLayout {
+    /// `(int256[] prices, bool[] ok)`
+    Prices,
+    /// `(int256[] prices, bool[] ok, uint64 observedAt, uint8 decimals, uint16 reveals)`
+    Timestamped,
+}
+
 /// Executes the tally phase within the SEDA network.
 /// This phase aggregates the results (e.g., price data) revealed during the execution phase,
 /// calculates the median value, and submits it as the final result.
 /// Note: The number of reveals depends on the replication factor set in the data request parameters.
 pub fn tally_phase() -> Result<()> {
-    // Retrieve consensus reveals from the tally phase.
-    let reveals = get_reveals()?;
-    let mut revealed_prices: Vec<Vec<u128>> = Vec::with_capacity(reveals.len());
+    // Tally inputs of "timestamped" select the timestamped layout, anything else keeps the default one.
+    let layout = match Process::get_inputs().as_slice() {
+        b"timestamped" => OutputLayout::Timestamped,
+        _ => OutputLayout::Prices,
+    };
+
+    // Retrieve consensus reveals from the tally phase, and parse their content as a `PriceReveal` of optional
+    // signed integers (i128), where `None` marks a pair the executor failed to fetch. Every pair has a price
+    // and decimals, so a reveal with as many decimals as most reveals always lines up with their prices.
+    let reveals: Vec<PriceReveal> = get_reveals()?
+        .iter()
+        .filter_map(
+            |reveal| match serde_json::from_slice::<PriceReveal>(&reveal.body.reveal) {
+                Ok(reveal) if reveal.prices.len() == reveal.decimals.len() => Some(reveal),
+                Ok(reveal) => {
+                    elog!(
+                        "Ignoring reveal with {} prices but {} decimals",
+                        reveal.prices.len(),
+                        reveal.decimals.len()
+                    );
+                    None
+                }
+                Err(err) => {
+                    elog!("Failed to parse revealed prices: {err}");
+                    None
+                }
+            },
+        )
+        .collect();
+    let mut revealed_prices: Vec<Vec<Option<i128>>> = Vec::with_capacity(reveals.len());
+    let mut revealed_timestamps: Vec<u64> = Vec::with_capacity(reveals.len());
 
-    // Iterate over each reveal, parse its content as an unsigned integer (u128), and store it in the prices array.
+    // All executors ran with the same inputs, so the decimals most of them revealed are the expected ones,
+    // rather than letting a single executor decide which reveals are kept.
+    let decimals = most_common(
+        &reveals
+            .iter()
+            .map(|reveal| reveal.decimals.clone())
+            .collect::<Vec<_>>(),
+    );
+
+    // Store the prices of every reveal with the expected decimals in the prices array.
     for reveal in reveals {
-        let prices = match serde_json::from_slice::<Vec<u128>>(&reveal.body.reveal) {
-            Ok(prices) => prices,
-            Err(err) => {
-                elog!("Failed to parse revealed prices: {err}");
-                continue;
-            }
-        };
+        if decimals.as_ref() != Some(&reveal.decimals) {
+            elog!(
+                "Ignoring reveal with mismatching decimals: {:?}",
+                reveal.decimals
+            );
+            continue;
+        }
 
-        revealed_prices.push(prices);
+        revealed_prices.push(reveal.prices);
+        revealed_timestamps.push(reveal.observed_at);
     }
 
     if revealed_prices.is_empty() {
@@ -31,29 +79,87 @@ pub fn tally_phase() -> Result<()> {
     }
 
     // If there are valid prices revealed, calculate the median price from price reports.
-    let final_prices = median_each_asset(&revealed_prices)?;
-    log!("Final median prices: {final_prices:?}",);
+    let (final_prices, ok) = median_each_asset(&revealed_prices)?;
+    log!("Final median prices: {final_prices:?}, ok: {ok:?}");
+
+    // Encode final prices and their status flags as ABI-encoded bytes for EVM contract use.
+    let encoded_result = match layout {
+        OutputLayout::Prices => ethabi::encode(&[Token::Array(final_prices), Token::Array(ok)]),
+        OutputLayout::Timestamped => {
+            // A single decimals value can only describe the prices if all pairs share it.
+            let decimals = decimals.unwrap_or_default();
+            let shared_decimals = match decimals.split_first() {
+                Some((&first, rest)) if rest.iter().all(|&other| other == first) => first,
+                _ => {
+                    elog!(
+                        "Timestamped layout requires the same decimals for all pairs: {decimals:?}"
+                    );
+                    Process::error("Mixed decimals in timestamped layout".as_bytes());
+                    return Ok(());
+                }
+            };
 
-    // Encode final prices as ABI-encoded bytes for EVM contract use
-    let encoded_result = ethabi::encode(&[Token::Array(final_prices)]);
+            revealed_timestamps.sort();
+            let observed_at = median_sorted_timestamps(&revealed_timestamps);
+            log!(
+                "Observed at: {observed_at}, decimals: {shared_decimals}, reveals: {}",
+                revealed_prices.len()
+            );
+
+            let Ok(reveal_count) = u16::try_from(revealed_prices.len()) else {
+                elog!("Too many reveals for a uint16: {}", revealed_prices.len());
+                Process::error("Too many reveals".as_bytes());
+                return Ok(());
+            };
+
+            ethabi::encode(&[
+                Token::Array(final_prices),
+                Token::Array(ok),
+                Token::Uint(U256::from(observed_at)),
+                Token::Uint(U256::from(shared_decimals)),
+                Token::Uint(U256::from(reveal_count)),
+            ])
+        }
+    };
     Process::success(&encoded_result);
 
     Ok(())
 }
 
+/// Finds the value most reveals agree on, the earliest one on a tie.
+fn most_common<T: Clone + PartialEq>(values: &[T]) -> Option<T> {
+    values
+        .iter()
+        .rev()
+        .max_by_key(|value| values.iter().filter(|other| other == value).count())
+        .cloned()
+}
+
 /// Calculates the median of a sorted vector
-fn median_sorted(vals: &[u128]) -> u128 {
+fn median_sorted(vals: &[i128]) -> i128 {
+    let mid = vals.len() / 2;
+    if vals.len().is_multiple_of(2) {
+        vals[mid - 1].midpoint(vals[mid])
+    } else {
+        vals[mid]
+    }
+}
+
+/// Calculates the median of a sorted vector of timestamps
+fn median_sorted_timestamps(vals: &[u64]) -> u64 {
     let mid = vals.len() / 2;
-    if vals.len() % 2 == 0 {
+    if vals.len().is_multiple_of(2) {
         vals[mid - 1].midpoint(vals[mid])
     } else {
         vals[mid]
     }
 }
 
-/// Finds the median of a list of prices per price report.
-/// Returns an error if the data is inconsistent or empty.
-fn median_each_asset(data: &[Vec<u128>]) -> Result<Vec<Token>> {
+/// Finds the median of a list of prices per price report, skipping the failed ones.
+/// Returns the median prices along with a flag per asset telling whether any executor reported it,
+/// assets no executor could fetch are reported as `0` with a `false` flag.
+/// Returns an error if the data is empty, the reveals are expected to have the same number of prices.
+fn median_each_asset(data: &[Vec<Option<i128>>]) -> Result<(Vec<Token>, Vec<Token>)> {
     if data.is_empty() {
         return Err(anyhow::anyhow!("No data provided for median calculation"));
     }
@@ -63,20 +169,29 @@ fn median_each_asset(data: &[Vec<u128>]) -> Result<Vec<Token>> {
         return Err(anyhow::anyhow!("Empty price vectors provided"));
     }
 
-    // Verify all rows have the same length
-    if !data.iter().all(|row| row.len() == m) {
-        elog!("Inconsistent row lengths in data reveals");
-        Process::error("Inconsistent row lengths in data reveals".as_bytes());
-        return Err(anyhow::anyhow!("Inconsistent data structure"));
-    }
-
     // Calculate median for each column
     Ok((0..m)
         .map(|col| {
-            let mut vals: Vec<u128> = data.iter().map(|row| row[col]).collect();
+            let mut vals: Vec<i128> = data.iter().filter_map(|row| row[col]).collect();
+            if vals.is_empty() {
+                return (Token::Int(U256::zero()), Token::Bool(false));
+            }
+
             vals.sort();
-            median_sorted(&vals)
+            (
+                Token::Int(to_int256(median_sorted(&vals))),
+                Token::Bool(true),
+            )
         })
-        .map(|p| Token::Int(U256::from(p)))
-        .collect())
+        .unzip())
+}
+
+/// Converts a signed integer into its two's complement `int256` representation.
+fn to_int256(value: i128) -> U256 {
+    if value >= 0 {
+        U256::from(value as u128)
+    } else {
+        // -x == !x + 1 in two's complement, `!` on a U256 flips all 256 bits.
+        !U256::from(value.unsigned_abs()) + U256::one()
+    }
 }
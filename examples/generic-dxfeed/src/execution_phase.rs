@@ -1,14 +1,19 @@
 use anyhow::Result;
 #[cfg(any(feature = "testnet", feature = "mainnet"))]
 use seda_sdk_rs::{Process, elog, log, proxy_http_fetch};
+use shared::UrlBuilder;
+
+use crate::SessionReveal;
+#[cfg(any(feature = "testnet", feature = "mainnet"))]
+use crate::VerificationData;
 
 #[cfg(feature = "testnet")]
-const API_URL: &str = "http://98.84.79.123:5384/proxy/";
+pub const API_URL: &str = "http://98.84.79.123:5384/proxy/";
 #[cfg(feature = "testnet")]
 const PROXY_PUBLIC_KEY: &str = "0375038bc3e61dc2a52e24ff207a5753e38d020a06fff9efc8ec96875f72f4d081";
 
 #[cfg(feature = "mainnet")]
-const API_URL: &str = "http://seda-proxy.dxfeed.com:5384/proxy/";
+pub const API_URL: &str = "http://seda-proxy.dxfeed.com:5384/proxy/";
 #[cfg(feature = "mainnet")]
 const PROXY_PUBLIC_KEY: &str = "021dd035f760061e2833581d4ab50440a355db0ac98e489bf63a5dbc0e89e4af79";
 
@@ -62,7 +67,21 @@ pub fn execution_phase() -> Result<()> {
 // 	"status": "OK"
 // }
 
-const ASSET_TYPES: [&str; 6] = ["cfd", "equity", "fx", "fx_r", "uslf_q", "uslf_t"];
+pub const ASSET_TYPES: [&str; 6] = ["cfd", "equity", "fx", "fx_r", "uslf_q", "uslf_t"];
+
+/// The price fields that can be requested, the reveal refers to them by their index in this list:
+/// - `bid`: the `bidPrice` of a Quote event.
+/// - `ask`: the `askPrice` of a Quote event.
+/// - `mid`: the midpoint of the fixed point `bidPrice` and `askPrice` of a Quote event.
+/// - `last`: the `price` of a Trade event.
+pub const VALID_FIELDS: &[&str] = &["bid", "ask", "mid", "last"];
+
+/// The fields available from the Quote events of `cfd`, `fx`, `fx_r` and `uslf_q`, `ask` being the default.
+const QUOTE_FIELDS: &[&str] = &["ask", "bid", "mid"];
+
+/// The fields available from the Trade events of `equity` and `uslf_t`, `last` being the default.
+/// `session` reveals the last trade along with its session instead, and must be requested alone.
+const TRADE_FIELDS: &[&str] = &["last", "session"];
 
 #[derive(serde::Deserialize)]
 struct QuoteResponse {
@@ -76,34 +95,275 @@ struct TradeResponse {
     trade: serde_json::value::Map<String, serde_json::value::Value>,
 }
 
+/// The prefix of the inputs that selects the verification mode (e.g., "verify:cfd/XAU/USD"), which is
+/// always used when built with the `verification` feature.
+pub const VERIFY_PREFIX: &str = "verify:";
+
+/// The decimals a price is reported with unless the symbol asks for others.
+pub const DEFAULT_DECIMALS: u32 = 2;
+
+/// The most decimals a price can be reported with, so the fixed point conversion stays exact enough.
+pub const MAX_DECIMALS: u32 = 18;
+
+/// A symbol of the input, along with the fields and decimals its prices are reported with.
+#[cfg(any(feature = "testnet", feature = "mainnet"))]
+struct SymbolRequest<'a> {
+    asset_type: &'a str,
+    symbol: &'a str,
+    fields: Vec<&'a str>,
+    decimals: u32,
+}
+
 #[cfg(any(feature = "testnet", feature = "mainnet"))]
 pub fn execution_phase() -> Result<()> {
-    // Expected to be in the format "symbol,..." (e.g., "cfd/XAU/USD", "equity/AAPL")
-    use seda_sdk_rs::HttpFetchOptions;
+    // Expected to be in the format "symbol,..." (e.g., "cfd/XAU/USD", "equity/AAPL,cfd/XAU,fx/EUR"),
+    // each symbol optionally followed by the decimals to report its price with separated by a colon(:) (e.g., "fx/EUR:6").
+    // Optionally followed by the price fields (e.g., "bid,ask,mid") separated by a hyphen(-).
+    // Optionally prefixed by "verify:" to ship the proxy response to the tally rather than the prices.
     let dr_inputs_raw = String::from_utf8(Process::get_inputs())?;
 
     // If no input is provided, log an error and return.
     if dr_inputs_raw.is_empty() {
         elog!("No input provided for the commodity price request.");
         Process::error("No input provided".as_bytes());
+        return Ok(());
     }
 
-    // If the input is not in the expected format, log an error and return.
-    // split at the first /
-    let (asset_type, symbol) = dr_inputs_raw.split_once('/').ok_or_else(|| {
-        elog!("Invalid input format. Expected format: 'fx/SYMBOL/CURRENCY' or 'equity/SYMBOL'");
-        Process::error("Invalid input format".as_bytes());
-        anyhow::anyhow!("Invalid input format")
-    })?;
-
-    if !ASSET_TYPES.contains(&asset_type) {
-        elog!("Invalid asset type. Expected one of: {:?}", ASSET_TYPES);
-        Process::error("Invalid asset type".as_bytes());
-        return Ok(());
+    let (verification, dr_inputs) = match dr_inputs_raw.strip_prefix(VERIFY_PREFIX) {
+        Some(dr_inputs) => (true, dr_inputs),
+        None => (cfg!(feature = "verification"), dr_inputs_raw.as_str()),
+    };
+
+    let parts: Vec<&str> = dr_inputs.split('-').collect();
+    let (symbols, fields) = match parts.as_slice() {
+        // The fields keep the requested order, duplicates included.
+        [symbols, fields] => (*symbols, Some(fields.split(',').collect::<Vec<_>>())),
+        [symbols] => (*symbols, None),
+        _ => {
+            elog!("Invalid input format");
+            Process::error("Invalid input format".as_bytes());
+            return Ok(());
+        }
+    };
+
+    let mut requests: Vec<SymbolRequest> = Vec::new();
+    for symbol in symbols.split(',') {
+        let (symbol, decimals) = match symbol.split_once(':') {
+            Some((symbol, decimals)) => (symbol, decimals.parse::<u32>().ok()),
+            None => (symbol, Some(DEFAULT_DECIMALS)),
+        };
+        let Some(decimals) = decimals.filter(|decimals| *decimals <= MAX_DECIMALS) else {
+            elog!("Invalid decimals for {symbol}. Expected a number up to {MAX_DECIMALS}");
+            Process::error("Invalid decimals".as_bytes());
+            return Ok(());
+        };
+
+        // If the input is not in the expected format, log an error and return.
+        // split at the first /
+        let Some((asset_type, symbol)) = symbol.split_once('/') else {
+            elog!("Invalid input format. Expected format: 'fx/SYMBOL/CURRENCY' or 'equity/SYMBOL'");
+            Process::error("Invalid input format".as_bytes());
+            return Ok(());
+        };
+
+        if !ASSET_TYPES.contains(&asset_type) {
+            elog!("Invalid asset type. Expected one of: {:?}", ASSET_TYPES);
+            Process::error("Invalid asset type".as_bytes());
+            return Ok(());
+        }
+
+        let available_fields = available_fields(asset_type).expect("the asset type is valid");
+        let fields = fields
+            .clone()
+            .unwrap_or_else(|| available_fields[..1].to_vec());
+
+        // Validate that each field is available for the asset type, rather than silently dropping the invalid ones.
+        let invalid_fields: Vec<&str> = fields
+            .iter()
+            .filter(|field| !available_fields.contains(field))
+            .copied()
+            .collect();
+        if !invalid_fields.is_empty() {
+            elog!(
+                "Invalid fields: {invalid_fields:?}, expected any of {available_fields:?} for {asset_type}/{symbol}"
+            );
+            Process::error(format!("Invalid fields: {invalid_fields:?}").as_bytes());
+            return Ok(());
+        }
+
+        requests.push(SymbolRequest {
+            asset_type,
+            symbol,
+            fields,
+            decimals,
+        });
     }
-    log!("Fetching price for asset type: {asset_type}, symbol: {symbol}");
 
-    let url = [API_URL, asset_type, "/", symbol].concat();
+    // The session of a trade only makes sense for a single symbol, not mixed with its prices.
+    if requests
+        .iter()
+        .any(|request| request.fields.contains(&"session"))
+    {
+        let [request] = requests.as_slice() else {
+            elog!("The session fields can only be requested for a single symbol");
+            Process::error("Invalid input format".as_bytes());
+            return Ok(());
+        };
+        if request.fields != ["session"] {
+            elog!(
+                "The session fields can't be requested along with {:?}",
+                request.fields
+            );
+            Process::error("Invalid input format".as_bytes());
+            return Ok(());
+        }
+
+        if !verification {
+            return session_execution(request);
+        }
+    }
+
+    // The tally verifies a single proxy response, so a batch can't be verified.
+    if verification {
+        let [request] = requests.as_slice() else {
+            elog!("Only a single symbol can be requested in verification mode");
+            Process::error("Invalid input format".as_bytes());
+            return Ok(());
+        };
+
+        return verification_execution(request);
+    }
+
+    // The reveal has one chunk per symbol, in input order: the number of fields, the index of every field
+    // and the decimals, followed by the price of every field and the time the prices were observed at,
+    // so the tally can discard stale prices.
+    let mut result: Vec<u128> = Vec::new();
+    for request in &requests {
+        log!(
+            "Fetching price for asset type: {}, symbol: {}, using {:?} with {} decimals",
+            request.asset_type,
+            request.symbol,
+            request.fields,
+            request.decimals
+        );
+        let (prices, observed_at) = match fetch_event(request)
+            .and_then(|event| event_fields(&event, &request.fields, request.decimals))
+        {
+            Ok(prices) => prices,
+            Err(error) => {
+                elog!(
+                    "Failed to fetch {}/{}: {error}",
+                    request.asset_type,
+                    request.symbol
+                );
+                Process::error("Error while fetching commodity price".as_bytes());
+                return Ok(());
+            }
+        };
+        log!("Fetched prices: {prices:?}, observed at: {observed_at}");
+
+        result.push(request.fields.len() as u128);
+        result.extend(request.fields.iter().map(|field| {
+            VALID_FIELDS
+                .iter()
+                .position(|valid_field| valid_field == field)
+                .unwrap_or_default() as u128
+        }));
+        result.push(request.decimals.into());
+        result.extend(prices);
+        result.push(observed_at);
+    }
+
+    // Report the successful result back to the SEDA network.
+    let result: Vec<u8> = result.into_iter().flat_map(u128::to_le_bytes).collect();
+    Process::success(&result);
+
+    Ok(())
+}
+
+/// Reveals the last trade of a symbol along with the session it printed in and the volume of the day.
+#[cfg(any(feature = "testnet", feature = "mainnet"))]
+fn session_execution(request: &SymbolRequest) -> Result<()> {
+    log!(
+        "Fetching session for asset type: {}, symbol: {}",
+        request.asset_type,
+        request.symbol
+    );
+    let reveal =
+        match fetch_event(request).and_then(|event| session_reveal(&event, request.decimals)) {
+            Ok(reveal) => reveal,
+            Err(error) => {
+                elog!(
+                    "Failed to fetch {}/{}: {error}",
+                    request.asset_type,
+                    request.symbol
+                );
+                Process::error("Error while fetching commodity price".as_bytes());
+                return Ok(());
+            }
+        };
+    log!(
+        "Fetched price: {}, time: {}, extended hours: {}, day volume: {}",
+        reveal.price,
+        reveal.time,
+        reveal.extended_hours,
+        reveal.day_volume
+    );
+
+    // The session is revealed as JSON rather than little-endian values.
+    Process::success(&serde_json::to_vec(&reveal)?);
+
+    Ok(())
+}
+
+/// Ships the signed proxy response of a symbol to the tally, which verifies it and reads the prices.
+#[cfg(any(feature = "testnet", feature = "mainnet"))]
+fn verification_execution(request: &SymbolRequest) -> Result<()> {
+    log!(
+        "Fetching price for asset type: {}, symbol: {}, using {:?} with {} decimals for verification",
+        request.asset_type,
+        request.symbol,
+        request.fields,
+        request.decimals
+    );
+    let response = match fetch_response(request) {
+        Ok(response) => response,
+        Err(error) => {
+            elog!(
+                "Failed to fetch {}/{}: {error}",
+                request.asset_type,
+                request.symbol
+            );
+            Process::error("Error while fetching commodity price".as_bytes());
+            return Ok(());
+        }
+    };
+
+    let data = VerificationData {
+        asset_type: request.asset_type.to_string(),
+        symbol: request.symbol.to_string(),
+        fields: request.fields.iter().map(ToString::to_string).collect(),
+        decimals: request.decimals,
+        response,
+    };
+    Process::success(&serde_json::to_vec(&data)?);
+
+    Ok(())
+}
+
+/// Fetches the Quote or Trade event of a symbol through the Data Proxy.
+#[cfg(any(feature = "testnet", feature = "mainnet"))]
+fn fetch_event(request: &SymbolRequest) -> Result<serde_json::Value> {
+    let response = fetch_response(request)?;
+    parse_event(request.asset_type, request.symbol, &response.bytes)
+}
+
+/// Fetches the response of a symbol through the Data Proxy, failing if it was rejected.
+#[cfg(any(feature = "testnet", feature = "mainnet"))]
+fn fetch_response(request: &SymbolRequest) -> Result<seda_sdk_rs::HttpFetchResponse> {
+    use seda_sdk_rs::HttpFetchOptions;
+
+    let url = symbol_url(request.asset_type, request.symbol);
     let response = proxy_http_fetch(
         url,
         Some(PROXY_PUBLIC_KEY.to_string()),
@@ -117,47 +377,130 @@ pub fn execution_phase() -> Result<()> {
 
     // Handle the case where the HTTP request failed or was rejected.
     if !response.is_ok() {
-        elog!(
+        return Err(anyhow::anyhow!(
             "HTTP Response was rejected: {} - {}",
             response.status,
             String::from_utf8(response.bytes)?
-        );
-        Process::error("Error while fetching commodity price".as_bytes());
-        return Ok(());
+        ));
     }
 
+    Ok(response)
+}
+
+/// Reads the Quote or Trade event of a symbol from the response of its asset type.
+pub fn parse_event(asset_type: &str, symbol: &str, response: &[u8]) -> Result<serde_json::Value> {
     let path = match asset_type {
         "cfd" => format!("{symbol}:BFX"),
         "equity" => symbol.to_string(),
         "fx" => format!("{symbol}/USD"),
         "fx_r" => format!("USD/{symbol}"),
         "uslf_q" | "uslf_t" => format!("{symbol}:USLF24"),
-        _ => unreachable!(),
+        _ => return Err(anyhow::anyhow!("Invalid asset type: {asset_type}")),
     };
 
     // Parse the API response as defined earlier.
-    let price = match asset_type {
-        "cfd" | "fx" | "fx_r" | "uslf_q" => {
-            serde_json::from_slice::<QuoteResponse>(&response.bytes)?
-                .quote
-                .get(&path)
-                .and_then(|quote| quote.get("askPrice"))
-                .and_then(|price| price.as_f64())
-        }
-        "equity" | "uslf_t" => serde_json::from_slice::<TradeResponse>(&response.bytes)?
+    match asset_type {
+        "cfd" | "fx" | "fx_r" | "uslf_q" => serde_json::from_slice::<QuoteResponse>(response)?
+            .quote
+            .remove(&path),
+        _ => serde_json::from_slice::<TradeResponse>(response)?
             .trade
-            .get(&path)
-            .and_then(|quote| quote.get("price"))
-            .and_then(|price| price.as_f64()),
-        _ => unreachable!(),
+            .remove(&path),
     }
-    .ok_or_else(|| anyhow::anyhow!("Price not found in response"))?;
+    .ok_or_else(|| anyhow::anyhow!("Price not found in response"))
+}
 
-    let price_lossless = (price * 100.0) as u128;
-    log!("Fetched price: {price_lossless:?}");
+/// Returns the fields available for an asset type, the first one being the default, or `None` for an
+/// invalid asset type.
+pub fn available_fields(asset_type: &str) -> Option<&'static [&'static str]> {
+    // Quote events have bid and ask prices, Trade events only have the price of the last trade.
+    match asset_type {
+        "cfd" | "fx" | "fx_r" | "uslf_q" => Some(QUOTE_FIELDS),
+        "equity" | "uslf_t" => Some(TRADE_FIELDS),
+        _ => None,
+    }
+}
 
-    // Report the successful result back to the SEDA network.
-    Process::success(&price_lossless.to_le_bytes());
+/// Builds the Data Proxy URL of a symbol, every part of a symbol such as "XAU/USD" being its own path segment.
+pub fn symbol_url(asset_type: &str, symbol: &str) -> String {
+    symbol
+        .split('/')
+        .fold(
+            UrlBuilder::new(API_URL).segment(asset_type),
+            UrlBuilder::segment,
+        )
+        .build()
+}
 
-    Ok(())
+/// Reads the last trade of a Trade event along with the session it printed in, its price with `decimals`
+/// precision.
+pub fn session_reveal(event: &serde_json::Value, decimals: u32) -> Result<SessionReveal> {
+    let price = event_fields(event, &["last"], decimals)?.0[0];
+    let field = |key: &str| {
+        event
+            .get(key)
+            .ok_or_else(|| anyhow::anyhow!("{key} not found in response"))
+    };
+
+    Ok(SessionReveal {
+        decimals,
+        price,
+        time: field("time")?
+            .as_u64()
+            .ok_or_else(|| anyhow::anyhow!("Invalid time"))?,
+        extended_hours: field("extendedTradingHours")?
+            .as_bool()
+            .ok_or_else(|| anyhow::anyhow!("Invalid extendedTradingHours"))?,
+        day_volume: field("dayVolume")?
+            .as_f64()
+            .ok_or_else(|| anyhow::anyhow!("Invalid dayVolume"))?
+            .round() as u128,
+    })
+}
+
+/// Reads the requested fields from a Quote or Trade event, each as a `u128` with `decimals` precision,
+/// along with the time in epoch milliseconds of the oldest `bidTime`, `askTime` or `time` they depend on.
+pub fn event_fields(
+    event: &serde_json::Value,
+    fields: &[&str],
+    decimals: u32,
+) -> Result<(Vec<u128>, u128)> {
+    // A missing time counts as the epoch, so its prices never pass a maximum age.
+    let time = |key: &str| {
+        event
+            .get(key)
+            .and_then(|time| time.as_u64())
+            .unwrap_or_default() as u128
+    };
+    let observed_at = fields
+        .iter()
+        .map(|field| match *field {
+            "bid" => time("bidTime"),
+            "ask" => time("askTime"),
+            "mid" => time("bidTime").min(time("askTime")),
+            _ => time("time"),
+        })
+        .min()
+        .unwrap_or_default();
+
+    let price = |key: &str| {
+        event
+            .get(key)
+            .and_then(|price| price.as_f64())
+            .map(|price| (price * 10_f64.powi(decimals as i32)) as u128)
+            .ok_or_else(|| anyhow::anyhow!("Price not found in response"))
+    };
+
+    fields
+        .iter()
+        .map(|field| match *field {
+            "bid" => price("bidPrice"),
+            "ask" => price("askPrice"),
+            // The midpoint of the fixed point prices, so every executor rounds it the same way.
+            "mid" => Ok(price("bidPrice")?.midpoint(price("askPrice")?)),
+            "last" => price("price"),
+            field => Err(anyhow::anyhow!("Invalid field: {field}")),
+        })
+        .collect::<Result<Vec<_>>>()
+        .map(|prices| (prices, observed_at))
 }
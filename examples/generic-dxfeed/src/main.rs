@@ -1,10 +1,39 @@
 use execution_phase::execution_phase;
-use seda_sdk_rs::oracle_program;
+use seda_sdk_rs::{HttpFetchResponse, oracle_program};
 use tally_phase::tally_phase;
 
 mod execution_phase;
 mod tally_phase;
 
+/// The reveal of the `session` mode: the last trade of a symbol along with the session it printed in.
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SessionReveal {
+    /// The decimals the price is reported with.
+    decimals: u32,
+    /// The `price` of the last trade.
+    price: u128,
+    /// The `time` of the last trade in epoch milliseconds.
+    time: u64,
+    /// Whether the last trade printed outside of regular trading hours.
+    extended_hours: bool,
+    /// The `dayVolume` of the symbol, rounded to a whole number of shares.
+    day_volume: u128,
+}
+
+/// The reveal of the verification mode: the raw proxy response of a single symbol, which the tally
+/// verifies the signature of before reading the requested fields from it.
+#[derive(serde::Serialize, serde::Deserialize)]
+struct VerificationData {
+    asset_type: String,
+    symbol: String,
+    /// The requested fields, in order.
+    fields: Vec<String>,
+    /// The decimals the prices are reported with.
+    decimals: u32,
+    response: HttpFetchResponse,
+}
+
 #[oracle_program]
 impl GenericDxFeed {
     fn execute() {
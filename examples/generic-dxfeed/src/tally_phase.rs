@@ -1,62 +1,582 @@
 use anyhow::Result;
 use ethabi::{Token, ethereum_types::U256};
-use seda_sdk_rs::{Process, elog, get_reveals, log};
+use seda_sdk_rs::{HttpFetchMethod, Process, elog, get_reveals, get_unfiltered_reveals, log};
+
+use crate::{
+    SessionReveal, VerificationData,
+    execution_phase::{
+        ASSET_TYPES, DEFAULT_DECIMALS, MAX_DECIMALS, VALID_FIELDS, VERIFY_PREFIX, available_fields,
+        event_fields, parse_event, session_reveal, symbol_url,
+    },
+};
+
+/// The public keys of the Data Proxy the verified responses must be signed with. Several keys can be accepted
+/// while the proxy rotates them, the execution phase `PROXY_PUBLIC_KEY` being one of them.
+#[cfg(all(feature = "testnet", not(feature = "test")))]
+const PROXY_PUBLIC_KEYS: &[&str] =
+    &["0375038bc3e61dc2a52e24ff207a5753e38d020a06fff9efc8ec96875f72f4d081"];
+
+#[cfg(feature = "mainnet")]
+const PROXY_PUBLIC_KEYS: &[&str] =
+    &["021dd035f760061e2833581d4ab50440a355db0ac98e489bf63a5dbc0e89e4af79"];
+
+/// The key the test Data Proxy signs with, derived from the private key `1`.
+#[cfg(feature = "test")]
+const PROXY_PUBLIC_KEYS: &[&str] =
+    &["0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798"];
+
+/// The error of a response that was signed, but not by any of the [`PROXY_PUBLIC_KEYS`].
+const UNEXPECTED_PUBLIC_KEY: &str = "Unexpected proxy public key";
+
+/// The fields and decimals the prices of a symbol are reported with.
+#[derive(Clone, Debug, PartialEq)]
+struct SymbolHeader {
+    field_ids: Vec<u128>,
+    decimals: u128,
+}
+
+/// The prices of every symbol of a reveal, along with the time in epoch milliseconds the oldest of them
+/// was observed at.
+struct FieldPrices {
+    prices: Vec<Vec<u128>>,
+    observed_at: u128,
+}
+
+/// What an executor revealed, depending on whether the session of the last trade was requested.
+enum Reveal {
+    Prices(Vec<SymbolHeader>, FieldPrices),
+    Session(SessionReveal),
+}
+
+/// What a reveal was made for: the fields and decimals of every symbol, or the decimals of the session.
+#[derive(Clone, Debug, PartialEq)]
+enum RevealHeader {
+    Prices(Vec<SymbolHeader>),
+    Session(u32),
+}
+
+impl Reveal {
+    fn header(&self) -> RevealHeader {
+        match self {
+            Reveal::Prices(headers, _) => RevealHeader::Prices(headers.clone()),
+            Reveal::Session(session) => RevealHeader::Session(session.decimals),
+        }
+    }
+}
+
+/// The request every verified response must answer, derived from the tally inputs rather than trusting
+/// the request the executors revealed along with the response.
+#[derive(Debug)]
+struct VerifiedRequest<'a> {
+    asset_type: &'a str,
+    symbol: &'a str,
+    fields: Vec<&'a str>,
+    decimals: u32,
+    url: String,
+}
 
 pub fn tally_phase() -> Result<()> {
-    // Retrieve consensus reveals from the tally phase.
-    let reveals = get_reveals()?;
-    let mut revealed_prices: Vec<u128> = Vec::with_capacity(reveals.len());
+    // Tally inputs are a comma-separated list of options (e.g., "timestamped,maxAge=60000,now=1753710744000"):
+    // - "timestamped" reports the median time the prices were observed at alongside them.
+    // - "maxAge=<ms>" discards the reveals whose prices are older than that many milliseconds.
+    // - "now=<unix ms>" is the time the age is measured from, the median observation time of the reveals otherwise.
+    // Prefixed by "verify:" for the verification mode, which is always used with the `verification` feature,
+    // along with the request of the execution inputs (e.g., "verify:symbol=cfd/XAU/USD,fields=bid;ask,decimals=4"):
+    // - "symbol=<asset type>/<symbol>" is the symbol every response must answer.
+    // - "fields=<field>;<field>" are the requested fields in order, the default field of the asset type otherwise.
+    // - "decimals=<decimals>" are the requested decimals, 2 otherwise.
+    let tally_inputs = String::from_utf8(Process::get_inputs()).unwrap_or_default();
+    let (verification, options) = match tally_inputs.strip_prefix(VERIFY_PREFIX) {
+        Some(options) => (true, options),
+        None => (cfg!(feature = "verification"), tally_inputs.as_str()),
+    };
+    let mut timestamped = false;
+    let mut max_age_ms: Option<u128> = None;
+    let mut reference_ms: Option<u128> = None;
+    let mut symbol: Option<&str> = None;
+    let mut fields: Option<Vec<&str>> = None;
+    let mut decimals: Option<&str> = None;
+    for option in options.split(',') {
+        match option.split_once('=') {
+            Some(("maxAge", value)) => max_age_ms = value.parse().ok(),
+            Some(("now", value)) => reference_ms = value.parse().ok(),
+            Some(("symbol", value)) if !value.is_empty() => symbol = Some(value),
+            Some(("fields", value)) => fields = Some(value.split(';').collect()),
+            Some(("decimals", value)) => decimals = Some(value),
+            _ if option == "timestamped" => timestamped = true,
+            _ => {}
+        }
+    }
+
+    // A verification reveal ships the signed proxy response, which is verified here rather than agreed upon.
+    let reveals = if verification {
+        let Some(symbol) = symbol else {
+            elog!("No symbol provided in the tally inputs.");
+            Process::error("No symbol provided".as_bytes());
+            return Ok(());
+        };
+        let Some(request) = verified_request(symbol, fields, decimals) else {
+            elog!("Invalid verification request in the tally inputs: {options}");
+            Process::error("Invalid tally inputs".as_bytes());
+            return Ok(());
+        };
+        log!("Verifying the responses of {request:?}");
 
-    // Iterate over each reveal, parse its content as an unsigned integer (u128), and store it in the prices array.
+        let (reveals, unexpected_signer) = verified_reveals(&request)?;
+        // If every signed response came from an unknown Data Proxy, report it rather than a lack of consensus.
+        if reveals.is_empty() && unexpected_signer {
+            Process::error(UNEXPECTED_PUBLIC_KEY.as_bytes());
+            return Ok(());
+        }
+        reveals
+    } else {
+        // Retrieve consensus reveals from the tally phase.
+        get_reveals()?
+            .iter()
+            .filter_map(|reveal| match parse_reveal(&reveal.body.reveal) {
+                Ok(reveal) => Some(reveal),
+                Err(err) => {
+                    elog!("Failed to parse revealed prices: {err}");
+                    None
+                }
+            })
+            .collect::<Vec<_>>()
+    };
+    // Every reveal is either made of the requested fields and decimals of every symbol along with their prices
+    // (u128) and the time they were observed at, or of the session of the last trade. The payload most reveals
+    // agree on is the expected one, whichever executor happened to reveal first.
+    let expected = most_common(&reveals.iter().map(Reveal::header).collect::<Vec<_>>());
+    let mut revealed_prices: Vec<FieldPrices> = Vec::with_capacity(reveals.len());
+    let mut revealed_sessions: Vec<SessionReveal> = Vec::new();
     for reveal in reveals {
-        let price = match reveal.body.reveal.as_slice().try_into() {
-            Ok(price) => u128::from_le_bytes(price),
-            Err(err) => {
-                elog!("Failed to parse revealed prices: {err}");
-                continue;
+        let header = reveal.header();
+        if expected.as_ref() != Some(&header) {
+            elog!("Ignoring reveal with a mismatching payload: {header:?}");
+            continue;
+        }
+
+        match reveal {
+            Reveal::Prices(_, prices) => revealed_prices.push(prices),
+            Reveal::Session(session) => revealed_sessions.push(session),
+        }
+    }
+
+    if !revealed_sessions.is_empty() {
+        return tally_sessions(revealed_sessions, max_age_ms, reference_ms);
+    }
+
+    // Discard the reveals that are older than the maximum age.
+    if let Some(max_age_ms) = max_age_ms
+        && !revealed_prices.is_empty()
+    {
+        let reference_ms = reference_ms.unwrap_or_else(|| {
+            median(
+                &revealed_prices
+                    .iter()
+                    .map(|prices| prices.observed_at)
+                    .collect::<Vec<_>>(),
+            )
+        });
+
+        revealed_prices.retain(|prices| {
+            let age = reference_ms.saturating_sub(prices.observed_at);
+            if age > max_age_ms {
+                elog!(
+                    "Ignoring stale reveal observed at {}, {age}ms older than {reference_ms}",
+                    prices.observed_at
+                );
             }
-        };
+            age <= max_age_ms
+        });
 
-        revealed_prices.push(price);
+        if revealed_prices.is_empty() {
+            elog!("Every reveal is older than {max_age_ms}ms");
+            Process::error("All revealed prices are stale".as_bytes());
+            return Ok(());
+        }
     }
 
     // If no valid prices were revealed, report an error indicating no consensus.
-    if revealed_prices.is_empty() {
+    let Some(RevealHeader::Prices(headers)) = expected.filter(|_| !revealed_prices.is_empty())
+    else {
         Process::error("No consensus among revealed results".as_bytes());
         return Ok(());
-    }
+    };
 
-    // If there are valid prices revealed, calculate the median price from price reports.
-    let final_prices = median(&revealed_prices);
+    // If there are valid prices revealed, calculate the median price of every field of every symbol
+    // from price reports, in input order.
+    let final_prices: Vec<Token> = headers
+        .iter()
+        .enumerate()
+        .flat_map(|(symbol, header)| (0..header.field_ids.len()).map(move |field| (symbol, field)))
+        .map(|(symbol, field)| {
+            let prices: Vec<u128> = revealed_prices
+                .iter()
+                .map(|prices| prices.prices[symbol][field])
+                .collect();
+            Token::Uint(U256::from(median(&prices)))
+        })
+        .collect();
     log!("Final median prices: {final_prices:?}");
 
+    let mut tokens = match (headers.as_slice(), final_prices.as_slice()) {
+        // A single field of a single symbol is encoded as a EVM `uint256`.
+        ([_], [final_price]) => vec![final_price.clone()],
+        // Several fields of a single symbol are encoded as a `uint256[]` in the requested order.
+        ([_], _) => vec![Token::Array(final_prices)],
+        // Several symbols are encoded as `(uint256[], uint8[])`, the fields of every symbol one after
+        // the other followed by the decimals of every symbol.
+        _ => vec![
+            Token::Array(final_prices),
+            Token::Array(
+                headers
+                    .iter()
+                    .map(|header| Token::Uint(U256::from(header.decimals)))
+                    .collect(),
+            ),
+        ],
+    };
+
+    // The timestamped layout follows the prices with the median observation time as a `uint64`.
+    if timestamped {
+        let observed_at = median(
+            &revealed_prices
+                .iter()
+                .map(|prices| prices.observed_at)
+                .collect::<Vec<_>>(),
+        );
+        log!("Observed at: {observed_at}");
+        tokens.push(Token::Uint(U256::from(observed_at)));
+    }
+
     // Report the successful result in the tally phase.
-    let result = ethabi::encode(&[final_prices]);
-    Process::success(&result);
+    Process::success(&ethabi::encode(&tokens));
 
     Ok(())
 }
 
-/// Finds the median of a list of prices per price report.
-fn median(data: &[u128]) -> Token {
-    let m = data.len();
+/// Builds the request of the verification mode from the symbol, fields and decimals of the tally inputs,
+/// validated the same way as the execution inputs. Returns `None` if they don't make up a valid request.
+fn verified_request<'a>(
+    symbol: &'a str,
+    fields: Option<Vec<&'a str>>,
+    decimals: Option<&str>,
+) -> Option<VerifiedRequest<'a>> {
+    let (asset_type, symbol) = symbol.split_once('/')?;
+    if !ASSET_TYPES.contains(&asset_type) || symbol.is_empty() {
+        return None;
+    }
+
+    let available_fields = available_fields(asset_type)?;
+    let fields = fields.unwrap_or_else(|| available_fields[..1].to_vec());
+    if fields.is_empty()
+        || !fields.iter().all(|field| available_fields.contains(field))
+        || (fields.contains(&"session") && fields != ["session"])
+    {
+        return None;
+    }
+
+    let decimals = match decimals {
+        Some(decimals) => decimals
+            .parse::<u32>()
+            .ok()
+            .filter(|decimals| *decimals <= MAX_DECIMALS)?,
+        None => DEFAULT_DECIMALS,
+    };
+
+    Some(VerifiedRequest {
+        asset_type,
+        symbol,
+        fields,
+        decimals,
+        url: symbol_url(asset_type, symbol),
+    })
+}
+
+/// Verifies the signed proxy response of every reveal of the verification mode, and reads the requested
+/// fields or session from the verified ones. The failed executions, unverifiable responses and responses to
+/// another request are discarded, so a single faulty executor can't affect the result. Also reports whether
+/// any response was signed by an unexpected proxy public key.
+fn verified_reveals(request: &VerifiedRequest) -> Result<(Vec<Reveal>, bool)> {
+    let reveals = get_unfiltered_reveals()?;
+    log!("Verifying {} reveals", reveals.len());
+
+    let mut unexpected_signer = false;
+    let verified = reveals
+        .iter()
+        .filter(|reveal| {
+            if reveal.body.exit_code != 0 {
+                elog!("Ignoring failed execution");
+            }
+            reveal.body.exit_code == 0
+        })
+        .filter_map(
+            |reveal| match verified_reveal(&reveal.body.reveal, request) {
+                Ok(reveal) => Some(reveal),
+                Err(err) => {
+                    elog!("Ignoring unverifiable reveal: {err}");
+                    unexpected_signer |= err.to_string() == UNEXPECTED_PUBLIC_KEY;
+                    None
+                }
+            },
+        )
+        .collect();
 
-    if m == 0 {
-        Process::error("No valid data available for median calculation".as_bytes());
+    Ok((verified, unexpected_signer))
+}
+
+/// Verifies the signature and URL of the proxy response of a reveal, then reads the requested fields or
+/// session from it the same way as the execution phase does.
+fn verified_reveal(reveal: &[u8], request: &VerifiedRequest) -> Result<Reveal> {
+    let data: VerificationData = serde_json::from_slice(reveal)?;
+
+    if !data
+        .response
+        .proxy_verification(HttpFetchMethod::Get, None)?
+    {
+        return Err(anyhow::anyhow!("Signature verification failed"));
+    }
+
+    // A valid signature only proves the response wasn't tampered with, it must also come from a known Data Proxy.
+    let public_key = data
+        .response
+        .headers
+        .get("x-seda-publickey")
+        .map(String::as_str)
+        .unwrap_or_default();
+    // The header is hex, which may come in either case.
+    if !PROXY_PUBLIC_KEYS
+        .iter()
+        .any(|key| key.eq_ignore_ascii_case(public_key))
+    {
+        elog!("Response signed by an unexpected proxy public key: {public_key}");
+        return Err(anyhow::anyhow!(UNEXPECTED_PUBLIC_KEY));
+    }
+
+    // The signature covers the URL, so a response for another symbol can't be passed off as the requested one.
+    if data.response.url != request.url {
+        elog!(
+            "Response answers an unexpected URL: {}, expected {}",
+            data.response.url,
+            request.url
+        );
+        return Err(anyhow::anyhow!("Unexpected response URL"));
     }
+    // The fields and decimals aren't part of the response, so they must be the requested ones as well.
+    if data.asset_type != request.asset_type
+        || data.symbol != request.symbol
+        || !data
+            .fields
+            .iter()
+            .map(String::as_str)
+            .eq(request.fields.iter().copied())
+        || data.decimals != request.decimals
+    {
+        return Err(anyhow::anyhow!(
+            "Unexpected request: {}/{} {:?} with {} decimals",
+            data.asset_type,
+            data.symbol,
+            data.fields,
+            data.decimals
+        ));
+    }
+
+    let event = parse_event(request.asset_type, request.symbol, &data.response.bytes)?;
+    if request.fields == ["session"] {
+        return Ok(Reveal::Session(session_reveal(&event, request.decimals)?));
+    }
+
+    let field_ids = request
+        .fields
+        .iter()
+        .map(|field| {
+            VALID_FIELDS
+                .iter()
+                .position(|valid_field| valid_field == field)
+                .map(|id| id as u128)
+                .ok_or_else(|| anyhow::anyhow!("Invalid field: {field}"))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let (prices, observed_at) = event_fields(&event, &request.fields, request.decimals)?;
+    log!("Verified prices: {prices:?}, observed at: {observed_at}");
+
+    Ok(Reveal::Prices(
+        vec![SymbolHeader {
+            field_ids,
+            decimals: request.decimals.into(),
+        }],
+        FieldPrices {
+            prices: vec![prices],
+            observed_at,
+        },
+    ))
+}
 
+/// Reports the last trade of the `session` mode as `(uint256 price, uint64 time, bool extendedHours,
+/// uint256 dayVolume)`, the medians of the fresh reveals that all agree on the session flag.
+fn tally_sessions(
+    mut sessions: Vec<SessionReveal>,
+    max_age_ms: Option<u128>,
+    reference_ms: Option<u128>,
+) -> Result<()> {
+    let times = |sessions: &[SessionReveal]| -> Vec<u128> {
+        sessions.iter().map(|session| session.time.into()).collect()
+    };
+
+    // Discard the reveals that are older than the maximum age.
+    if let Some(max_age_ms) = max_age_ms {
+        let reference_ms = reference_ms.unwrap_or_else(|| median(&times(&sessions)));
+
+        sessions.retain(|session| {
+            let age = reference_ms.saturating_sub(session.time.into());
+            if age > max_age_ms {
+                elog!(
+                    "Ignoring stale reveal observed at {}, {age}ms older than {reference_ms}",
+                    session.time
+                );
+            }
+            age <= max_age_ms
+        });
+
+        if sessions.is_empty() {
+            elog!("Every reveal is older than {max_age_ms}ms");
+            Process::error("All revealed prices are stale".as_bytes());
+            return Ok(());
+        }
+    }
+
+    // A price from extended hours can't be mixed with one from regular hours, so every executor must agree.
+    let extended_hours = sessions[0].extended_hours;
+    if sessions
+        .iter()
+        .any(|session| session.extended_hours != extended_hours)
+    {
+        elog!("Reveals disagree on whether the last trade printed during extended hours");
+        Process::error("No consensus on the trading session".as_bytes());
+        return Ok(());
+    }
+
+    let price = median(
+        &sessions
+            .iter()
+            .map(|session| session.price)
+            .collect::<Vec<_>>(),
+    );
+    let time = median(&times(&sessions));
+    let day_volume = median(
+        &sessions
+            .iter()
+            .map(|session| session.day_volume)
+            .collect::<Vec<_>>(),
+    );
+    log!(
+        "Final price: {price}, time: {time}, extended hours: {extended_hours}, day volume: {day_volume}"
+    );
+
+    // Report the successful result in the tally phase.
+    Process::success(&ethabi::encode(&[
+        Token::Uint(U256::from(price)),
+        Token::Uint(U256::from(time)),
+        Token::Bool(extended_hours),
+        Token::Uint(U256::from(day_volume)),
+    ]));
+
+    Ok(())
+}
+
+/// Parses a reveal made of one chunk per symbol: the number of fields, the index of every field, the
+/// decimals, the price of every field and the time the prices were observed at, each as a little endian `u128`.
+/// The session of a trade is revealed as JSON rather than little-endian values.
+fn parse_reveal(reveal: &[u8]) -> Result<Reveal> {
+    if let Ok(session) = serde_json::from_slice::<SessionReveal>(reveal) {
+        return Ok(Reveal::Session(session));
+    }
+
+    if !reveal.len().is_multiple_of(size_of::<u128>()) {
+        return Err(anyhow::anyhow!(
+            "expected a multiple of {} bytes, got {}",
+            size_of::<u128>(),
+            reveal.len()
+        ));
+    }
+
+    let values: Vec<u128> = reveal
+        .chunks_exact(size_of::<u128>())
+        .map(|chunk| u128::from_le_bytes(chunk.try_into().expect("chunk is 16 bytes")))
+        .collect();
+
+    let mut headers = Vec::new();
+    let mut prices = Vec::new();
+    let mut observed_at = u128::MAX;
+    let mut values = values.as_slice();
+    while let Some((&field_count, rest)) = values.split_first() {
+        // Every chunk takes up its field ids and prices, the decimals and the observation time.
+        let field_count = usize::try_from(field_count)?;
+        let Some((chunk, rest)) = field_count
+            .checked_mul(2)
+            .and_then(|values| rest.split_at_checked(values + 2))
+            .filter(|_| field_count > 0)
+        else {
+            return Err(anyhow::anyhow!(
+                "expected {field_count} fields and prices, got {} values",
+                rest.len()
+            ));
+        };
+
+        let (field_ids, chunk) = chunk.split_at(field_count);
+        let (&decimals, chunk) = chunk.split_first().expect("chunk has the decimals");
+        let (symbol_prices, time) = chunk.split_at(field_count);
+        if field_ids.iter().any(|&id| id >= VALID_FIELDS.len() as u128) {
+            return Err(anyhow::anyhow!("unknown fields {field_ids:?}"));
+        }
+        if decimals > u8::MAX.into() {
+            return Err(anyhow::anyhow!("decimals {decimals} don't fit in a uint8"));
+        }
+
+        headers.push(SymbolHeader {
+            field_ids: field_ids.to_vec(),
+            decimals,
+        });
+        prices.push(symbol_prices.to_vec());
+        // The reveal is only as fresh as its oldest symbol.
+        observed_at = observed_at.min(time[0]);
+        values = rest;
+    }
+
+    if headers.is_empty() {
+        return Err(anyhow::anyhow!("empty reveal"));
+    }
+
+    Ok(Reveal::Prices(
+        headers,
+        FieldPrices {
+            prices,
+            observed_at,
+        },
+    ))
+}
+
+/// Finds the value most reveals agree on, the earliest one on a tie.
+fn most_common<T: Clone + PartialEq>(values: &[T]) -> Option<T> {
+    values
+        .iter()
+        .rev()
+        .max_by_key(|value| values.iter().filter(|other| other == value).count())
+        .cloned()
+}
+
+/// Finds the median of a list of prices per price report.
+fn median(data: &[u128]) -> u128 {
     let mut sorted_data = data.to_vec();
     sorted_data.sort_unstable();
 
-    let median_price = if m % 2 == 0 {
+    let m = sorted_data.len();
+    if m.is_multiple_of(2) {
         // safe average of two u128s without overflow
         let a = sorted_data[m / 2 - 1];
         let b = sorted_data[m / 2];
         a.midpoint(b)
     } else {
         sorted_data[m / 2]
-    };
-
-    // convert to Token::Uint for encoding
-    Token::Uint(U256::from(median_price))
+    }
 }
@@ -1,6 +1,8 @@
-use anyhow::Result;
+use anyhow::{Result, anyhow};
+use ethabi::ethereum_types::U256;
 #[cfg(any(feature = "testnet", feature = "mainnet"))]
 use seda_sdk_rs::{Process, elog, http_fetch, log};
+use shared::UrlBuilder;
 
 // Response:
 // {
@@ -12,8 +14,34 @@ use seda_sdk_rs::{Process, elog, http_fetch, log};
 //     }
 //  }
 
+const API_URL: &str = "https://lite-api.jup.ag/price/v3";
+
+/// The decimals prices are reported with when none are requested.
+const DEFAULT_DECIMALS: u32 = 6;
+
+/// The base58 alphabet used by Solana addresses.
+const BASE58_ALPHABET: &[u8] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+/// The decimals the USD prices are converted with before dividing them into a cross price.
+const CROSS_PRICE_DECIMALS: u32 = 18;
+
+/// The length in bytes of a decoded Solana address.
+const MINT_ADDRESS_LENGTH: usize = 32;
+
+/// Optional limits a token's price data has to be within for its price to be reported.
+#[derive(Debug, Default)]
+struct Thresholds {
+    /// The maximum absolute `priceChange24h`, in percent.
+    max_price_change_24h: Option<f64>,
+    /// The oldest `blockId` (Solana slot) a price may have been observed at.
+    min_block_id: Option<u64>,
+}
+
 pub fn execution_phase() -> Result<()> {
     // Expected to be in the format "tokenContractAddressA,..." (e.g., "So11111111111111111111111111111111111111112").
+    // Optionally followed by the decimals to report the prices with (e.g., "...,...-8") and the
+    // thresholds "maxChange24h=<percent>" and "minBlockId=<slot>", and the quote mint "vsToken=<mint>"
+    // to report the prices in instead of USD, each separated by a hyphen(-).
     let dr_inputs_raw = String::from_utf8(Process::get_inputs())?;
 
     // If no input is provided, log an error and return.
@@ -23,10 +51,66 @@ pub fn execution_phase() -> Result<()> {
         return Ok(());
     }
 
-    // Log the asset pair being fetched as part of the Execution Standard Out.
-    log!("Fetching price for asset: {dr_inputs_raw}");
+    let mut parts = dr_inputs_raw.split('-');
+    let mints = parts.next().unwrap_or_default();
+    let mut decimals = DEFAULT_DECIMALS;
+    let mut thresholds = Thresholds::default();
+    let mut quote_mint: Option<&str> = None;
+    for option in parts {
+        let parsed = match option.split_once('=') {
+            Some(("maxChange24h", value)) => value
+                .parse::<f64>()
+                .ok()
+                .filter(|value| value.is_finite() && *value >= 0.0)
+                .map(|value| thresholds.max_price_change_24h = Some(value)),
+            Some(("minBlockId", value)) => value
+                .parse::<u64>()
+                .ok()
+                .map(|value| thresholds.min_block_id = Some(value)),
+            Some(("vsToken", value)) => {
+                quote_mint = Some(value);
+                Some(())
+            }
+            Some(_) => None,
+            None => option.parse::<u32>().ok().map(|value| decimals = value),
+        };
+
+        if parsed.is_none() {
+            elog!(
+                "Invalid option: {option}. Expected decimals, maxChange24h=<percent>, minBlockId=<slot> or vsToken=<mint>"
+            );
+            Process::error("Invalid input option".as_bytes());
+            return Ok(());
+        }
+    }
+
+    // Validate every mint before it is put in the URL.
+    let mints: Vec<&str> = mints.split(',').collect();
+    if let Some(invalid_mint) = mints
+        .iter()
+        .chain(quote_mint.as_ref())
+        .find(|mint| !is_valid_mint(mint))
+    {
+        elog!("Invalid mint address: {invalid_mint}");
+        Process::error("Invalid mint address".as_bytes());
+        return Ok(());
+    }
 
-    let url: String = ["https://lite-api.jup.ag/price/v3?ids=", &dr_inputs_raw].concat();
+    // Log the assets being fetched as part of the Execution Standard Out.
+    log!(
+        "Fetching prices for assets: {mints:?}, in {}, with {decimals} decimals and {thresholds:?}",
+        quote_mint.unwrap_or("USD")
+    );
+
+    // The quote token's USD price is fetched in the same call as the other tokens.
+    let mut ids = mints.clone();
+    if let Some(quote_mint) = quote_mint
+        && !ids.contains(&quote_mint)
+    {
+        ids.push(quote_mint);
+    }
+
+    let url = UrlBuilder::new(API_URL).query_list("ids", &ids).build();
     let response = http_fetch(url, None);
 
     // Handle the case where the HTTP request failed or was rejected.
@@ -45,19 +129,203 @@ pub fn execution_phase() -> Result<()> {
         serde_json::value::Map<String, serde_json::value::Value>,
     >(&response.bytes)?;
 
-    // Extract the prices for each symbol from the response data.
-    let price = if let Some(price_data) = response_data.get(&dr_inputs_raw) {
-        price_data["usdPrice"].as_f64().unwrap_or_default()
+    // Extract the USD prices for each mint in the input order followed by the quote mint, scaled
+    // to the requested decimals or to the cross price decimals when they still need to be divided.
+    let usd_decimals = if quote_mint.is_some() {
+        CROSS_PRICE_DECIMALS
     } else {
-        elog!("Price not found for token: {}", dr_inputs_raw);
-        Process::error("Token price not found".as_bytes());
-        return Ok(());
+        decimals
     };
+    let mut prices: Vec<u128> = Vec::with_capacity(mints.len() + 1);
+    let mut observed_block_id: Option<u64> = None;
+    for &mint in mints.iter().chain(quote_mint.as_ref()) {
+        let price_data = response_data
+            .get(mint)
+            .cloned()
+            .unwrap_or(serde_json::Value::Null);
+        // Numbers keep their raw JSON text, so they convert to fixed point without a float round trip.
+        let price = match &price_data["usdPrice"] {
+            serde_json::Value::Number(price) => price.to_string(),
+            _ => {
+                elog!("Price not found for token: {mint}");
+                Process::error("Token price not found".as_bytes());
+                return Ok(());
+            }
+        };
+
+        let Some(block_id) = price_data["blockId"].as_u64() else {
+            elog!("Block id not found for token: {mint}");
+            Process::error("Token block id not found".as_bytes());
+            return Ok(());
+        };
+
+        if let Some(min_block_id) = thresholds.min_block_id
+            && block_id < min_block_id
+        {
+            elog!("Price of token {mint} was observed at block {block_id}, before {min_block_id}");
+            Process::error("Token price is too old".as_bytes());
+            return Ok(());
+        }
+
+        if let Some(max_price_change_24h) = thresholds.max_price_change_24h {
+            match price_data["priceChange24h"].as_f64() {
+                Some(price_change_24h) if price_change_24h.abs() <= max_price_change_24h => {}
+                price_change_24h => {
+                    elog!(
+                        "Price of token {mint} changed {price_change_24h:?}% in 24h, more than {max_price_change_24h}%"
+                    );
+                    Process::error("Token price change exceeds threshold".as_bytes());
+                    return Ok(());
+                }
+            }
+        }
+
+        let price = match make_price(&price, usd_decimals) {
+            Ok(price) => price,
+            Err(error) => {
+                elog!("Failed to convert price {price} of token {mint}: {error}");
+                Process::error("Failed to convert token price".as_bytes());
+                return Ok(());
+            }
+        };
+
+        // A missing price must never be reported as a valid zero price.
+        if price == 0 {
+            elog!("Price of token {mint} is zero with {usd_decimals} decimals");
+            Process::error("Token price is zero".as_bytes());
+            return Ok(());
+        }
+
+        log!("Fetched price for {mint}: {price}, at block {block_id}");
+        prices.push(price);
+        // The batch is only as recent as its oldest price.
+        observed_block_id = Some(observed_block_id.map_or(block_id, |oldest| oldest.min(block_id)));
+    }
 
-    log!("Fetched price: {price:?}");
+    // Divide the USD prices by the quote token's USD price.
+    if let Some(quote_mint) = quote_mint {
+        let quote_price = prices.pop().unwrap_or_default();
+        for (price, mint) in prices.iter_mut().zip(&mints) {
+            *price = match cross_price(*price, quote_price, decimals) {
+                Some(cross_price) if cross_price != 0 => cross_price,
+                cross_price => {
+                    elog!(
+                        "Cross price of token {mint} in {quote_mint} is {cross_price:?} with {decimals} decimals"
+                    );
+                    Process::error("Invalid cross price".as_bytes());
+                    return Ok(());
+                }
+            };
+        }
+        log!("Cross prices in {quote_mint}: {prices:?}");
+    }
+
+    // Reveal the block id the prices were observed at, followed by the prices.
+    let observed_block_id = observed_block_id.unwrap_or_default();
+    log!("Prices observed at block {observed_block_id}");
+    let result: Vec<u8> = std::iter::once(observed_block_id as u128)
+        .chain(prices)
+        .flat_map(u128::to_le_bytes)
+        .collect();
 
     // Report the successful result back to the SEDA network.
-    Process::success(&price.to_le_bytes());
+    Process::success(&result);
 
     Ok(())
 }
+
+/// Checks that a mint is a base58 encoded 32 byte Solana address.
+fn is_valid_mint(mint: &str) -> bool {
+    // Decode into big-endian bytes, the leading '1's each stand for a zero byte.
+    let mut bytes: Vec<u8> = Vec::with_capacity(MINT_ADDRESS_LENGTH);
+    for char in mint.bytes() {
+        let Some(digit) = BASE58_ALPHABET.iter().position(|&c| c == char) else {
+            return false;
+        };
+
+        let mut carry = digit as u32;
+        for byte in bytes.iter_mut().rev() {
+            carry += *byte as u32 * 58;
+            *byte = carry as u8;
+            carry >>= 8;
+        }
+        while carry > 0 {
+            bytes.insert(0, carry as u8);
+            carry >>= 8;
+        }
+
+        if bytes.len() > MINT_ADDRESS_LENGTH {
+            return false;
+        }
+    }
+
+    let leading_zeros = mint.bytes().take_while(|&c| c == b'1').count();
+    !mint.is_empty() && leading_zeros + bytes.len() == MINT_ADDRESS_LENGTH
+}
+
+/// Divides two prices with the same decimals into a price with `decimals` precision, rounding down.
+/// Returns `None` if the quote price is zero or the result doesn't fit in a u128.
+fn cross_price(base_price: u128, quote_price: u128, decimals: u32) -> Option<u128> {
+    let scale = U256::from(10).checked_pow(U256::from(decimals))?;
+    let cross_price = U256::from(base_price)
+        .checked_mul(scale)?
+        .checked_div(U256::from(quote_price))?;
+
+    (cross_price <= U256::from(u128::MAX)).then(|| cross_price.as_u128())
+}
+
+/// Convert a decimal price string (e.g., "1234.5678" or "1.5e-7") into a u128 with `decimals` precision.
+/// - Truncates extra precision if the input has more decimals than requested.
+/// - Multiplies (with overflow checks) if the input has fewer decimals than requested.
+fn make_price(price_str: &str, decimals: u32) -> Result<u128> {
+    let (mantissa, exponent) = match price_str.split_once(['e', 'E']) {
+        Some((mantissa, exponent)) => (
+            mantissa,
+            exponent
+                .parse::<i64>()
+                .map_err(|_| anyhow!("Failed to parse price: {price_str}"))?,
+        ),
+        None => (price_str, 0),
+    };
+
+    let (int_part, frac_part) = match mantissa.split_once('.') {
+        Some((i, f)) => (i, f),
+        None => (mantissa, ""),
+    };
+
+    let digits = format!("{int_part}{frac_part}");
+    if digits.is_empty() || !digits.bytes().all(|digit| digit.is_ascii_digit()) {
+        return Err(anyhow!("Failed to parse price: {price_str}"));
+    }
+
+    // How many of the digits are past the requested decimals once the exponent is applied.
+    let extra_places = (frac_part.len() as i64)
+        .checked_sub(exponent)
+        .and_then(|places| places.checked_sub(decimals.into()))
+        .ok_or_else(|| anyhow!("Price conversion overflow"))?;
+
+    let result = if extra_places >= 0 {
+        // Truncate the extra digits before parsing, so long inputs don't overflow.
+        let kept_digits = digits.len().saturating_sub(extra_places as usize);
+        if kept_digits == 0 {
+            0
+        } else {
+            digits[..kept_digits]
+                .parse::<u128>()
+                .map_err(|_| anyhow!("Price conversion overflow"))?
+        }
+    } else {
+        let price_int = digits
+            .parse::<u128>()
+            .map_err(|_| anyhow!("Price conversion overflow"))?;
+        let mul = u32::try_from(extra_places.unsigned_abs())
+            .ok()
+            .and_then(|places| 10_u128.checked_pow(places))
+            .ok_or_else(|| anyhow!("Price conversion overflow"))?;
+        price_int
+            .checked_mul(mul)
+            .ok_or_else(|| anyhow!("Price conversion overflow"))?
+    };
+
+    Ok(result)
+}
@@ -1,51 +1,125 @@
 use anyhow::Result;
+use ethabi::{Token, ethereum_types::U256};
 use seda_sdk_rs::{Process, elog, get_reveals, log};
 
+/// How many slots behind the median reveal a reveal may have been observed at when the
+/// tally inputs don't specify it (roughly a minute of Solana slots).
+const DEFAULT_MAX_SLOT_LAG: u64 = 150;
+
 pub fn tally_phase() -> Result<()> {
+    // Tally inputs are a comma-separated list of options (e.g., "maxSlotLag=300"):
+    // - "maxSlotLag=<slots>" is how far behind the median slot a reveal may be.
+    let tally_inputs = String::from_utf8(Process::get_inputs()).unwrap_or_default();
+    let mut max_slot_lag = DEFAULT_MAX_SLOT_LAG;
+    for option in tally_inputs.split(',') {
+        if let Some(("maxSlotLag", value)) = option.split_once('=') {
+            let Ok(slots) = value.parse() else {
+                elog!("Invalid slot lag in the tally inputs: {value}");
+                Process::error("Invalid tally inputs".as_bytes());
+                return Ok(());
+            };
+            max_slot_lag = slots;
+        }
+    }
+
     // Retrieve consensus reveals from the tally phase.
     let reveals = get_reveals()?;
-    let mut revealed_prices: Vec<f64> = Vec::with_capacity(reveals.len());
+    let mut revealed: Vec<(u64, Vec<u128>)> = Vec::with_capacity(reveals.len());
 
+    // Iterate over each reveal, parse its content as a list of unsigned integers (u128),
+    // the block id the prices were observed at followed by one price per token.
     for reveal in reveals {
         let reveal_bytes = &reveal.body.reveal;
-        let price = match reveal_bytes.as_slice().try_into() {
-            Ok(bytes) => f64::from_le_bytes(bytes),
-            Err(_) => {
-                elog!(
-                    "Failed to parse revealed price: expected 8 bytes for f64, got {} bytes",
-                    reveal_bytes.len()
-                );
-                continue;
-            }
-        };
+        if reveal_bytes.len() < 2 * size_of::<u128>()
+            || !reveal_bytes.len().is_multiple_of(size_of::<u128>())
+        {
+            elog!(
+                "Failed to parse revealed prices: expected a block id and prices as u128 values, got {} bytes",
+                reveal_bytes.len()
+            );
+            continue;
+        }
 
-        revealed_prices.push(price);
+        let mut values = reveal_bytes
+            .chunks_exact(size_of::<u128>())
+            .map(|chunk| u128::from_le_bytes(chunk.try_into().expect("chunk is 16 bytes")));
+        let block_id = values.next().unwrap_or_default();
+        let Ok(block_id) = u64::try_from(block_id) else {
+            elog!("Failed to parse revealed prices: block id {block_id} doesn't fit in a u64");
+            continue;
+        };
+        revealed.push((block_id, values.collect()));
     }
 
+    // Drop the executors that observed a much older slot than the median one, so a single executor
+    // revealing a far future slot can't make every other reveal look stale.
+    let mut block_ids: Vec<u128> = revealed
+        .iter()
+        .map(|(block_id, _)| u128::from(*block_id))
+        .collect();
+    block_ids.sort_unstable();
+    let median_block_id = if block_ids.is_empty() {
+        0
+    } else {
+        median_sorted(&block_ids)
+    };
+    let revealed_prices: Vec<Vec<u128>> = revealed
+        .into_iter()
+        .filter(|(block_id, _)| {
+            let lag = median_block_id.saturating_sub(u128::from(*block_id));
+            if lag > u128::from(max_slot_lag) {
+                elog!("Ignoring reveal observed at block {block_id}, {lag} slots behind");
+            }
+            lag <= u128::from(max_slot_lag)
+        })
+        .map(|(_, prices)| prices)
+        .collect();
+
     if revealed_prices.is_empty() {
         // If no valid prices were revealed, report an error indicating no consensus.
         Process::error("No consensus among revealed results".as_bytes());
         return Ok(());
     }
 
-    // If there are valid prices revealed, calculate the median price from price reports.
-    let final_price = median(&revealed_prices);
-    log!("Final median prices: {final_price:?}");
+    // All executors ran with the same inputs, so they should report the same number of tokens.
+    let token_count = revealed_prices[0].len();
+    if !revealed_prices.iter().all(|row| row.len() == token_count) {
+        elog!("Inconsistent number of prices in revealed results");
+        Process::error("Inconsistent row lengths in data reveals".as_bytes());
+        return Ok(());
+    }
+
+    // If there are valid prices revealed, calculate the median price of each token.
+    let final_prices: Vec<u128> = (0..token_count)
+        .map(|col| {
+            let mut vals: Vec<u128> = revealed_prices.iter().map(|row| row[col]).collect();
+            vals.sort_unstable();
+            median_sorted(&vals)
+        })
+        .collect();
+    log!("Final median prices: {final_prices:?}");
+
+    // Encode the final median prices as a EVM `uint256[]`.
+    let result = ethabi::encode(&[Token::Array(
+        final_prices
+            .into_iter()
+            .map(|price| Token::Uint(U256::from(price)))
+            .collect(),
+    )]);
 
     // Report the successful result in the tally phase.
-    Process::success(final_price.to_string().as_bytes());
+    Process::success(&result);
 
     Ok(())
 }
 
-/// Finds the median of a list of prices per price report using f64 values.
-fn median(data: &[f64]) -> f64 {
-    let mut vals = data.to_vec();
-    vals.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+/// Calculates the median of a sorted list of prices.
+fn median_sorted(vals: &[u128]) -> u128 {
     let mid = vals.len() / 2;
 
-    if vals.len() % 2 == 0 {
-        (vals[mid - 1] + vals[mid]) / 2.0
+    if vals.len().is_multiple_of(2) {
+        // safe average of two u128s without overflow
+        vals[mid - 1].midpoint(vals[mid])
     } else {
         vals[mid]
     }
@@ -1,12 +1,32 @@
 use anyhow::{Context, Result};
 use seda_sdk_rs::{Process, elog, log};
 
+/// How the prices reported by the different feeds are combined into one.
+enum Aggregation {
+    /// The median of all reported prices.
+    Median,
+    /// The average of all reported prices, weighted by each feed's 24h quote volume.
+    VolumeWeighted,
+}
+
 pub fn execution_phase() -> Result<()> {
-    // Expected to be in the format "symbolA-SymbolB,..." (e.g., "BTC-USDT").
+    // Expected to be in the format "symbolA-SymbolB" (e.g., "BTC-USDT").
+    // Optionally followed by the aggregation mode ("median" or "vwap") separated by a comma(,).
     let dr_inputs_raw = String::from_utf8(Process::get_inputs())?;
     log!("Fetching price for pair: {dr_inputs_raw}");
 
-    let dr_inputs: Vec<&str> = dr_inputs_raw.split("-").collect();
+    let (pair, aggregation) = match dr_inputs_raw.split_once(',') {
+        Some((pair, "median")) => (pair, Aggregation::Median),
+        Some((pair, "vwap")) => (pair, Aggregation::VolumeWeighted),
+        Some((_, mode)) => {
+            elog!("Invalid aggregation mode: {mode}. Expected one of: median, vwap");
+            Process::error("Invalid aggregation mode".as_bytes());
+            return Ok(());
+        }
+        None => (dr_inputs_raw.as_str(), Aggregation::Median),
+    };
+
+    let dr_inputs: Vec<&str> = pair.split("-").collect();
     let symbol_a = dr_inputs
         .first()
         .context("format should be tokenA-tokenB")?
@@ -17,7 +37,10 @@ pub fn execution_phase() -> Result<()> {
         .to_uppercase();
 
     let mut prices = Vec::with_capacity(3);
+    let mut weighted_prices = Vec::with_capacity(3);
     let decimals: f32 = 1_000_000.0;
+    // Volumes are scaled to fixed point as well, so the volumes below 1 of illiquid venues still weigh in.
+    let volume_decimals: f64 = 1_000_000.0;
 
     // Fetch prices from multiple feeds.
     // Each feed is expected to return a price in the format of f32.
@@ -28,9 +51,18 @@ pub fn execution_phase() -> Result<()> {
         crate::feeds::okx::fetch_token_price(&symbol_a, &symbol_b),
     ] {
         match response {
-            Ok(price) => {
-                log!("Got reported price: {price}");
-                prices.push((price * decimals) as u128);
+            Ok(report) => {
+                log!(
+                    "Got reported price: {}, quote volume: {:?}",
+                    report.price,
+                    report.quote_volume
+                );
+                let price = (report.price * decimals) as u128;
+                prices.push(price);
+                // Feeds that don't report a volume can't take part in the weighted average.
+                if let Some(quote_volume) = report.quote_volume {
+                    weighted_prices.push((price, (quote_volume * volume_decimals) as u128));
+                }
             }
             // If any of the responses fail, log the error and continue.
             Err(error) => {
@@ -39,11 +71,27 @@ pub fn execution_phase() -> Result<()> {
         }
     }
 
-    let median_price = crate::median(&prices);
-    log!("Median price: {median_price}");
+    let final_price = match aggregation {
+        Aggregation::Median => {
+            let median_price = crate::median(&prices);
+            log!("Median price: {median_price}");
+            median_price
+        }
+        Aggregation::VolumeWeighted => match crate::volume_weighted_average(&weighted_prices) {
+            Some(weighted_price) => {
+                log!("Volume weighted price: {weighted_price}");
+                weighted_price
+            }
+            None => {
+                elog!("No volume data available for {weighted_prices:?}");
+                Process::error("No volume data available for a volume weighted price".as_bytes());
+                return Ok(());
+            }
+        },
+    };
 
     // Report the successful result back to the SEDA network.
-    Process::success(&median_price.to_le_bytes());
+    Process::success(&final_price.to_le_bytes());
 
     Ok(())
 }
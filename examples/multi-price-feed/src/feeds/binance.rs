@@ -1,8 +1,13 @@
 use anyhow::Result;
+use shared::UrlBuilder;
 
-const API_URL: &str = "https://api.binance.com/api/v3/ticker/price?symbol=";
+use crate::feeds::PriceReport;
 
-pub fn fetch_token_price(symbol_a: &str, symbol_b: &str) -> Result<f32> {
-    let url = format!("{API_URL}{symbol_a}{symbol_b}",);
-    crate::feeds::make_request_price("Binance", url)
+const API_URL: &str = "https://api.binance.com/api/v3/ticker/24hr";
+
+pub fn fetch_token_price(symbol_a: &str, symbol_b: &str) -> Result<PriceReport> {
+    let url = UrlBuilder::new(API_URL)
+        .query("symbol", &[symbol_a, symbol_b].concat())
+        .build();
+    crate::feeds::make_request_24hr_ticker("Binance", url)
 }
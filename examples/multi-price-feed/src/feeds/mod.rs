@@ -5,6 +5,12 @@ pub mod binance;
 pub mod mexc;
 pub mod okx;
 
+/// A price reported by a feed, along with its 24h quote volume when the API provides one.
+pub struct PriceReport {
+    pub price: f32,
+    pub quote_volume: Option<f64>,
+}
+
 fn _make_request<T: serde::de::DeserializeOwned>(api: &str, url: String) -> anyhow::Result<T> {
     let response = seda_sdk_rs::http_fetch(url, None);
 
@@ -20,20 +26,28 @@ fn _make_request<T: serde::de::DeserializeOwned>(api: &str, url: String) -> anyh
 }
 
 #[derive(Deserialize)]
-struct StringPriceResponse {
-    price: String,
+#[serde(rename_all = "camelCase")]
+struct Ticker24hrResponse {
+    last_price: String,
+    quote_volume: String,
 }
 
 #[inline]
-fn make_request_price(api: &str, url: String) -> anyhow::Result<f32> {
-    let data = _make_request::<StringPriceResponse>(api, url)?;
-    let price: f32 = data.price.parse()?;
-    Ok(price)
+fn make_request_24hr_ticker(api: &str, url: String) -> anyhow::Result<PriceReport> {
+    let data = _make_request::<Ticker24hrResponse>(api, url)?;
+    let price: f32 = data.last_price.parse()?;
+    let quote_volume: f64 = data.quote_volume.parse()?;
+    Ok(PriceReport {
+        price,
+        quote_volume: Some(quote_volume),
+    })
 }
 
 #[derive(Deserialize)]
 struct LastPriceResponse {
     last: String,
+    /// The 24h volume in the base currency, only reported by some APIs (e.g. OKX).
+    vol24h: Option<String>,
 }
 
 #[derive(Deserialize)]
@@ -42,13 +56,21 @@ struct VecLastPriceResponse {
 }
 
 #[inline]
-fn make_request_last_prices(api: &str, url: String) -> anyhow::Result<f32> {
+fn make_request_last_prices(api: &str, url: String) -> anyhow::Result<PriceReport> {
     let data = _make_request::<VecLastPriceResponse>(api, url)?;
     match data.data.first() {
         Some(info) => {
             let price: f32 = info.last.parse()?;
-            Ok(price)
+            // Convert the base volume to a quote volume so it can be weighted against other feeds.
+            let quote_volume = match &info.vol24h {
+                Some(volume) => Some(volume.parse::<f64>()? * info.last.parse::<f64>()?),
+                None => None,
+            };
+            Ok(PriceReport {
+                price,
+                quote_volume,
+            })
         }
-        None => Err(Error::msg("No data returned by Mexc")),
+        None => Err(Error::msg(format!("No data returned by {api}"))),
     }
 }
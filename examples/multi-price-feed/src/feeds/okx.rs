@@ -1,8 +1,13 @@
 use anyhow::Result;
+use shared::UrlBuilder;
 
-const API_URL: &str = "https://www.okx.com/api/v5/market/ticker?instId=";
+use crate::feeds::PriceReport;
 
-pub fn fetch_token_price(symbol_a: &str, symbol_b: &str) -> Result<f32> {
-    let url = format!("{API_URL}{symbol_a}-{symbol_b}");
+const API_URL: &str = "https://www.okx.com/api/v5/market/ticker";
+
+pub fn fetch_token_price(symbol_a: &str, symbol_b: &str) -> Result<PriceReport> {
+    let url = UrlBuilder::new(API_URL)
+        .query("instId", &[symbol_a, "-", symbol_b].concat())
+        .build();
     crate::feeds::make_request_last_prices("Okx", url)
 }
@@ -28,7 +28,7 @@ pub fn median(data: &[u128]) -> u128 {
     let mut sorted_data = data.to_vec();
     sorted_data.sort_unstable();
 
-    if m % 2 == 0 {
+    if m.is_multiple_of(2) {
         // safe average of two u128s without overflow
         let a = sorted_data[m / 2 - 1];
         let b = sorted_data[m / 2];
@@ -37,3 +37,17 @@ pub fn median(data: &[u128]) -> u128 {
         sorted_data[m / 2]
     }
 }
+
+/// Finds the volume weighted average of a list of `(price, volume)` reports.
+/// Returns `None` if there is no volume to weight by or the weighted sum overflows.
+pub fn volume_weighted_average(data: &[(u128, u128)]) -> Option<u128> {
+    let mut weighted_sum: u128 = 0;
+    let mut total_volume: u128 = 0;
+
+    for &(price, volume) in data {
+        weighted_sum = weighted_sum.checked_add(price.checked_mul(volume)?)?;
+        total_volume = total_volume.checked_add(volume)?;
+    }
+
+    weighted_sum.checked_div(total_volume)
+}
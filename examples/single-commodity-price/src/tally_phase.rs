@@ -2,22 +2,94 @@ use anyhow::Result;
 use ethabi::{Token, ethereum_types::U256};
 use seda_sdk_rs::{Process, elog, get_reveals, log};
 
+use crate::execution_phase::VALID_FIELDS;
+
+/// The prices of a reveal, along with the time in epoch milliseconds they were observed at.
+struct FieldPrices {
+    prices: Vec<u128>,
+    observed_at: u128,
+}
+
 pub fn tally_phase() -> Result<()> {
+    // Tally inputs are a comma-separated list of options (e.g., "timestamped,maxAge=60000,now=1753710744000"):
+    // - "timestamped" reports the median time the prices were observed at alongside them.
+    // - "maxAge=<ms>" discards the reveals whose prices are older than that many milliseconds.
+    // - "now=<unix ms>" is the time the age is measured from, the median observation time of the reveals otherwise.
+    let tally_inputs = String::from_utf8(Process::get_inputs()).unwrap_or_default();
+    let mut timestamped = false;
+    let mut max_age_ms: Option<u128> = None;
+    let mut reference_ms: Option<u128> = None;
+    for option in tally_inputs.split(',') {
+        match option.split_once('=') {
+            Some(("maxAge", value)) => max_age_ms = value.parse().ok(),
+            Some(("now", value)) => reference_ms = value.parse().ok(),
+            _ if option == "timestamped" => timestamped = true,
+            _ => {}
+        }
+    }
+
     // Retrieve consensus reveals from the tally phase.
     let reveals = get_reveals()?;
-    let mut revealed_prices: Vec<u128> = Vec::with_capacity(reveals.len());
 
-    // Iterate over each reveal, parse its content as an unsigned integer (u128), and store it in the prices array.
-    for reveal in reveals {
-        let price = match reveal.body.reveal.as_slice().try_into() {
-            Ok(price) => u128::from_le_bytes(price),
+    // Iterate over each reveal, parse its content as the requested fields followed by their prices (u128)
+    // and the time they were observed at.
+    let parsed: Vec<(Vec<u128>, FieldPrices)> = reveals
+        .iter()
+        .filter_map(|reveal| match parse_reveal(&reveal.body.reveal) {
+            Ok(reveal) => Some(reveal),
             Err(err) => {
                 elog!("Failed to parse revealed prices: {err}");
-                continue;
+                None
             }
-        };
+        })
+        .collect();
 
-        revealed_prices.push(price);
+    // The expected fields are the ones most reveals were made with, so an outlier can't discard the honest
+    // reveals by revealing first.
+    let field_ids = most_common(
+        &parsed
+            .iter()
+            .map(|(ids, _)| ids.clone())
+            .collect::<Vec<_>>(),
+    );
+    let mut revealed_prices: Vec<FieldPrices> = Vec::with_capacity(parsed.len());
+    for (ids, prices) in parsed {
+        if field_ids.as_ref() != Some(&ids) {
+            elog!("Ignoring reveal with mismatching fields: {ids:?}");
+            continue;
+        }
+        revealed_prices.push(prices);
+    }
+
+    // Discard the reveals that are older than the maximum age.
+    if let Some(max_age_ms) = max_age_ms
+        && !revealed_prices.is_empty()
+    {
+        let reference_ms = reference_ms.unwrap_or_else(|| {
+            median(
+                &revealed_prices
+                    .iter()
+                    .map(|prices| prices.observed_at)
+                    .collect::<Vec<_>>(),
+            )
+        });
+
+        revealed_prices.retain(|prices| {
+            let age = reference_ms.saturating_sub(prices.observed_at);
+            if age > max_age_ms {
+                elog!(
+                    "Ignoring stale reveal observed at {}, {age}ms older than {reference_ms}",
+                    prices.observed_at
+                );
+            }
+            age <= max_age_ms
+        });
+
+        if revealed_prices.is_empty() {
+            elog!("Every reveal is older than {max_age_ms}ms");
+            Process::error("All revealed prices are stale".as_bytes());
+            return Ok(());
+        }
     }
 
     // If no valid prices were revealed, report an error indicating no consensus.
@@ -26,37 +98,107 @@ pub fn tally_phase() -> Result<()> {
         return Ok(());
     }
 
-    // If there are valid prices revealed, calculate the median price from price reports.
-    let final_prices = median(&revealed_prices);
+    // If there are valid prices revealed, calculate the median price of every field from price reports.
+    let final_prices: Vec<Token> = (0..revealed_prices[0].prices.len())
+        .map(|index| {
+            let prices: Vec<u128> = revealed_prices
+                .iter()
+                .map(|prices| prices.prices[index])
+                .collect();
+            Token::Uint(U256::from(median(&prices)))
+        })
+        .collect();
     log!("Final median prices: {final_prices:?}");
 
+    // A single field is encoded as a EVM `uint256`, several fields as a `uint256[]` in the requested order.
+    let mut tokens = match final_prices.as_slice() {
+        [final_price] => vec![final_price.clone()],
+        _ => vec![Token::Array(final_prices)],
+    };
+
+    // The timestamped layout follows the prices with the median observation time as a `uint64`.
+    if timestamped {
+        let observed_at = median(
+            &revealed_prices
+                .iter()
+                .map(|prices| prices.observed_at)
+                .collect::<Vec<_>>(),
+        );
+        log!("Observed at: {observed_at}");
+        tokens.push(Token::Uint(U256::from(observed_at)));
+    }
+
     // Report the successful result in the tally phase.
-    let result = ethabi::encode(&[final_prices]);
-    Process::success(&result);
+    Process::success(&ethabi::encode(&tokens));
 
     Ok(())
 }
 
-/// Finds the median of a list of prices per price report.
-fn median(data: &[u128]) -> Token {
-    let m = data.len();
+/// Finds the value most reveals agree on, the earliest one on a tie.
+fn most_common<T: Clone + PartialEq>(values: &[T]) -> Option<T> {
+    values
+        .iter()
+        .rev()
+        .max_by_key(|value| values.iter().filter(|other| other == value).count())
+        .cloned()
+}
+
+/// Parses a reveal made of the number of fields, the index of every field, the price of every field and
+/// the time the prices were observed at, each as a little endian `u128`.
+fn parse_reveal(reveal: &[u8]) -> Result<(Vec<u128>, FieldPrices)> {
+    if !reveal.len().is_multiple_of(size_of::<u128>()) {
+        return Err(anyhow::anyhow!(
+            "expected a multiple of {} bytes, got {}",
+            size_of::<u128>(),
+            reveal.len()
+        ));
+    }
+
+    let values: Vec<u128> = reveal
+        .chunks_exact(size_of::<u128>())
+        .map(|chunk| u128::from_le_bytes(chunk.try_into().expect("chunk is 16 bytes")))
+        .collect();
+    let Some((&field_count, values)) = values.split_first() else {
+        return Err(anyhow::anyhow!("empty reveal"));
+    };
+    let Some((&observed_at, values)) = values.split_last() else {
+        return Err(anyhow::anyhow!("missing observation time"));
+    };
+
+    let field_count = usize::try_from(field_count)?;
+    if field_count == 0 || field_count.checked_mul(2) != Some(values.len()) {
+        return Err(anyhow::anyhow!(
+            "expected {field_count} fields and prices, got {} values",
+            values.len()
+        ));
+    }
 
-    if m == 0 {
-        Process::error("No valid data available for median calculation".as_bytes());
+    let (ids, prices) = values.split_at(field_count);
+    if ids.iter().any(|&id| id >= VALID_FIELDS.len() as u128) {
+        return Err(anyhow::anyhow!("unknown fields {ids:?}"));
     }
 
+    Ok((
+        ids.to_vec(),
+        FieldPrices {
+            prices: prices.to_vec(),
+            observed_at,
+        },
+    ))
+}
+
+/// Finds the median of a list of prices per price report.
+fn median(data: &[u128]) -> u128 {
     let mut sorted_data = data.to_vec();
     sorted_data.sort_unstable();
 
-    let median_price = if m % 2 == 0 {
+    let m = sorted_data.len();
+    if m.is_multiple_of(2) {
         // safe average of two u128s without overflow
         let a = sorted_data[m / 2 - 1];
         let b = sorted_data[m / 2];
         a.midpoint(b)
     } else {
         sorted_data[m / 2]
-    };
-
-    // convert to Token::Uint for encoding
-    Token::Uint(U256::from(median_price))
+    }
 }
@@ -1,14 +1,16 @@
 use anyhow::Result;
 #[cfg(any(feature = "testnet", feature = "mainnet"))]
 use seda_sdk_rs::{Process, elog, log, proxy_http_fetch};
+#[cfg(any(feature = "testnet", feature = "mainnet"))]
+use shared::UrlBuilder;
 
 #[cfg(feature = "testnet")]
-const API_URL: &str = "http://98.84.79.123:5384/proxy/uslf_q/";
+pub const API_URL: &str = "http://98.84.79.123:5384/proxy/uslf_q/";
 #[cfg(feature = "testnet")]
 const PROXY_PUBLIC_KEY: &str = "0375038bc3e61dc2a52e24ff207a5753e38d020a06fff9efc8ec96875f72f4d081";
 
 #[cfg(feature = "mainnet")]
-const API_URL: &str = "http://seda-proxy.dxfeed.com:5384/proxy/uslf_q/";
+pub const API_URL: &str = "http://seda-proxy.dxfeed.com:5384/proxy/uslf_q/";
 #[cfg(feature = "mainnet")]
 const PROXY_PUBLIC_KEY: &str = "021dd035f760061e2833581d4ab50440a355db0ac98e489bf63a5dbc0e89e4af79";
 
@@ -18,20 +20,17 @@ pub fn execution_phase() -> Result<()> {
     Ok(())
 }
 
+/// The fields available from the Quote events this program fetches, `ask` being the default.
+/// The tally reads them from the verified response.
+#[cfg(any(feature = "testnet", feature = "mainnet"))]
+pub const QUOTE_FIELDS: &[&str] = &["ask", "bid", "mid"];
+
 #[cfg(any(feature = "testnet", feature = "mainnet"))]
 pub fn execution_phase() -> Result<()> {
     use crate::VerificationData;
 
-    #[cfg(not(feature = "test"))]
-    if Process::replication_factor() != 1 {
-        elog!(
-            "Replication factor must be 1 for the single price feed verification oracle program."
-        );
-        Process::error("Invalid replication factor".as_bytes());
-        return Ok(());
-    }
-
     // Expected to be in the format "symbol,..." (e.g., "AAPL" or "GOOG").
+    // Optionally followed by the price fields (e.g., "bid,ask,mid") separated by a hyphen(-).
     let dr_inputs_raw = String::from_utf8(Process::get_inputs())?;
     if dr_inputs_raw.is_empty() {
         // If no input is provided, log an error and return.
@@ -40,10 +39,34 @@ pub fn execution_phase() -> Result<()> {
         return Ok(());
     }
 
+    let parts: Vec<&str> = dr_inputs_raw.split('-').collect();
+    let (symbol, fields) = match parts.as_slice() {
+        // The fields keep the requested order, duplicates included.
+        [symbol, fields] => (*symbol, fields.split(',').collect::<Vec<_>>()),
+        [symbol] => (*symbol, QUOTE_FIELDS[..1].to_vec()),
+        _ => {
+            elog!("Invalid input format");
+            Process::error("Invalid input format".as_bytes());
+            return Ok(());
+        }
+    };
+
+    // Validate that each field is available from a Quote event, rather than silently dropping the invalid ones.
+    let invalid_fields: Vec<&str> = fields
+        .iter()
+        .filter(|field| !QUOTE_FIELDS.contains(field))
+        .copied()
+        .collect();
+    if !invalid_fields.is_empty() {
+        elog!("Invalid fields: {invalid_fields:?}, expected any of {QUOTE_FIELDS:?}");
+        Process::error(format!("Invalid fields: {invalid_fields:?}").as_bytes());
+        return Ok(());
+    }
+
     // Log the asset being fetched as part of the Execution Standard Out.
-    log!("Fetching price for: {dr_inputs_raw}");
+    log!("Fetching price for: {symbol}, using {fields:?}");
 
-    let url = [API_URL, &dr_inputs_raw].concat();
+    let url = UrlBuilder::new(API_URL).segment(symbol).build();
     let response = proxy_http_fetch(url, Some(PROXY_PUBLIC_KEY.to_string()), None);
 
     // Handle the case where the HTTP request failed or was rejected.
@@ -59,7 +82,8 @@ pub fn execution_phase() -> Result<()> {
 
     let data = VerificationData {
         response,
-        symbol: dr_inputs_raw,
+        symbol: symbol.to_string(),
+        fields: fields.iter().map(ToString::to_string).collect(),
     };
     let data_json = serde_json::to_vec(&data)?;
     Process::success(&data_json);
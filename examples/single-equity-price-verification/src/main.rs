@@ -8,6 +8,8 @@ mod tally_phase;
 #[derive(serde::Serialize, serde::Deserialize)]
 struct VerificationData {
     symbol: String,
+    /// The requested price fields, in order.
+    fields: Vec<String>,
     response: HttpFetchResponse,
 }
 
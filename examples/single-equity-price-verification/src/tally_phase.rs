@@ -1,8 +1,30 @@
 use anyhow::Result;
 use ethabi::{Token, ethereum_types::U256};
 use seda_sdk_rs::{HttpFetchMethod, Process, elog, get_unfiltered_reveals, log};
+use shared::UrlBuilder;
 
-use crate::VerificationData;
+use crate::{
+    VerificationData,
+    execution_phase::{API_URL, QUOTE_FIELDS},
+};
+
+/// The public keys of the Data Proxy the verified responses must be signed with. Several keys can be accepted
+/// while the proxy rotates them, the execution phase `PROXY_PUBLIC_KEY` being one of them.
+#[cfg(all(feature = "testnet", not(feature = "test")))]
+const PROXY_PUBLIC_KEYS: &[&str] =
+    &["0375038bc3e61dc2a52e24ff207a5753e38d020a06fff9efc8ec96875f72f4d081"];
+
+#[cfg(feature = "mainnet")]
+const PROXY_PUBLIC_KEYS: &[&str] =
+    &["021dd035f760061e2833581d4ab50440a355db0ac98e489bf63a5dbc0e89e4af79"];
+
+/// The key the test Data Proxy signs with, derived from the private key `1`.
+#[cfg(feature = "test")]
+const PROXY_PUBLIC_KEYS: &[&str] =
+    &["0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798"];
+
+/// The error of a response that was signed, but not by any of the [`PROXY_PUBLIC_KEYS`].
+const UNEXPECTED_PUBLIC_KEY: &str = "Unexpected proxy public key";
 
 // {
 // 	"Quote": {
@@ -30,46 +52,279 @@ struct EquityPriceResponse {
     quote: serde_json::value::Map<String, serde_json::value::Value>,
 }
 
+/// The prices read from a verified response, along with the time in epoch milliseconds they were observed at.
+struct FieldPrices {
+    prices: Vec<u128>,
+    observed_at: u128,
+}
+
 pub fn tally_phase() -> Result<()> {
-    // Retrieve consensus reveals from the tally phase.
+    // Tally inputs are a comma-separated list of options (e.g., "symbol=AAPL,fields=bid;ask,timestamped"):
+    // - "symbol=<symbol>" is the symbol of the execution inputs, which every response must answer.
+    // - "fields=<field>;<field>" are the fields of the execution inputs in order, "ask" otherwise.
+    // - "timestamped" reports the median time the prices were observed at alongside them.
+    // - "maxAge=<ms>" discards the reveals whose prices are older than that many milliseconds.
+    // - "now=<unix ms>" is the time the age is measured from, the median observation time of the reveals otherwise.
+    //   With a single reveal that's the observation time itself, so a maximum age needs it to have any effect.
+    let tally_inputs = String::from_utf8(Process::get_inputs()).unwrap_or_default();
+    let mut timestamped = false;
+    let mut max_age_ms: Option<u128> = None;
+    let mut reference_ms: Option<u128> = None;
+    let mut symbol: Option<&str> = None;
+    let mut fields: Vec<&str> = vec!["ask"];
+    for option in tally_inputs.split(',') {
+        match option.split_once('=') {
+            Some(("symbol", value)) if !value.is_empty() => symbol = Some(value),
+            Some(("fields", value)) => fields = value.split(';').collect(),
+            Some(("maxAge", value)) => max_age_ms = value.parse().ok(),
+            Some(("now", value)) => reference_ms = value.parse().ok(),
+            _ if option == "timestamped" => timestamped = true,
+            _ => {}
+        }
+    }
+
+    // The URL every response must answer is derived again from the requested symbol, rather than trusting
+    // the symbol the executors revealed, and so are the fields every reveal must have read.
+    let Some(symbol) = symbol else {
+        elog!("No symbol provided in the tally inputs.");
+        Process::error("No symbol provided".as_bytes());
+        return Ok(());
+    };
+    if !fields.iter().all(|field| QUOTE_FIELDS.contains(field)) {
+        elog!("Invalid fields in the tally inputs: {fields:?}, expected any of {QUOTE_FIELDS:?}");
+        Process::error("Invalid tally inputs".as_bytes());
+        return Ok(());
+    }
+    let expected_url = UrlBuilder::new(API_URL).segment(symbol).build();
+
+    // Retrieve every reveal, the signatures rather than the consensus decide which ones can be trusted.
     let reveals = get_unfiltered_reveals()?;
+    let mut revealed_prices: Vec<FieldPrices> = Vec::with_capacity(reveals.len());
+    let mut unexpected_signer = false;
 
-    if reveals.len() != 1 {
-        elog!(
-            "Expected exactly one reveal(replication factor 1), found {}",
-            reveals.len()
+    // Iterate over each reveal, verify the signature of its proxy response and read the requested fields
+    // from the verified ones, discarding the failed executions and unverifiable responses.
+    for reveal in reveals {
+        if reveal.body.exit_code != 0 {
+            elog!("Ignoring failed execution");
+            continue;
+        }
+
+        let prices = match verified_prices(&reveal.body.reveal, symbol, &fields, &expected_url) {
+            Ok(verified) => verified,
+            Err(err) => {
+                elog!("Ignoring unverifiable reveal: {err}");
+                unexpected_signer |= err.to_string() == UNEXPECTED_PUBLIC_KEY;
+                continue;
+            }
+        };
+
+        revealed_prices.push(prices);
+    }
+
+    // If every signed response came from an unknown Data Proxy, report it rather than a missing reveal.
+    if revealed_prices.is_empty() && unexpected_signer {
+        Process::error(UNEXPECTED_PUBLIC_KEY.as_bytes());
+        return Ok(());
+    }
+
+    // Discard the reveals that are older than the maximum age.
+    if let Some(max_age_ms) = max_age_ms
+        && !revealed_prices.is_empty()
+    {
+        let reference_ms = reference_ms.unwrap_or_else(|| {
+            median(
+                &revealed_prices
+                    .iter()
+                    .map(|prices| prices.observed_at)
+                    .collect::<Vec<_>>(),
+            )
+        });
+
+        revealed_prices.retain(|prices| {
+            let age = reference_ms.saturating_sub(prices.observed_at);
+            if age > max_age_ms {
+                elog!(
+                    "Ignoring stale reveal observed at {}, {age}ms older than {reference_ms}",
+                    prices.observed_at
+                );
+            }
+            age <= max_age_ms
+        });
+
+        if revealed_prices.is_empty() {
+            elog!("Every reveal is older than {max_age_ms}ms");
+            Process::error("All revealed prices are stale".as_bytes());
+            return Ok(());
+        }
+    }
+
+    // If no verified prices were revealed, report an error.
+    if revealed_prices.is_empty() {
+        Process::error("No verified reveals".as_bytes());
+        return Ok(());
+    }
+
+    // Calculate the median price of every field from the verified reveals.
+    let prices: Vec<Token> = (0..revealed_prices[0].prices.len())
+        .map(|index| {
+            let prices: Vec<u128> = revealed_prices
+                .iter()
+                .map(|prices| prices.prices[index])
+                .collect();
+            Token::Uint(U256::from(median(&prices)))
+        })
+        .collect();
+    log!("Final median prices: {prices:?}");
+
+    // A single field is encoded as a EVM `uint256`, several fields as a `uint256[]` in the requested order.
+    let mut tokens = match prices.as_slice() {
+        [price] => vec![price.clone()],
+        _ => vec![Token::Array(prices)],
+    };
+
+    // The timestamped layout follows the prices with the median observation time as a `uint64`.
+    if timestamped {
+        let observed_at = median(
+            &revealed_prices
+                .iter()
+                .map(|prices| prices.observed_at)
+                .collect::<Vec<_>>(),
         );
-        return Err(anyhow::anyhow!("Invalid number of reveals"));
+        tokens.push(Token::Uint(U256::from(observed_at)));
     }
+    let result = ethabi::encode(&tokens);
 
-    let data: VerificationData = serde_json::from_slice(&reveals[0].body.reveal)?;
+    // Report the successful result in the tally phase.
+    Process::success(&result);
+
+    Ok(())
+}
+
+/// Verifies the signature and URL of the proxy response of a reveal, then reads the requested fields of the
+/// requested symbol from it.
+fn verified_prices(
+    reveal: &[u8],
+    symbol: &str,
+    fields: &[&str],
+    expected_url: &str,
+) -> Result<FieldPrices> {
+    let data: VerificationData = serde_json::from_slice(reveal)?;
 
     let verified = data
         .response
         .proxy_verification(HttpFetchMethod::Get, None)?;
 
     if !verified {
-        elog!("Signature verification failed for the proxy response: {verified}");
         return Err(anyhow::anyhow!("Signature verification failed"));
     }
 
+    // A valid signature only proves the response wasn't tampered with, it must also come from a known Data Proxy.
+    let public_key = data
+        .response
+        .headers
+        .get("x-seda-publickey")
+        .map(String::as_str)
+        .unwrap_or_default();
+    // Compare the keys case insensitively, an upper case hex key is the same key.
+    if !PROXY_PUBLIC_KEYS
+        .iter()
+        .any(|key| key.eq_ignore_ascii_case(public_key))
+    {
+        elog!("Response signed by an unexpected proxy public key: {public_key}");
+        return Err(anyhow::anyhow!(UNEXPECTED_PUBLIC_KEY));
+    }
+
+    // The signature covers the URL, so a response for another asset can't be passed off as the requested one.
+    if data.response.url != expected_url {
+        elog!(
+            "Response answers an unexpected URL: {}, expected {expected_url}",
+            data.response.url
+        );
+        return Err(anyhow::anyhow!("Unexpected response URL"));
+    }
+    if data.symbol != symbol {
+        return Err(anyhow::anyhow!("Unexpected symbol: {}", data.symbol));
+    }
+    // The fields aren't part of the response, so they must be the requested ones as well.
+    if !data
+        .fields
+        .iter()
+        .map(String::as_str)
+        .eq(fields.iter().copied())
+    {
+        return Err(anyhow::anyhow!("Unexpected fields: {:?}", data.fields));
+    }
+
     // Parse the API response as defined earlier.
     let response_data = serde_json::from_slice::<EquityPriceResponse>(&data.response.bytes)?;
 
-    let price = response_data
+    let quote = response_data
         .quote
-        .get(&format!("{}:USLF24", data.symbol))
-        .and_then(|quote| quote.get("askPrice"))
-        .and_then(|price| price.as_f64())
+        .get(&format!("{symbol}:USLF24"))
         .ok_or_else(|| anyhow::anyhow!("Price not found in response"))?;
-    let price_lossless = Token::Uint(U256::from((price * 100.0) as u128));
-    log!("Fetched price: {price_lossless:?}");
+    let (prices, observed_at) = quote_fields(quote, fields)?;
+    log!("Verified prices: {prices:?}, observed at: {observed_at}");
 
-    // Encode the price as a EVM `uint256`.
-    let result = ethabi::encode(&[price_lossless]);
+    Ok(FieldPrices {
+        prices,
+        observed_at,
+    })
+}
 
-    // Report the successful result in the tally phase.
-    Process::success(&result);
+/// Finds the median of a list of prices per price report.
+fn median(data: &[u128]) -> u128 {
+    let mut sorted_data = data.to_vec();
+    sorted_data.sort_unstable();
 
-    Ok(())
+    let m = sorted_data.len();
+    if m.is_multiple_of(2) {
+        // safe average of two u128s without overflow
+        let a = sorted_data[m / 2 - 1];
+        let b = sorted_data[m / 2];
+        a.midpoint(b)
+    } else {
+        sorted_data[m / 2]
+    }
+}
+
+/// Reads the requested fields from a Quote event, each as a `u128` with 2 decimal precision, along with
+/// the time in epoch milliseconds of the oldest `bidTime` or `askTime` they depend on.
+fn quote_fields(quote: &serde_json::Value, fields: &[&str]) -> Result<(Vec<u128>, u128)> {
+    // A missing time counts as the epoch, so its prices never pass a maximum age.
+    let time = |key: &str| {
+        quote
+            .get(key)
+            .and_then(|time| time.as_u64())
+            .unwrap_or_default() as u128
+    };
+    let observed_at = fields
+        .iter()
+        .map(|field| match *field {
+            "bid" => time("bidTime"),
+            "ask" => time("askTime"),
+            _ => time("bidTime").min(time("askTime")),
+        })
+        .min()
+        .unwrap_or_default();
+
+    let price = |key: &str| {
+        quote
+            .get(key)
+            .and_then(|price| price.as_f64())
+            .map(|price| (price * 100.0) as u128)
+            .ok_or_else(|| anyhow::anyhow!("Price not found in response"))
+    };
+
+    fields
+        .iter()
+        .map(|field| match *field {
+            "bid" => price("bidPrice"),
+            "ask" => price("askPrice"),
+            // The midpoint of the fixed point prices, so it's rounded the same way as the other dxFeed programs.
+            "mid" => Ok(price("bidPrice")?.midpoint(price("askPrice")?)),
+            field => Err(anyhow::anyhow!("Invalid field: {field}")),
+        })
+        .collect::<Result<Vec<_>>>()
+        .map(|prices| (prices, observed_at))
 }
@@ -1,6 +1,8 @@
 use anyhow::Result;
 #[cfg(any(feature = "testnet", feature = "mainnet"))]
 use seda_sdk_rs::{Process, elog, log, proxy_http_fetch};
+#[cfg(any(feature = "testnet", feature = "mainnet"))]
+use shared::UrlBuilder;
 
 #[cfg(feature = "testnet")]
 const API_URL: &str = "http://98.84.79.123:5384/proxy/uslf_q/";
@@ -44,9 +46,21 @@ struct EquityPriceResponse {
     quote: serde_json::value::Map<String, serde_json::value::Value>,
 }
 
+/// The price fields that can be requested, the reveal refers to them by their index in this list:
+/// - `bid`: the `bidPrice` of a Quote event.
+/// - `ask`: the `askPrice` of a Quote event.
+/// - `mid`: the midpoint of the fixed point `bidPrice` and `askPrice` of a Quote event.
+/// - `last`: the `price` of a Trade event, which this program doesn't fetch.
+pub const VALID_FIELDS: &[&str] = &["bid", "ask", "mid", "last"];
+
+/// The fields available from the Quote events this program fetches, `ask` being the default.
+#[cfg(any(feature = "testnet", feature = "mainnet"))]
+const QUOTE_FIELDS: &[&str] = &["ask", "bid", "mid"];
+
 #[cfg(any(feature = "testnet", feature = "mainnet"))]
 pub fn execution_phase() -> Result<()> {
     // Expected to be in the format "symbol,..." (e.g., "AAPL" or "GOOG").
+    // Optionally followed by the price fields (e.g., "bid,ask,mid") separated by a hyphen(-).
     let dr_inputs_raw = String::from_utf8(Process::get_inputs())?;
     if dr_inputs_raw.is_empty() {
         // If no input is provided, log an error and return.
@@ -55,10 +69,34 @@ pub fn execution_phase() -> Result<()> {
         return Ok(());
     }
 
+    let parts: Vec<&str> = dr_inputs_raw.split('-').collect();
+    let (symbol, fields) = match parts.as_slice() {
+        // The fields keep the requested order, duplicates included.
+        [symbol, fields] => (*symbol, fields.split(',').collect::<Vec<_>>()),
+        [symbol] => (*symbol, QUOTE_FIELDS[..1].to_vec()),
+        _ => {
+            elog!("Invalid input format");
+            Process::error("Invalid input format".as_bytes());
+            return Ok(());
+        }
+    };
+
+    // Validate that each field is available from a Quote event, rather than silently dropping the invalid ones.
+    let invalid_fields: Vec<&str> = fields
+        .iter()
+        .filter(|field| !QUOTE_FIELDS.contains(field))
+        .copied()
+        .collect();
+    if !invalid_fields.is_empty() {
+        elog!("Invalid fields: {invalid_fields:?}, expected any of {QUOTE_FIELDS:?}");
+        Process::error(format!("Invalid fields: {invalid_fields:?}").as_bytes());
+        return Ok(());
+    }
+
     // Log the asset being fetched as part of the Execution Standard Out.
-    log!("Fetching price for: {dr_inputs_raw}");
+    log!("Fetching price for: {symbol}, using {fields:?}");
 
-    let url = [API_URL, &dr_inputs_raw].concat();
+    let url = UrlBuilder::new(API_URL).segment(symbol).build();
     let response = proxy_http_fetch(url, Some(PROXY_PUBLIC_KEY.to_string()), None);
 
     // Handle the case where the HTTP request failed or was rejected.
@@ -75,17 +113,71 @@ pub fn execution_phase() -> Result<()> {
     // Parse the API response as defined earlier.
     let response_data = serde_json::from_slice::<EquityPriceResponse>(&response.bytes)?;
 
-    let price = response_data
+    let quote = response_data
         .quote
-        .get(&format!("{dr_inputs_raw}:USLF24"))
-        .and_then(|quote| quote.get("askPrice"))
-        .and_then(|price| price.as_f64())
+        .get(&format!("{symbol}:USLF24"))
         .ok_or_else(|| anyhow::anyhow!("Price not found in response"))?;
-    let price_lossless = (price * 100.0) as u128;
-    log!("Fetched price: {price_lossless:?}");
+    let (prices, observed_at) = quote_fields(quote, &fields)?;
+    log!("Fetched prices: {prices:?}, observed at: {observed_at}");
+
+    // The reveal is the number of fields and the index of every field, followed by the price of every field
+    // and the time the prices were observed at, so the tally can discard stale prices.
+    let result: Vec<u8> = std::iter::once(fields.len() as u128)
+        .chain(fields.iter().map(|field| {
+            VALID_FIELDS
+                .iter()
+                .position(|valid_field| valid_field == field)
+                .unwrap_or_default() as u128
+        }))
+        .chain(prices)
+        .chain(std::iter::once(observed_at))
+        .flat_map(u128::to_le_bytes)
+        .collect();
 
     // Report the successful result back to the SEDA network.
-    Process::success(&price_lossless.to_le_bytes());
+    Process::success(&result);
 
     Ok(())
 }
+
+/// Reads the requested fields from a Quote event, each as a `u128` with 2 decimal precision, along with
+/// the time in epoch milliseconds of the oldest `bidTime` or `askTime` they depend on.
+#[cfg(any(feature = "testnet", feature = "mainnet"))]
+fn quote_fields(quote: &serde_json::Value, fields: &[&str]) -> Result<(Vec<u128>, u128)> {
+    // A missing time counts as the epoch, so its prices never pass a maximum age.
+    let time = |key: &str| {
+        quote
+            .get(key)
+            .and_then(|time| time.as_u64())
+            .unwrap_or_default() as u128
+    };
+    let observed_at = fields
+        .iter()
+        .map(|field| match *field {
+            "bid" => time("bidTime"),
+            "ask" => time("askTime"),
+            _ => time("bidTime").min(time("askTime")),
+        })
+        .min()
+        .unwrap_or_default();
+
+    let price = |key: &str| {
+        quote
+            .get(key)
+            .and_then(|price| price.as_f64())
+            .map(|price| (price * 100.0) as u128)
+            .ok_or_else(|| anyhow::anyhow!("Price not found in response"))
+    };
+
+    fields
+        .iter()
+        .map(|field| match *field {
+            "bid" => price("bidPrice"),
+            "ask" => price("askPrice"),
+            // The midpoint of the fixed point prices, so every executor rounds it the same way.
+            "mid" => Ok(price("bidPrice")?.midpoint(price("askPrice")?)),
+            field => Err(anyhow::anyhow!("Invalid field: {field}")),
+        })
+        .collect::<Result<Vec<_>>>()
+        .map(|prices| (prices, observed_at))
+}
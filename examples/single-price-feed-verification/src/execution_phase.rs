@@ -1,14 +1,16 @@
 use anyhow::Result;
 #[cfg(any(feature = "testnet", feature = "mainnet"))]
 use seda_sdk_rs::{Process, elog, log, proxy_http_fetch};
+#[cfg(any(feature = "testnet", feature = "mainnet"))]
+use shared::UrlBuilder;
 
 #[cfg(feature = "testnet")]
-const API_URL: &str = "http://34.78.7.237:5384/proxy/usd/";
+pub const API_URL: &str = "http://34.78.7.237:5384/proxy/usd/";
 #[cfg(feature = "testnet")]
 const PROXY_PUBLIC_KEY: &str = "02ee9686b002e8f57f9a2ca7089a6b587c9ef4e6c2b67159add5151a42ce5e6668";
 
 #[cfg(feature = "mainnet")]
-const API_URL: &str = "http://34.77.123.159:5384/proxy/usd/";
+pub const API_URL: &str = "http://34.77.123.159:5384/proxy/usd/";
 #[cfg(feature = "mainnet")]
 const PROXY_PUBLIC_KEY: &str = "02095af5db08cef43871a4aa48a80bdddc5249e4234e7432c3d7eca14f31261b10";
 
@@ -20,15 +22,6 @@ pub fn execution_phase() -> Result<()> {
 
 #[cfg(any(feature = "testnet", feature = "mainnet"))]
 pub fn execution_phase() -> Result<()> {
-    #[cfg(not(feature = "test"))]
-    if Process::replication_factor() != 1 {
-        elog!(
-            "Replication factor must be 1 for the single price feed verification oracle program."
-        );
-        Process::error("Invalid replication factor".as_bytes());
-        return Ok(());
-    }
-
     // Expected to be in the format "symbolA,SymbolB,..." (e.g., "BTC,ETH").
     let dr_inputs_raw = String::from_utf8(Process::get_inputs())?;
 
@@ -42,7 +35,8 @@ pub fn execution_phase() -> Result<()> {
     // Log the asset pair being fetched as part of the Execution Standard Out.
     log!("Fetching price for asset(s): {dr_inputs_raw}");
 
-    let url = [API_URL, &dr_inputs_raw].concat();
+    let symbols: Vec<&str> = dr_inputs_raw.split(',').collect();
+    let url = UrlBuilder::new(API_URL).segment_list(&symbols).build();
     let response = proxy_http_fetch(url, Some(PROXY_PUBLIC_KEY.to_string()), None);
 
     // Handle the case where the HTTP request failed or was rejected.
@@ -1,41 +1,96 @@
 use anyhow::Result;
 use ethabi::{Token, ethereum_types::U256};
 use seda_sdk_rs::{HttpFetchMethod, HttpFetchResponse, Process, elog, get_unfiltered_reveals, log};
+use shared::UrlBuilder;
+
+use crate::execution_phase::API_URL;
+
+/// The public keys of the Data Proxy the verified responses must be signed with. Several keys can be accepted
+/// while the proxy rotates them, the execution phase `PROXY_PUBLIC_KEY` being one of them.
+#[cfg(all(feature = "testnet", not(feature = "test")))]
+const PROXY_PUBLIC_KEYS: &[&str] =
+    &["02ee9686b002e8f57f9a2ca7089a6b587c9ef4e6c2b67159add5151a42ce5e6668"];
+
+#[cfg(feature = "mainnet")]
+const PROXY_PUBLIC_KEYS: &[&str] =
+    &["02095af5db08cef43871a4aa48a80bdddc5249e4234e7432c3d7eca14f31261b10"];
+
+/// The key the test Data Proxy signs with, derived from the private key `1`.
+#[cfg(feature = "test")]
+const PROXY_PUBLIC_KEYS: &[&str] =
+    &["0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798"];
+
+/// The error of a response that was signed, but not by any of the [`PROXY_PUBLIC_KEYS`].
+const UNEXPECTED_PUBLIC_KEY: &str = "Unexpected proxy public key";
 
 pub fn tally_phase() -> Result<()> {
-    // Retrieve consensus reveals from the tally phase.
+    // Tally inputs are a comma-separated list of options (e.g., "symbol=BTC;ETH"):
+    // - "symbol=<symbol>;<symbol>" are the symbols of the execution inputs in order, which every response must
+    //   answer. The URL is derived from them again rather than trusting the responses the executors revealed.
+    let tally_inputs = String::from_utf8(Process::get_inputs()).unwrap_or_default();
+    let mut symbols: Option<Vec<&str>> = None;
+    for option in tally_inputs.split(',') {
+        if let Some(("symbol", value)) = option.split_once('=')
+            && !value.is_empty()
+        {
+            symbols = Some(value.split(';').collect());
+        }
+    }
+
+    let Some(symbols) = symbols else {
+        elog!("No symbol provided in the tally inputs.");
+        Process::error("No symbol provided".as_bytes());
+        return Ok(());
+    };
+    let expected_url = UrlBuilder::new(API_URL).segment_list(&symbols).build();
+
+    // Retrieve every reveal, the signatures rather than the consensus decide which ones can be trusted.
     let reveals = get_unfiltered_reveals()?;
+    let mut revealed_prices: Vec<Vec<u128>> = Vec::with_capacity(reveals.len());
+    let mut unexpected_signer = false;
 
-    if reveals.len() != 1 {
-        elog!(
-            "Expected exactly one reveal(replication factor 1), found {}",
-            reveals.len()
-        );
-        return Err(anyhow::anyhow!("Invalid number of reveals"));
-    }
+    // Iterate over each reveal, verify the signature of its proxy response and read the prices from the
+    // verified ones, discarding the failed executions and unverifiable responses.
+    for reveal in reveals {
+        if reveal.body.exit_code != 0 {
+            elog!("Ignoring failed execution");
+            continue;
+        }
 
-    let http_response: HttpFetchResponse = serde_json::from_slice(&reveals[0].body.reveal)?;
+        let prices = match verified_prices(&reveal.body.reveal, &expected_url, &symbols) {
+            Ok(prices) => prices,
+            Err(err) => {
+                elog!("Ignoring unverifiable reveal: {err}");
+                unexpected_signer |= err.to_string() == UNEXPECTED_PUBLIC_KEY;
+                continue;
+            }
+        };
 
-    let verified = http_response.proxy_verification(HttpFetchMethod::Get, None)?;
+        revealed_prices.push(prices);
+    }
 
-    if !verified {
-        elog!("Signature verification failed for the proxy response: {verified}");
-        return Err(anyhow::anyhow!("Signature verification failed"));
+    // If every signed response came from an unknown Data Proxy, report it rather than a missing reveal.
+    if revealed_prices.is_empty() && unexpected_signer {
+        Process::error(UNEXPECTED_PUBLIC_KEY.as_bytes());
+        return Ok(());
     }
 
-    // Parse the API response as defined earlier.
-    let response_data = serde_json::from_slice::<
-        serde_json::value::Map<String, serde_json::value::Value>,
-    >(&http_response.bytes)?;
+    // If no verified prices were revealed, report an error.
+    if revealed_prices.is_empty() {
+        Process::error("No verified reveals".as_bytes());
+        return Ok(());
+    }
 
-    // Extract the prices for each symbol from the response data.
-    let prices = response_data
-        .values()
-        .map(|price| (price["usd"].as_f64().unwrap_or_default() * 1_000_000f64) as u128)
+    // Calculate the median price of every symbol from the verified reveals.
+    let prices = (0..revealed_prices[0].len())
+        .map(|index| {
+            let prices: Vec<u128> = revealed_prices.iter().map(|prices| prices[index]).collect();
+            median(&prices)
+        })
         .map(U256::from)
         .map(Token::Uint)
         .collect::<Vec<Token>>();
-    log!("Fetched prices: {prices:?}");
+    log!("Final median prices: {prices:?}");
 
     // Encode the final median price as a EVM `uint256[]`.
     let result = ethabi::encode(&[Token::Array(prices)]);
@@ -44,3 +99,77 @@ pub fn tally_phase() -> Result<()> {
 
     Ok(())
 }
+
+/// Verifies the signature and URL of the proxy response of a reveal, then reads the price of every requested
+/// symbol from it, in the requested order.
+fn verified_prices(reveal: &[u8], expected_url: &str, symbols: &[&str]) -> Result<Vec<u128>> {
+    let http_response: HttpFetchResponse = serde_json::from_slice(reveal)?;
+
+    let verified = http_response.proxy_verification(HttpFetchMethod::Get, None)?;
+
+    if !verified {
+        return Err(anyhow::anyhow!("Signature verification failed"));
+    }
+
+    // A valid signature only proves the response wasn't tampered with, it must also come from a known Data Proxy.
+    let public_key = http_response
+        .headers
+        .get("x-seda-publickey")
+        .map(String::as_str)
+        .unwrap_or_default();
+    // The hex of the header is matched regardless of its case.
+    if !PROXY_PUBLIC_KEYS
+        .iter()
+        .any(|key| key.eq_ignore_ascii_case(public_key))
+    {
+        elog!("Response signed by an unexpected proxy public key: {public_key}");
+        return Err(anyhow::anyhow!(UNEXPECTED_PUBLIC_KEY));
+    }
+
+    // The signature covers the URL, so a response for another asset can't be passed off as the requested one.
+    if http_response.url != expected_url {
+        elog!(
+            "Response answers an unexpected URL: {}, expected {expected_url}",
+            http_response.url
+        );
+        return Err(anyhow::anyhow!("Unexpected response URL"));
+    }
+
+    // Parse the API response as defined earlier.
+    let response_data = serde_json::from_slice::<
+        serde_json::value::Map<String, serde_json::value::Value>,
+    >(&http_response.bytes)?;
+
+    // Extract the price of each requested symbol, keyed by its lowercase symbol in the response data. A missing
+    // or zero price fails the whole reveal rather than being reported as a price of 0.
+    let prices = symbols
+        .iter()
+        .map(|symbol| {
+            response_data
+                .get(&symbol.to_lowercase())
+                .and_then(|price| price["usd"].as_f64())
+                .map(|price| (price * 1_000_000f64) as u128)
+                .filter(|price| *price > 0)
+                .ok_or_else(|| anyhow::anyhow!("No valid price for {symbol}"))
+        })
+        .collect::<Result<Vec<u128>>>()?;
+    log!("Verified prices: {prices:?}");
+
+    Ok(prices)
+}
+
+/// Finds the median of a list of prices per price report.
+fn median(data: &[u128]) -> u128 {
+    let mut sorted_data = data.to_vec();
+    sorted_data.sort_unstable();
+
+    let m = sorted_data.len();
+    if m.is_multiple_of(2) {
+        // safe average of two u128s without overflow
+        let a = sorted_data[m / 2 - 1];
+        let b = sorted_data[m / 2];
+        a.midpoint(b)
+    } else {
+        sorted_data[m / 2]
+    }
+}
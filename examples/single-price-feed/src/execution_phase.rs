@@ -1,6 +1,8 @@
 use anyhow::Result;
 #[cfg(any(feature = "testnet", feature = "mainnet"))]
 use seda_sdk_rs::{Process, elog, log, proxy_http_fetch};
+#[cfg(any(feature = "testnet", feature = "mainnet"))]
+use shared::UrlBuilder;
 
 #[cfg(feature = "testnet")]
 const API_URL: &str = "http://34.78.7.237:5384/proxy/usd/";
@@ -33,7 +35,8 @@ pub fn execution_phase() -> Result<()> {
     // Log the asset pair being fetched as part of the Execution Standard Out.
     log!("Fetching price for asset(s): {dr_inputs_raw}");
 
-    let url = [API_URL, &dr_inputs_raw].concat();
+    let symbols: Vec<&str> = dr_inputs_raw.split(',').collect();
+    let url = UrlBuilder::new(API_URL).segment_list(&symbols).build();
     let response = proxy_http_fetch(url, Some(PROXY_PUBLIC_KEY.to_string()), None);
 
     // Handle the case where the HTTP request failed or was rejected.
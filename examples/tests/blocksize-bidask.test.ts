@@ -5,12 +5,16 @@ import { testOracleProgramExecution, testOracleProgramTally } from '@seda-protoc
 import {
   handleBigIntArrayTallyVmResult as handleVmResult,
   handleBigIntArrayExecutionVmResult as handleExecutionVmResult,
+  handleTupleTallyVmResult,
   createRevealArray,
   RevealKind,
 } from './utils.js';
 
 const WASM_PATH = 'target/wasm32-wasip1/release/blocksize-bidask.wasm';
 
+// The `ts` the tally reveals are observed at
+const TS = 1756156227634385n;
+
 const fetchMock = mock();
 
 afterEach(() => {
@@ -47,16 +51,91 @@ describe('blocksize bidask', () => {
       );
 
       handleExecutionVmResult(vmResult, 0, [
+        6n,
+        0n,
+        1n,
+        2n,
+        3n,
+        4n,
+        5n,
         4362597230n,
         98427674n,
         4364092969n,
         125292602n,
         4363345100n,
         1756156227634385n,
+        1756156227634385n,
       ]);
     });
 
-    it('works with a singular specified field', async () => {
+    it('works with several pairs sharing the field list', async () => {
+      fetchMock.mockImplementation((url) => {
+        const ticker = url.toString().split('/').pop();
+        return new Response(
+          JSON.stringify({
+            ticker,
+            agg_bid_price: ticker === 'ETHUSD' ? '4362.597230371793' : '110772.1',
+            agg_bid_size: '98.42767488000001',
+            agg_ask_price: ticker === 'ETHUSD' ? '4364.092969924804' : '110773.2',
+            agg_ask_size: '125.29260208',
+            agg_mid_price: ticker === 'ETHUSD' ? '4363.345100148298' : '110772.65',
+            ts: 1756156227634385,
+          }),
+        );
+      });
+
+      const oracleProgram = await file(WASM_PATH).arrayBuffer();
+
+      const vmResult = await testOracleProgramExecution(
+        Buffer.from(oracleProgram),
+        Buffer.from('ETHUSD,BTCUSD-agg_bid_price,agg_ask_price'),
+        fetchMock,
+        undefined,
+        undefined,
+        undefined,
+        0n,
+      );
+
+      // The number of fields per pair and their ids come first, followed by one row per pair
+      handleExecutionVmResult(vmResult, 0, [
+        2n,
+        0n,
+        2n,
+        4362597230n,
+        4364092969n,
+        110772100000n,
+        110773200000n,
+        1756156227634385n,
+        1756156227634385n,
+      ]);
+    });
+
+    it('errors if one of the pairs fails', async () => {
+      fetchMock.mockImplementation((url) => {
+        if (url.toString().endsWith('BTCUSD')) {
+          return new Response('Not found', { status: 404 });
+        }
+        return new Response(
+          JSON.stringify({ ticker: 'ETHUSD', agg_bid_price: '4362.597230371793', agg_ask_price: '4364.09', ts: 1 }),
+        );
+      });
+
+      const oracleProgram = await file(WASM_PATH).arrayBuffer();
+
+      const vmResult = await testOracleProgramExecution(
+        Buffer.from(oracleProgram),
+        Buffer.from('ETHUSD,BTCUSD-agg_bid_price'),
+        fetchMock,
+        undefined,
+        undefined,
+        undefined,
+        0n,
+      );
+
+      handleExecutionVmResult(vmResult, 1, []);
+    });
+
+    it('returns the derived fields', async () => {
       fetchMock.mockImplementation((_) => {
         return new Response(
           JSON.stringify({
@@ -75,7 +154,7 @@ describe('blocksize bidask', () => {
 
       const vmResult = await testOracleProgramExecution(
         Buffer.from(oracleProgram),
-        Buffer.from('ETHUSD-agg_bid_price'),
+        Buffer.from('ETHUSD-mid_from_bidask,spread_bps'),
         fetchMock,
         undefined,
         undefined,
@@ -83,17 +162,18 @@ describe('blocksize bidask', () => {
         0n,
       );
 
-      handleExecutionVmResult(vmResult, 0, [4362597230n]);
+      // (4362597230 + 4364092969) / 2 and (4364092969 - 4362597230) * 10^10 / 4363345099
+      handleExecutionVmResult(vmResult, 0, [2n, 6n, 7n, 4363345099n, 3427964n, 1756156227634385n]);
     });
 
-    it('works with a multi specified field and returns in the specified order', async () => {
+    it('rejects a crossed book', async () => {
       fetchMock.mockImplementation((_) => {
         return new Response(
           JSON.stringify({
             ticker: 'ETHUSD',
-            agg_bid_price: '4362.597230371793',
+            agg_bid_price: '4364.092969924804',
             agg_bid_size: '98.42767488000001',
-            agg_ask_price: '4364.092969924804',
+            agg_ask_price: '4362.597230371793',
             agg_ask_size: '125.29260208',
             agg_mid_price: '4363.345100148298',
             ts: 1756156227634385,
@@ -105,7 +185,7 @@ describe('blocksize bidask', () => {
 
       const vmResult = await testOracleProgramExecution(
         Buffer.from(oracleProgram),
-        Buffer.from('ETHUSD-agg_mid_price,agg_bid_price'),
+        Buffer.from('ETHUSD-agg_bid_size'),
         fetchMock,
         undefined,
         undefined,
@@ -113,10 +193,10 @@ describe('blocksize bidask', () => {
         0n,
       );
 
-      handleExecutionVmResult(vmResult, 0, [4363345100n, 4362597230n]);
+      handleExecutionVmResult(vmResult, 1, []);
     });
 
-    it('ignores if a non-existent field is requested', async () => {
+    it('works with a singular specified field', async () => {
       fetchMock.mockImplementation((_) => {
         return new Response(
           JSON.stringify({
@@ -135,19 +215,18 @@ describe('blocksize bidask', () => {
 
       const vmResult = await testOracleProgramExecution(
         Buffer.from(oracleProgram),
-        Buffer.from('ETHUSD-does_not_exist,agg_mid_price'),
+        Buffer.from('ETHUSD-agg_bid_price'),
         fetchMock,
         undefined,
         undefined,
         undefined,
         0n,
       );
-      console.log('VM Result:', vmResult);
 
-      handleExecutionVmResult(vmResult, 0, [4363345100n]);
+      handleExecutionVmResult(vmResult, 0, [1n, 0n, 4362597230n, 1756156227634385n]);
     });
 
-    it('ignores if a non-existent field is requested', async () => {
+    it('works with a multi specified field and returns in the specified order', async () => {
       fetchMock.mockImplementation((_) => {
         return new Response(
           JSON.stringify({
@@ -166,19 +245,18 @@ describe('blocksize bidask', () => {
 
       const vmResult = await testOracleProgramExecution(
         Buffer.from(oracleProgram),
-        Buffer.from('ETHUSD-does_not_exist,agg_mid_price'),
+        Buffer.from('ETHUSD-agg_mid_price,agg_bid_price'),
         fetchMock,
         undefined,
         undefined,
         undefined,
         0n,
       );
-      handleExecutionVmResult(vmResult, 0, [4363345100n]);
 
-      expect(vmResult.stderr).toContain('Invalid field: does_not_exist');
+      handleExecutionVmResult(vmResult, 0, [2n, 4n, 0n, 4363345100n, 4362597230n, 1756156227634385n]);
     });
 
-    it('errors if no valid fields are selected', async () => {
+    it('keeps the order and duplicates of the requested fields', async () => {
       fetchMock.mockImplementation((_) => {
         return new Response(
           JSON.stringify({
@@ -195,6 +273,73 @@ describe('blocksize bidask', () => {
 
       const oracleProgram = await file(WASM_PATH).arrayBuffer();
 
+      const vmResult = await testOracleProgramExecution(
+        Buffer.from(oracleProgram),
+        Buffer.from('ETHUSD-agg_ask_price,agg_bid_price,agg_ask_price'),
+        fetchMock,
+        undefined,
+        undefined,
+        undefined,
+        0n,
+      );
+
+      handleExecutionVmResult(vmResult, 0, [
+        3n,
+        2n,
+        0n,
+        2n,
+        4364092969n,
+        4362597230n,
+        4364092969n,
+        1756156227634385n,
+      ]);
+      expect(fetchMock.mock.calls.map(([url]) => url.toString())).toEqual([
+        'https://seda-proxy.blocksize.dev/proxy/bidask/ETHUSD',
+      ]);
+    });
+
+    it('percent-encodes the pair in the path', async () => {
+      fetchMock.mockImplementation((_) => new Response('{}'));
+
+      const oracleProgram = await file(WASM_PATH).arrayBuffer();
+
+      await testOracleProgramExecution(
+        Buffer.from(oracleProgram),
+        Buffer.from('ETH/USD?x=1&y'),
+        fetchMock,
+        undefined,
+        undefined,
+        undefined,
+        0n,
+      );
+
+      expect(fetchMock.mock.calls.map(([url]) => url.toString())).toEqual([
+        'https://seda-proxy.blocksize.dev/proxy/bidask/ETH%2FUSD%3Fx%3D1%26y',
+      ]);
+    });
+
+    it('errors listing every non-existent field requested', async () => {
+      const oracleProgram = await file(WASM_PATH).arrayBuffer();
+
+      const vmResult = await testOracleProgramExecution(
+        Buffer.from(oracleProgram),
+        Buffer.from('ETHUSD-does_not_exist,agg_mid_price,price'),
+        fetchMock,
+        undefined,
+        undefined,
+        undefined,
+        0n,
+      );
+
+      handleExecutionVmResult(vmResult, 1, []);
+      expect(Buffer.from(vmResult.result).toString()).toBe('Invalid fields: ["does_not_exist", "price"]');
+      expect(vmResult.stderr).toContain('Invalid fields: ["does_not_exist", "price"]');
+      expect(fetchMock).not.toHaveBeenCalled();
+    });
+
+    it('errors if no valid fields are selected', async () => {
+      const oracleProgram = await file(WASM_PATH).arrayBuffer();
+
       const vmResult = await testOracleProgramExecution(
         Buffer.from(oracleProgram),
         Buffer.from('ETHUSD-does_not_exist'),
@@ -206,16 +351,258 @@ describe('blocksize bidask', () => {
       );
 
       handleExecutionVmResult(vmResult, 1, []);
+      expect(Buffer.from(vmResult.result).toString()).toBe('Invalid fields: ["does_not_exist"]');
+      expect(fetchMock).not.toHaveBeenCalled();
+    });
+
+    it('errors if an empty field list is given', async () => {
+      const oracleProgram = await file(WASM_PATH).arrayBuffer();
+
+      const vmResult = await testOracleProgramExecution(
+        Buffer.from(oracleProgram),
+        Buffer.from('ETHUSD-'),
+        fetchMock,
+        undefined,
+        undefined,
+        undefined,
+        0n,
+      );
+
+      handleExecutionVmResult(vmResult, 1, []);
+      expect(fetchMock).not.toHaveBeenCalled();
     });
   });
 
   describe('tally phase', () => {
+    describe('staleness', () => {
+      // One millisecond in the unit of `ts`
+      const MS = 1000n;
+      const reveals = createRevealArray([
+        [RevealKind.BigIntArray, [1n, 1n, 100n, TS]],
+        [RevealKind.BigIntArray, [1n, 1n, 200n, TS - MS]],
+        [RevealKind.BigIntArray, [1n, 1n, 900n, TS - 10_000n * MS]],
+      ]);
+
+      it('keeps every reveal without a maximum age', async () => {
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+        const vmResult = await testOracleProgramTally(Buffer.from(oracleProgram), Buffer.from('tally-inputs'), reveals);
+        handleVmResult(vmResult, 0, [200n]);
+      });
+
+      it('discards the reveals older than the maximum age from the median ts', async () => {
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+        const vmResult = await testOracleProgramTally(Buffer.from(oracleProgram), Buffer.from('maxAge=5000'), reveals);
+        handleVmResult(vmResult, 0, [150n]);
+      });
+
+      it('measures the age from the reference time in the tally inputs', async () => {
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+        const now = TS / MS + 1n;
+        const vmResult = await testOracleProgramTally(
+          Buffer.from(oracleProgram),
+          Buffer.from(`nested,maxAge=1,now=${now}`),
+          reveals,
+        );
+        handleTupleTallyVmResult(vmResult, 0, [[[100n]]], ['uint256[][]']);
+      });
+
+      it('errors if every reveal is stale', async () => {
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+        const now = TS / MS + 60_000n;
+        const vmResult = await testOracleProgramTally(
+          Buffer.from(oracleProgram),
+          Buffer.from(`maxAge=5000,now=${now}`),
+          reveals,
+        );
+        handleVmResult(vmResult, 1, []);
+      });
+
+      it('uses the oldest pair of a reveal', async () => {
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+        const vmResult = await testOracleProgramTally(
+          Buffer.from(oracleProgram),
+          Buffer.from('maxAge=5000'),
+          createRevealArray([
+            [RevealKind.BigIntArray, [1n, 1n, 100n, 500n, TS, TS]],
+            [RevealKind.BigIntArray, [1n, 1n, 200n, 600n, TS, TS]],
+            [RevealKind.BigIntArray, [1n, 1n, 300n, 700n, TS, TS - 10_000n * MS]],
+          ]),
+        );
+        handleVmResult(vmResult, 0, [150n, 550n]);
+      });
+
+      it('errors if the maximum age is not a number', async () => {
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+        const vmResult = await testOracleProgramTally(Buffer.from(oracleProgram), Buffer.from('maxAge=5s'), reveals);
+        handleVmResult(vmResult, 1, []);
+      });
+
+      it('errors if the reference time is not a number', async () => {
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+        const vmResult = await testOracleProgramTally(
+          Buffer.from(oracleProgram),
+          Buffer.from('maxAge=5000,now=yesterday'),
+          reveals,
+        );
+        handleVmResult(vmResult, 1, []);
+      });
+    });
+
+    describe('book consistency', () => {
+      it('accepts an aggregated book with bid <= mid <= ask', async () => {
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+        const vmResult = await testOracleProgramTally(
+          Buffer.from(oracleProgram),
+          Buffer.from('tally-inputs'),
+          createRevealArray([
+            [RevealKind.BigIntArray, [3n, 0n, 4n, 2n, 100n, 101n, 102n, TS]],
+            [RevealKind.BigIntArray, [3n, 0n, 4n, 2n, 101n, 102n, 103n, TS]],
+          ]),
+        );
+        handleVmResult(vmResult, 0, [100n, 101n, 102n]);
+      });
+
+      it('errors if the aggregated mid is outside of the bid and ask', async () => {
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+        const vmResult = await testOracleProgramTally(
+          Buffer.from(oracleProgram),
+          Buffer.from('tally-inputs'),
+          createRevealArray([
+            [RevealKind.BigIntArray, [3n, 0n, 4n, 2n, 100n, 130n, 120n, TS]],
+            [RevealKind.BigIntArray, [3n, 0n, 4n, 2n, 110n, 130n, 125n, TS]],
+            [RevealKind.BigIntArray, [3n, 0n, 4n, 2n, 120n, 130n, 140n, TS]],
+          ]),
+        );
+        handleVmResult(vmResult, 1, []);
+      });
+
+      it('errors if the aggregated bid is above the aggregated ask', async () => {
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+        const vmResult = await testOracleProgramTally(
+          Buffer.from(oracleProgram),
+          Buffer.from('tally-inputs'),
+          createRevealArray([
+            [RevealKind.BigIntArray, [2n, 0n, 2n, 100n, 101n, TS]],
+            [RevealKind.BigIntArray, [2n, 0n, 2n, 150n, 90n, TS]],
+            [RevealKind.BigIntArray, [2n, 0n, 2n, 140n, 95n, TS]],
+          ]),
+        );
+        handleVmResult(vmResult, 1, []);
+      });
+
+      it('ignores reveals with different fields', async () => {
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+        const vmResult = await testOracleProgramTally(
+          Buffer.from(oracleProgram),
+          Buffer.from('tally-inputs'),
+          createRevealArray([
+            [RevealKind.BigIntArray, [1n, 0n, 100n, TS]],
+            [RevealKind.BigIntArray, [1n, 2n, 300n, TS]],
+            [RevealKind.BigIntArray, [1n, 0n, 200n, TS]],
+          ]),
+        );
+        handleVmResult(vmResult, 0, [150n]);
+      });
+
+      it('keeps the fields most reveals agree on', async () => {
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+        const vmResult = await testOracleProgramTally(
+          Buffer.from(oracleProgram),
+          Buffer.from('tally-inputs'),
+          createRevealArray([
+            [RevealKind.BigIntArray, [1n, 2n, 300n, TS]],
+            [RevealKind.BigIntArray, [1n, 0n, 100n, TS]],
+            [RevealKind.BigIntArray, [1n, 0n, 200n, TS]],
+          ]),
+        );
+        handleVmResult(vmResult, 0, [150n]);
+      });
+    });
+
+    describe('several pairs', () => {
+      const reveals = createRevealArray([
+        [RevealKind.BigIntArray, [2n, 1n, 3n, 100n, 110n, 500n, 510n, TS, TS]],
+        [RevealKind.BigIntArray, [2n, 1n, 3n, 300n, 310n, 900n, 910n, TS, TS]],
+        [RevealKind.BigIntArray, [2n, 1n, 3n, 200n, 210n, 700n, 710n, TS, TS]],
+      ]);
+
+      it('returns the fields of every pair flattened by default', async () => {
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+        const vmResult = await testOracleProgramTally(Buffer.from(oracleProgram), Buffer.from('tally-inputs'), reveals);
+        handleVmResult(vmResult, 0, [200n, 210n, 700n, 710n]);
+      });
+
+      it('returns one row per pair with the nested layout', async () => {
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+        const vmResult = await testOracleProgramTally(Buffer.from(oracleProgram), Buffer.from('nested'), reveals);
+        handleTupleTallyVmResult(
+          vmResult,
+          0,
+          [
+            [
+              [200n, 210n],
+              [700n, 710n],
+            ],
+          ],
+          ['uint256[][]'],
+        );
+      });
+
+      it('returns the stride along with the values with the strided layout', async () => {
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+        const vmResult = await testOracleProgramTally(Buffer.from(oracleProgram), Buffer.from('strided'), reveals);
+        handleTupleTallyVmResult(vmResult, 0, [2n, [200n, 210n, 700n, 710n]], ['uint256', 'uint256[]']);
+      });
+
+      it('ignores reveals with a different stride', async () => {
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+        const vmResult = await testOracleProgramTally(
+          Buffer.from(oracleProgram),
+          Buffer.from('tally-inputs'),
+          createRevealArray([
+            [RevealKind.BigIntArray, [2n, 1n, 3n, 100n, 110n, TS]],
+            [RevealKind.BigIntArray, [1n, 1n, 300n, 310n, TS, TS]],
+            [RevealKind.BigIntArray, [2n, 1n, 3n, 200n, 210n, TS]],
+          ]),
+        );
+        handleVmResult(vmResult, 0, [150n, 160n]);
+      });
+
+      it('keeps the stride most reveals agree on', async () => {
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+        const vmResult = await testOracleProgramTally(
+          Buffer.from(oracleProgram),
+          Buffer.from('tally-inputs'),
+          createRevealArray([
+            [RevealKind.BigIntArray, [1n, 1n, 300n, 310n, TS, TS]],
+            [RevealKind.BigIntArray, [2n, 1n, 3n, 100n, 110n, TS]],
+            [RevealKind.BigIntArray, [2n, 1n, 3n, 200n, 210n, TS]],
+          ]),
+        );
+        handleVmResult(vmResult, 0, [150n, 160n]);
+      });
+
+      it('ignores reveals with a different number of pairs than most reveals', async () => {
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+        const vmResult = await testOracleProgramTally(
+          Buffer.from(oracleProgram),
+          Buffer.from('tally-inputs'),
+          createRevealArray([
+            [RevealKind.BigIntArray, [1n, 1n, 300n, TS]],
+            [RevealKind.BigIntArray, [1n, 1n, 100n, 500n, TS, TS]],
+            [RevealKind.BigIntArray, [1n, 1n, 200n, 600n, TS, TS]],
+          ]),
+        );
+        handleVmResult(vmResult, 0, [150n, 550n]);
+      });
+    });
+
     it('works with 1 price', async () => {
       const oracleProgram = await file(WASM_PATH).arrayBuffer();
       const vmResult = await testOracleProgramTally(
         Buffer.from(oracleProgram),
         Buffer.from('tally-inputs'),
-        createRevealArray([[RevealKind.BigIntArray, [100n]]]),
+        createRevealArray([[RevealKind.BigIntArray, [1n, 1n, 100n, TS]]]),
       );
       handleVmResult(vmResult, 0, [100n]);
     });
@@ -226,8 +613,8 @@ describe('blocksize bidask', () => {
         Buffer.from(oracleProgram),
         Buffer.from('tally-inputs'),
         createRevealArray([
-          [RevealKind.BigIntArray, [100n]],
-          [RevealKind.BigIntArray, [200n]],
+          [RevealKind.BigIntArray, [1n, 1n, 100n, TS]],
+          [RevealKind.BigIntArray, [1n, 1n, 200n, TS]],
         ]),
       );
       handleVmResult(vmResult, 0, [150n]);
@@ -239,8 +626,8 @@ describe('blocksize bidask', () => {
         Buffer.from(oracleProgram),
         Buffer.from('tally-inputs'),
         createRevealArray([
-          [RevealKind.BigIntArray, [100n]],
-          [RevealKind.BigIntArray, [200n]],
+          [RevealKind.BigIntArray, [1n, 1n, 100n, TS]],
+          [RevealKind.BigIntArray, [1n, 1n, 200n, TS]],
         ]),
       );
       handleVmResult(vmResult, 0, [150n]);
@@ -252,9 +639,9 @@ describe('blocksize bidask', () => {
         Buffer.from(oracleProgram),
         Buffer.from('tally-inputs'),
         createRevealArray([
-          [RevealKind.BigIntArray, [100n, 500n]],
-          [RevealKind.BigIntArray, [300n, 900n]],
-          [RevealKind.BigIntArray, [200n, 700n]],
+          [RevealKind.BigIntArray, [2n, 1n, 3n, 100n, 500n, TS]],
+          [RevealKind.BigIntArray, [2n, 1n, 3n, 300n, 900n, TS]],
+          [RevealKind.BigIntArray, [2n, 1n, 3n, 200n, 700n, TS]],
         ]),
       );
       handleVmResult(vmResult, 0, [200n, 700n]);
@@ -266,11 +653,11 @@ describe('blocksize bidask', () => {
         Buffer.from(oracleProgram),
         Buffer.from('tally-inputs'),
         createRevealArray([
-          [RevealKind.BigIntArray, [100n]],
-          [RevealKind.BigIntArray, [200n]],
-          [RevealKind.BigIntArray, [300n]],
-          [RevealKind.BigIntArray, [400n]],
-          [RevealKind.BigIntArray, [500n]],
+          [RevealKind.BigIntArray, [1n, 1n, 100n, TS]],
+          [RevealKind.BigIntArray, [1n, 1n, 200n, TS]],
+          [RevealKind.BigIntArray, [1n, 1n, 300n, TS]],
+          [RevealKind.BigIntArray, [1n, 1n, 400n, TS]],
+          [RevealKind.BigIntArray, [1n, 1n, 500n, TS]],
         ]),
       );
       handleVmResult(vmResult, 0, [300n]);
@@ -282,16 +669,16 @@ describe('blocksize bidask', () => {
         Buffer.from(oracleProgram),
         Buffer.from('tally-inputs'),
         createRevealArray([
-          [RevealKind.BigIntArray, [100n]],
-          [RevealKind.BigIntArray, [200n]],
-          [RevealKind.BigIntArray, [300n]],
-          [RevealKind.BigIntArray, [400n]],
-          [RevealKind.BigIntArray, [500n]],
-          [RevealKind.BigIntArray, [600n]],
-          [RevealKind.BigIntArray, [700n]],
-          [RevealKind.BigIntArray, [800n]],
-          [RevealKind.BigIntArray, [900n]],
-          [RevealKind.BigIntArray, [1000n]],
+          [RevealKind.BigIntArray, [1n, 1n, 100n, TS]],
+          [RevealKind.BigIntArray, [1n, 1n, 200n, TS]],
+          [RevealKind.BigIntArray, [1n, 1n, 300n, TS]],
+          [RevealKind.BigIntArray, [1n, 1n, 400n, TS]],
+          [RevealKind.BigIntArray, [1n, 1n, 500n, TS]],
+          [RevealKind.BigIntArray, [1n, 1n, 600n, TS]],
+          [RevealKind.BigIntArray, [1n, 1n, 700n, TS]],
+          [RevealKind.BigIntArray, [1n, 1n, 800n, TS]],
+          [RevealKind.BigIntArray, [1n, 1n, 900n, TS]],
+          [RevealKind.BigIntArray, [1n, 1n, 1000n, TS]],
         ]),
       );
       handleVmResult(vmResult, 0, [550n]);
@@ -303,12 +690,12 @@ describe('blocksize bidask', () => {
         Buffer.from(oracleProgram),
         Buffer.from('tally-inputs'),
         createRevealArray([
-          [RevealKind.BigIntArray, [500n]],
-          [RevealKind.BigIntArray, [100n]],
-          [RevealKind.BigIntArray, [300n]],
-          [RevealKind.BigIntArray, [200n]],
-          [RevealKind.BigIntArray, [200n]],
-          [RevealKind.BigIntArray, [400n]],
+          [RevealKind.BigIntArray, [1n, 1n, 500n, TS]],
+          [RevealKind.BigIntArray, [1n, 1n, 100n, TS]],
+          [RevealKind.BigIntArray, [1n, 1n, 300n, TS]],
+          [RevealKind.BigIntArray, [1n, 1n, 200n, TS]],
+          [RevealKind.BigIntArray, [1n, 1n, 200n, TS]],
+          [RevealKind.BigIntArray, [1n, 1n, 400n, TS]],
         ]),
       );
       handleVmResult(vmResult, 0, [250n]);
@@ -321,7 +708,11 @@ describe('blocksize bidask', () => {
         const vmResult = await testOracleProgramTally(
           Buffer.from(oracleProgram),
           Buffer.from('tally-inputs'),
-          createRevealArray([[RevealKind.BigIntArray, [100n]], [RevealKind.Failed], [RevealKind.BigIntArray, [200n]]]),
+          createRevealArray([
+            [RevealKind.BigIntArray, [1n, 1n, 100n, TS]],
+            [RevealKind.Failed],
+            [RevealKind.BigIntArray, [1n, 1n, 200n, TS]],
+          ]),
         );
 
         handleVmResult(vmResult, 0, [150n]);
@@ -334,11 +725,11 @@ describe('blocksize bidask', () => {
           Buffer.from(oracleProgram),
           Buffer.from('tally-inputs'),
           createRevealArray([
-            [RevealKind.BigIntArray, [100n]],
+            [RevealKind.BigIntArray, [1n, 1n, 100n, TS]],
             [RevealKind.Failed],
-            [RevealKind.BigIntArray, [200n]],
+            [RevealKind.BigIntArray, [1n, 1n, 200n, TS]],
             [RevealKind.Failed],
-            [RevealKind.BigIntArray, [300n]],
+            [RevealKind.BigIntArray, [1n, 1n, 300n, TS]],
           ]),
         );
 
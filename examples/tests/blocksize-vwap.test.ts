@@ -1,16 +1,20 @@
 // biome-ignore assist/source/organizeImports: biome is lying
 import { file } from 'bun';
-import { afterEach, describe, it, mock } from 'bun:test';
+import { afterEach, describe, expect, it, mock } from 'bun:test';
 import { testOracleProgramExecution, testOracleProgramTally } from '@seda-protocol/dev-tools';
 import {
   handleBigIntArrayTallyVmResult as handleVmResult,
   handleBigIntArrayExecutionVmResult as handleExecutionVmResult,
+  handleTupleTallyVmResult,
   createRevealArray,
   RevealKind,
 } from './utils.js';
 
 const WASM_PATH = 'target/wasm32-wasip1/release/blocksize-vwap.wasm';
 
+// The `ts` the tally reveals are observed at
+const TS = 1756147348689n;
+
 const fetchMock = mock();
 
 afterEach(() => {
@@ -45,7 +49,104 @@ describe('blocksize vwap', () => {
       );
       console.log('VM Result:', vmResult);
 
-      handleExecutionVmResult(vmResult, 0, [112269918585n, 4564607n, 512468124750n, 1756147348689n]);
+      handleExecutionVmResult(vmResult, 0, [
+        4n,
+        112269918585n,
+        4564607n,
+        512468124750n,
+        1756147348689n,
+        1756147348689n,
+      ]);
+    });
+
+    it('works with several pairs sharing the field list', async () => {
+      fetchMock.mockImplementation((url) => {
+        const ticker = url.toString().split('/').pop();
+        return new Response(
+          JSON.stringify({
+            ticker,
+            price: ticker === 'ETHUSD' ? 4363.345 : 112269.91858575967,
+            size: 4.5646076099999995,
+            volume: 512468.12475063896,
+            ts: 1756147348689,
+          }),
+        );
+      });
+
+      const oracleProgram = await file(WASM_PATH).arrayBuffer();
+
+      const vmResult = await testOracleProgramExecution(
+        Buffer.from(oracleProgram),
+        Buffer.from('ETHUSD,BTCUSD-price,ts'),
+        fetchMock,
+        undefined,
+        undefined,
+        undefined,
+        0n,
+      );
+
+      // The number of fields per pair comes first, followed by one row per pair
+      handleExecutionVmResult(vmResult, 0, [
+        2n,
+        4363345000n,
+        1756147348689n,
+        112269918585n,
+        1756147348689n,
+        1756147348689n,
+        1756147348689n,
+      ]);
+
+      // The upstream requests are built from the pairs only
+      expect(fetchMock.mock.calls.map(([url]) => url.toString())).toEqual([
+        'https://seda-proxy.blocksize.dev/proxy/vwap/ETHUSD',
+        'https://seda-proxy.blocksize.dev/proxy/vwap/BTCUSD',
+      ]);
+    });
+
+    it('percent-encodes the pairs in the path', async () => {
+      fetchMock.mockImplementation((_) => {
+        return new Response(JSON.stringify({ ticker: 'ETHUSD', price: 4363.345, ts: 1756147348689 }));
+      });
+
+      const oracleProgram = await file(WASM_PATH).arrayBuffer();
+
+      await testOracleProgramExecution(
+        Buffer.from(oracleProgram),
+        Buffer.from('ETH/USD,BTC USD?x=1-price'),
+        fetchMock,
+        undefined,
+        undefined,
+        undefined,
+        0n,
+      );
+
+      expect(fetchMock.mock.calls.map(([url]) => url.toString())).toEqual([
+        'https://seda-proxy.blocksize.dev/proxy/vwap/ETH%2FUSD',
+        'https://seda-proxy.blocksize.dev/proxy/vwap/BTC%20USD%3Fx%3D1',
+      ]);
+    });
+
+    it('errors if one of the pairs fails', async () => {
+      fetchMock.mockImplementation((url) => {
+        if (url.toString().includes('BTCUSD')) {
+          return new Response('Not found', { status: 404 });
+        }
+        return new Response(JSON.stringify({ ticker: 'ETHUSD', price: 4363.345, ts: 1756147348689 }));
+      });
+
+      const oracleProgram = await file(WASM_PATH).arrayBuffer();
+
+      const vmResult = await testOracleProgramExecution(
+        Buffer.from(oracleProgram),
+        Buffer.from('ETHUSD,BTCUSD-price'),
+        fetchMock,
+        undefined,
+        undefined,
+        undefined,
+        0n,
+      );
+
+      handleExecutionVmResult(vmResult, 1, []);
     });
 
     it('works with a singular specified field', async () => {
@@ -73,7 +174,7 @@ describe('blocksize vwap', () => {
         0n,
       );
 
-      handleExecutionVmResult(vmResult, 0, [4564607n]);
+      handleExecutionVmResult(vmResult, 0, [1n, 4564607n, 1756147348689n]);
     });
 
     it('works with a multi specified field and returns in the specified order', async () => {
@@ -101,17 +202,18 @@ describe('blocksize vwap', () => {
         0n,
       );
 
-      handleExecutionVmResult(vmResult, 0, [1756147348689n, 112269918585n]);
+      handleExecutionVmResult(vmResult, 0, [2n, 1756147348689n, 112269918585n, 1756147348689n]);
     });
 
-    it('ignores if a non-existent field is requested', async () => {
+    it('works with a tiny-cap pair in scientific notation', async () => {
       fetchMock.mockImplementation((_) => {
+        // JSON.stringify writes numbers below 1e-6 in scientific notation
         return new Response(
           JSON.stringify({
-            ticker: 'BTCUSD',
-            price: 112269.91858575967,
-            size: 4.5646076099999995,
-            volume: 512468.12475063896,
+            ticker: 'PEPEUSD',
+            price: 0.0000015,
+            size: 1.5e-7,
+            volume: 0.000012345678,
             ts: 1756147348689,
           }),
         );
@@ -121,19 +223,69 @@ describe('blocksize vwap', () => {
 
       const vmResult = await testOracleProgramExecution(
         Buffer.from(oracleProgram),
-        Buffer.from('BTCUSD-does_not_exist,price'),
+        Buffer.from('PEPEUSD'),
         fetchMock,
         undefined,
         undefined,
         undefined,
         0n,
       );
-      console.log('VM Result:', vmResult);
 
-      handleExecutionVmResult(vmResult, 0, [112269918585n]);
+      handleExecutionVmResult(vmResult, 0, [4n, 1n, 0n, 12n, 1756147348689n, 1756147348689n]);
     });
 
-    it('errors if no valid fields are selected', async () => {
+    it('keeps every digit of a huge volume', async () => {
+      fetchMock.mockImplementation((_) => {
+        // Written by hand, these numbers have more digits than a float can hold
+        return new Response(
+          '{"ticker":"SHIBUSD","price":0.00001234,"size":123456789012345678901.123456789,' +
+            '"volume":98765432109876543210.987654321,"ts":1756147348689}',
+        );
+      });
+
+      const oracleProgram = await file(WASM_PATH).arrayBuffer();
+
+      const vmResult = await testOracleProgramExecution(
+        Buffer.from(oracleProgram),
+        Buffer.from('SHIBUSD'),
+        fetchMock,
+        undefined,
+        undefined,
+        undefined,
+        0n,
+      );
+
+      handleExecutionVmResult(vmResult, 0, [
+        4n,
+        12n,
+        123456789012345678901123456n,
+        98765432109876543210987654n,
+        1756147348689n,
+        1756147348689n,
+      ]);
+    });
+
+    it('works with a volume in exponent notation', async () => {
+      fetchMock.mockImplementation((_) => {
+        return new Response('{"ticker":"BTCUSD","price":112269.9,"size":4.5,"volume":1.2e+21,"ts":1756147348689}');
+      });
+
+      const oracleProgram = await file(WASM_PATH).arrayBuffer();
+
+      const vmResult = await testOracleProgramExecution(
+        Buffer.from(oracleProgram),
+        Buffer.from('BTCUSD-volume'),
+        fetchMock,
+        undefined,
+        undefined,
+        undefined,
+        0n,
+      );
+
+      handleExecutionVmResult(vmResult, 0, [1n, 1200000000000000000000000000n, 1756147348689n]);
+    });
+
+    it('keeps the order and duplicates of the requested fields', async () => {
       fetchMock.mockImplementation((_) => {
         return new Response(
           JSON.stringify({
@@ -148,6 +300,50 @@ describe('blocksize vwap', () => {
 
       const oracleProgram = await file(WASM_PATH).arrayBuffer();
 
+      const vmResult = await testOracleProgramExecution(
+        Buffer.from(oracleProgram),
+        Buffer.from('BTCUSD-ts,price,ts,price'),
+        fetchMock,
+        undefined,
+        undefined,
+        undefined,
+        0n,
+      );
+
+      handleExecutionVmResult(vmResult, 0, [
+        4n,
+        1756147348689n,
+        112269918585n,
+        1756147348689n,
+        112269918585n,
+        1756147348689n,
+      ]);
+      expect(fetchMock.mock.calls.map(([url]) => url.toString())).toEqual([
+        'https://seda-proxy.blocksize.dev/proxy/vwap/BTCUSD',
+      ]);
+    });
+
+    it('errors listing every non-existent field requested', async () => {
+      const oracleProgram = await file(WASM_PATH).arrayBuffer();
+
+      const vmResult = await testOracleProgramExecution(
+        Buffer.from(oracleProgram),
+        Buffer.from('BTCUSD-does_not_exist,price,bid'),
+        fetchMock,
+        undefined,
+        undefined,
+        undefined,
+        0n,
+      );
+
+      handleExecutionVmResult(vmResult, 1, []);
+      expect(Buffer.from(vmResult.result).toString()).toBe('Invalid fields: ["does_not_exist", "bid"]');
+      expect(fetchMock).not.toHaveBeenCalled();
+    });
+
+    it('errors if no valid fields are selected', async () => {
+      const oracleProgram = await file(WASM_PATH).arrayBuffer();
+
       const vmResult = await testOracleProgramExecution(
         Buffer.from(oracleProgram),
         Buffer.from('BTCUSD-does_not_exist'),
@@ -159,16 +355,187 @@ describe('blocksize vwap', () => {
       );
 
       handleExecutionVmResult(vmResult, 1, []);
+      expect(Buffer.from(vmResult.result).toString()).toBe('Invalid fields: ["does_not_exist"]');
+      expect(fetchMock).not.toHaveBeenCalled();
+    });
+
+    it('errors if an empty field list is given', async () => {
+      const oracleProgram = await file(WASM_PATH).arrayBuffer();
+
+      const vmResult = await testOracleProgramExecution(
+        Buffer.from(oracleProgram),
+        Buffer.from('BTCUSD-'),
+        fetchMock,
+        undefined,
+        undefined,
+        undefined,
+        0n,
+      );
+
+      handleExecutionVmResult(vmResult, 1, []);
+      expect(fetchMock).not.toHaveBeenCalled();
     });
   });
 
   describe('tally phase', () => {
+    describe('staleness', () => {
+      // One millisecond in the unit of `ts`
+      const MS = 1n;
+      const reveals = createRevealArray([
+        [RevealKind.BigIntArray, [1n, 100n, TS]],
+        [RevealKind.BigIntArray, [1n, 200n, TS - MS]],
+        [RevealKind.BigIntArray, [1n, 900n, TS - 10_000n * MS]],
+      ]);
+
+      it('keeps every reveal without a maximum age', async () => {
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+        const vmResult = await testOracleProgramTally(Buffer.from(oracleProgram), Buffer.from('tally-inputs'), reveals);
+        handleVmResult(vmResult, 0, [200n]);
+      });
+
+      it('discards the reveals older than the maximum age from the median ts', async () => {
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+        const vmResult = await testOracleProgramTally(Buffer.from(oracleProgram), Buffer.from('maxAge=5000'), reveals);
+        handleVmResult(vmResult, 0, [150n]);
+      });
+
+      it('measures the age from the reference time in the tally inputs', async () => {
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+        const now = TS / MS + 1n;
+        const vmResult = await testOracleProgramTally(
+          Buffer.from(oracleProgram),
+          Buffer.from(`nested,maxAge=1,now=${now}`),
+          reveals,
+        );
+        handleTupleTallyVmResult(vmResult, 0, [[[100n]]], ['uint256[][]']);
+      });
+
+      it('errors if every reveal is stale', async () => {
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+        const now = TS / MS + 60_000n;
+        const vmResult = await testOracleProgramTally(
+          Buffer.from(oracleProgram),
+          Buffer.from(`maxAge=5000,now=${now}`),
+          reveals,
+        );
+        handleVmResult(vmResult, 1, []);
+      });
+
+      it('uses the oldest pair of a reveal', async () => {
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+        const vmResult = await testOracleProgramTally(
+          Buffer.from(oracleProgram),
+          Buffer.from('maxAge=5000'),
+          createRevealArray([
+            [RevealKind.BigIntArray, [1n, 100n, 500n, TS, TS]],
+            [RevealKind.BigIntArray, [1n, 200n, 600n, TS, TS]],
+            [RevealKind.BigIntArray, [1n, 300n, 700n, TS, TS - 10_000n * MS]],
+          ]),
+        );
+        handleVmResult(vmResult, 0, [150n, 550n]);
+      });
+
+      it('errors if the maximum age is not a number', async () => {
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+        const vmResult = await testOracleProgramTally(Buffer.from(oracleProgram), Buffer.from('maxAge=5s'), reveals);
+        handleVmResult(vmResult, 1, []);
+      });
+
+      it('errors if the reference time is not a number', async () => {
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+        const vmResult = await testOracleProgramTally(
+          Buffer.from(oracleProgram),
+          Buffer.from('maxAge=5000,now=yesterday'),
+          reveals,
+        );
+        handleVmResult(vmResult, 1, []);
+      });
+    });
+
+    describe('several pairs', () => {
+      const reveals = createRevealArray([
+        [RevealKind.BigIntArray, [2n, 100n, 110n, 500n, 510n, TS, TS]],
+        [RevealKind.BigIntArray, [2n, 300n, 310n, 900n, 910n, TS, TS]],
+        [RevealKind.BigIntArray, [2n, 200n, 210n, 700n, 710n, TS, TS]],
+      ]);
+
+      it('returns the fields of every pair flattened by default', async () => {
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+        const vmResult = await testOracleProgramTally(Buffer.from(oracleProgram), Buffer.from('tally-inputs'), reveals);
+        handleVmResult(vmResult, 0, [200n, 210n, 700n, 710n]);
+      });
+
+      it('returns one row per pair with the nested layout', async () => {
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+        const vmResult = await testOracleProgramTally(Buffer.from(oracleProgram), Buffer.from('nested'), reveals);
+        handleTupleTallyVmResult(
+          vmResult,
+          0,
+          [
+            [
+              [200n, 210n],
+              [700n, 710n],
+            ],
+          ],
+          ['uint256[][]'],
+        );
+      });
+
+      it('returns the stride along with the values with the strided layout', async () => {
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+        const vmResult = await testOracleProgramTally(Buffer.from(oracleProgram), Buffer.from('strided'), reveals);
+        handleTupleTallyVmResult(vmResult, 0, [2n, [200n, 210n, 700n, 710n]], ['uint256', 'uint256[]']);
+      });
+
+      it('ignores reveals with a different stride', async () => {
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+        const vmResult = await testOracleProgramTally(
+          Buffer.from(oracleProgram),
+          Buffer.from('tally-inputs'),
+          createRevealArray([
+            [RevealKind.BigIntArray, [2n, 100n, 110n, TS]],
+            [RevealKind.BigIntArray, [1n, 300n, 310n, TS, TS]],
+            [RevealKind.BigIntArray, [2n, 200n, 210n, TS]],
+          ]),
+        );
+        handleVmResult(vmResult, 0, [150n, 160n]);
+      });
+
+      it('keeps the stride most reveals agree on', async () => {
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+        const vmResult = await testOracleProgramTally(
+          Buffer.from(oracleProgram),
+          Buffer.from('tally-inputs'),
+          createRevealArray([
+            [RevealKind.BigIntArray, [1n, 300n, 310n, TS, TS]],
+            [RevealKind.BigIntArray, [2n, 100n, 110n, TS]],
+            [RevealKind.BigIntArray, [2n, 200n, 210n, TS]],
+          ]),
+        );
+        handleVmResult(vmResult, 0, [150n, 160n]);
+      });
+
+      it('ignores reveals with a different number of pairs than most reveals', async () => {
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+        const vmResult = await testOracleProgramTally(
+          Buffer.from(oracleProgram),
+          Buffer.from('tally-inputs'),
+          createRevealArray([
+            [RevealKind.BigIntArray, [1n, 300n, TS]],
+            [RevealKind.BigIntArray, [1n, 100n, 500n, TS, TS]],
+            [RevealKind.BigIntArray, [1n, 200n, 600n, TS, TS]],
+          ]),
+        );
+        handleVmResult(vmResult, 0, [150n, 550n]);
+      });
+    });
+
     it('works with 1 price', async () => {
       const oracleProgram = await file(WASM_PATH).arrayBuffer();
       const vmResult = await testOracleProgramTally(
         Buffer.from(oracleProgram),
         Buffer.from('tally-inputs'),
-        createRevealArray([[RevealKind.BigIntArray, [100n]]]),
+        createRevealArray([[RevealKind.BigIntArray, [1n, 100n, TS]]]),
       );
       handleVmResult(vmResult, 0, [100n]);
     });
@@ -179,8 +546,8 @@ describe('blocksize vwap', () => {
         Buffer.from(oracleProgram),
         Buffer.from('tally-inputs'),
         createRevealArray([
-          [RevealKind.BigIntArray, [100n]],
-          [RevealKind.BigIntArray, [200n]],
+          [RevealKind.BigIntArray, [1n, 100n, TS]],
+          [RevealKind.BigIntArray, [1n, 200n, TS]],
         ]),
       );
       handleVmResult(vmResult, 0, [150n]);
@@ -192,8 +559,8 @@ describe('blocksize vwap', () => {
         Buffer.from(oracleProgram),
         Buffer.from('tally-inputs'),
         createRevealArray([
-          [RevealKind.BigIntArray, [100n]],
-          [RevealKind.BigIntArray, [200n]],
+          [RevealKind.BigIntArray, [1n, 100n, TS]],
+          [RevealKind.BigIntArray, [1n, 200n, TS]],
         ]),
       );
       handleVmResult(vmResult, 0, [150n]);
@@ -205,9 +572,9 @@ describe('blocksize vwap', () => {
         Buffer.from(oracleProgram),
         Buffer.from('tally-inputs'),
         createRevealArray([
-          [RevealKind.BigIntArray, [100n, 500n]],
-          [RevealKind.BigIntArray, [300n, 900n]],
-          [RevealKind.BigIntArray, [200n, 700n]],
+          [RevealKind.BigIntArray, [2n, 100n, 500n, TS]],
+          [RevealKind.BigIntArray, [2n, 300n, 900n, TS]],
+          [RevealKind.BigIntArray, [2n, 200n, 700n, TS]],
         ]),
       );
       handleVmResult(vmResult, 0, [200n, 700n]);
@@ -219,11 +586,11 @@ describe('blocksize vwap', () => {
         Buffer.from(oracleProgram),
         Buffer.from('tally-inputs'),
         createRevealArray([
-          [RevealKind.BigIntArray, [100n]],
-          [RevealKind.BigIntArray, [200n]],
-          [RevealKind.BigIntArray, [300n]],
-          [RevealKind.BigIntArray, [400n]],
-          [RevealKind.BigIntArray, [500n]],
+          [RevealKind.BigIntArray, [1n, 100n, TS]],
+          [RevealKind.BigIntArray, [1n, 200n, TS]],
+          [RevealKind.BigIntArray, [1n, 300n, TS]],
+          [RevealKind.BigIntArray, [1n, 400n, TS]],
+          [RevealKind.BigIntArray, [1n, 500n, TS]],
         ]),
       );
       handleVmResult(vmResult, 0, [300n]);
@@ -235,16 +602,16 @@ describe('blocksize vwap', () => {
         Buffer.from(oracleProgram),
         Buffer.from('tally-inputs'),
         createRevealArray([
-          [RevealKind.BigIntArray, [100n]],
-          [RevealKind.BigIntArray, [200n]],
-          [RevealKind.BigIntArray, [300n]],
-          [RevealKind.BigIntArray, [400n]],
-          [RevealKind.BigIntArray, [500n]],
-          [RevealKind.BigIntArray, [600n]],
-          [RevealKind.BigIntArray, [700n]],
-          [RevealKind.BigIntArray, [800n]],
-          [RevealKind.BigIntArray, [900n]],
-          [RevealKind.BigIntArray, [1000n]],
+          [RevealKind.BigIntArray, [1n, 100n, TS]],
+          [RevealKind.BigIntArray, [1n, 200n, TS]],
+          [RevealKind.BigIntArray, [1n, 300n, TS]],
+          [RevealKind.BigIntArray, [1n, 400n, TS]],
+          [RevealKind.BigIntArray, [1n, 500n, TS]],
+          [RevealKind.BigIntArray, [1n, 600n, TS]],
+          [RevealKind.BigIntArray, [1n, 700n, TS]],
+          [RevealKind.BigIntArray, [1n, 800n, TS]],
+          [RevealKind.BigIntArray, [1n, 900n, TS]],
+          [RevealKind.BigIntArray, [1n, 1000n, TS]],
         ]),
       );
       handleVmResult(vmResult, 0, [550n]);
@@ -256,12 +623,12 @@ describe('blocksize vwap', () => {
         Buffer.from(oracleProgram),
         Buffer.from('tally-inputs'),
         createRevealArray([
-          [RevealKind.BigIntArray, [500n]],
-          [RevealKind.BigIntArray, [100n]],
-          [RevealKind.BigIntArray, [300n]],
-          [RevealKind.BigIntArray, [200n]],
-          [RevealKind.BigIntArray, [200n]],
-          [RevealKind.BigIntArray, [400n]],
+          [RevealKind.BigIntArray, [1n, 500n, TS]],
+          [RevealKind.BigIntArray, [1n, 100n, TS]],
+          [RevealKind.BigIntArray, [1n, 300n, TS]],
+          [RevealKind.BigIntArray, [1n, 200n, TS]],
+          [RevealKind.BigIntArray, [1n, 200n, TS]],
+          [RevealKind.BigIntArray, [1n, 400n, TS]],
         ]),
       );
       handleVmResult(vmResult, 0, [250n]);
@@ -274,7 +641,11 @@ describe('blocksize vwap', () => {
         const vmResult = await testOracleProgramTally(
           Buffer.from(oracleProgram),
           Buffer.from('tally-inputs'),
-          createRevealArray([[RevealKind.BigIntArray, [100n]], [RevealKind.Failed], [RevealKind.BigIntArray, [200n]]]),
+          createRevealArray([
+            [RevealKind.BigIntArray, [1n, 100n, TS]],
+            [RevealKind.Failed],
+            [RevealKind.BigIntArray, [1n, 200n, TS]],
+          ]),
         );
 
         handleVmResult(vmResult, 0, [150n]);
@@ -287,11 +658,11 @@ describe('blocksize vwap', () => {
           Buffer.from(oracleProgram),
           Buffer.from('tally-inputs'),
           createRevealArray([
-            [RevealKind.BigIntArray, [100n]],
+            [RevealKind.BigIntArray, [1n, 100n, TS]],
             [RevealKind.Failed],
-            [RevealKind.BigIntArray, [200n]],
+            [RevealKind.BigIntArray, [1n, 200n, TS]],
             [RevealKind.Failed],
-            [RevealKind.BigIntArray, [300n]],
+            [RevealKind.BigIntArray, [1n, 300n, TS]],
           ]),
         );
 
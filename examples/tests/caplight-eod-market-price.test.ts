@@ -1,18 +1,46 @@
 // biome-ignore assist/source/organizeImports: biome is lying
 import { file } from 'bun';
-import { afterEach, describe, it, mock } from 'bun:test';
+import { afterEach, describe, expect, it, mock } from 'bun:test';
 import { testOracleProgramExecution, testOracleProgramTally } from '@seda-protocol/dev-tools';
 import {
   handleBigIntTallyVmResult as handleVmResult,
   handleBigIntExecutionVmResult as handleExecutionVmResult,
+  handleBigIntArrayExecutionVmResult,
+  handleTupleTallyVmResult,
   createRevealArray,
   RevealKind,
 } from './utils.js';
+import type { VmResult } from '@seda-protocol/vm';
 
 const WASM_PATH = 'target/wasm32-wasip1/release/caplight-eod-market-price.wasm';
 
 const fetchMock = mock();
 
+// The ABI types of the full valuation tuple
+const VALUATION_CODECS = ['uint256', 'uint256', 'uint256', 'uint32', 'uint32', 'int256'];
+
+function marketPriceResponse(overrides: Record<string, unknown> = {}) {
+  return JSON.stringify({
+    date: '2022-09-10',
+    price: 15.9,
+    estimatedValuation: 2150000000,
+    priceStandardError: 1.35,
+    generatedAtTimestamp: 1690000000,
+    daysSinceLastDataPoint: 10,
+    numberOfPoints6mo: 12,
+    orderImbalance: {
+      bidContribution: 0.53,
+      offerContribution: 0.25,
+    },
+    ...overrides,
+  });
+}
+
+// The prices of a batch along with whether every pitchbook id got a price
+function handleBatchTallyVmResult(vmResult: VmResult, exitCode: number, prices: bigint[], ok: boolean[]) {
+  handleTupleTallyVmResult(vmResult, exitCode, [prices, ok], ['uint256[]', 'bool[]']);
+}
+
 afterEach(() => {
   fetchMock.mockRestore();
 });
@@ -51,6 +79,427 @@ describe('caplight eod market price', () => {
       );
 
       handleExecutionVmResult(vmResult, 0, 1590n);
+      expect(fetchMock.mock.calls.map(([url]) => url.toString())).toEqual([
+        'http://104.155.34.32:5384/proxy/market-price-fixed-eod?pitchbookId=BTC-USDT',
+      ]);
+    });
+
+    it('percent-encodes the pitchbook id', async () => {
+      fetchMock.mockImplementation((_) => new Response(marketPriceResponse()));
+
+      const oracleProgram = await file(WASM_PATH).arrayBuffer();
+
+      const vmResult = await testOracleProgramExecution(
+        Buffer.from(oracleProgram),
+        Buffer.from('54782-29&utcHour=8'),
+        fetchMock,
+        undefined,
+        undefined,
+        undefined,
+        0n,
+      );
+
+      handleExecutionVmResult(vmResult, 0, 159000n);
+      expect(fetchMock.mock.calls.map(([url]) => url.toString())).toEqual([
+        'http://104.155.34.32:5384/proxy/market-price-fixed-eod?pitchbookId=54782-29%26utcHour%3D8',
+      ]);
+    });
+
+    it('reveals the full valuation payload', async () => {
+      fetchMock.mockImplementation((_) => new Response(marketPriceResponse()));
+
+      const oracleProgram = await file(WASM_PATH).arrayBuffer();
+
+      const vmResult = await testOracleProgramExecution(
+        Buffer.from(oracleProgram),
+        Buffer.from('54782-29,4,valuation'),
+        fetchMock,
+        undefined,
+        undefined,
+        undefined,
+        0n,
+      );
+
+      // price, valuation, stdErr and imbalance (0.53 - 0.25) have 4 decimals
+      handleBigIntArrayExecutionVmResult(vmResult, 0, [159000n, 21500000000000n, 13500n, 10n, 12n, 2800n]);
+      expect(fetchMock.mock.calls.map(([url]) => url.toString())).toEqual([
+        'http://104.155.34.32:5384/proxy/market-price-fixed-eod?pitchbookId=54782-29&utcHour=4',
+      ]);
+    });
+
+    it('errors if a valuation field is missing', async () => {
+      fetchMock.mockImplementation((_) => new Response(marketPriceResponse({ orderImbalance: null })));
+
+      const oracleProgram = await file(WASM_PATH).arrayBuffer();
+
+      const vmResult = await testOracleProgramExecution(
+        Buffer.from(oracleProgram),
+        Buffer.from('54782-29,valuation'),
+        fetchMock,
+        undefined,
+        undefined,
+        undefined,
+        0n,
+      );
+
+      handleBigIntArrayExecutionVmResult(vmResult, 1, []);
+    });
+    describe('historical date', () => {
+      it('passes the date to the proxy and reveals it with the response date', async () => {
+        fetchMock.mockImplementation((_) => new Response(marketPriceResponse()));
+
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+
+        const vmResult = await testOracleProgramExecution(
+          Buffer.from(oracleProgram),
+          Buffer.from('54782-29,4,date=2022-09-10'),
+          fetchMock,
+          undefined,
+          undefined,
+          undefined,
+          0n,
+        );
+
+        handleBigIntArrayExecutionVmResult(vmResult, 0, [159000n, 20220910n, 20220910n]);
+        expect(fetchMock.mock.calls.map(([url]) => url.toString())).toEqual([
+          'http://104.155.34.32:5384/proxy/market-price-fixed-eod?pitchbookId=54782-29&utcHour=4&date=2022-09-10',
+        ]);
+      });
+
+      it('reveals the date of the response even if it differs', async () => {
+        fetchMock.mockImplementation((_) => new Response(marketPriceResponse({ date: '2022-09-09' })));
+
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+
+        const vmResult = await testOracleProgramExecution(
+          Buffer.from(oracleProgram),
+          Buffer.from('54782-29,valuation,date=2022-09-10'),
+          fetchMock,
+          undefined,
+          undefined,
+          undefined,
+          0n,
+        );
+
+        handleBigIntArrayExecutionVmResult(vmResult, 0, [
+          159000n,
+          21500000000000n,
+          13500n,
+          10n,
+          12n,
+          2800n,
+          20220910n,
+          20220909n,
+        ]);
+      });
+
+      it("rejects a date that doesn't exist", async () => {
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+
+        const vmResult = await testOracleProgramExecution(
+          Buffer.from(oracleProgram),
+          Buffer.from('54782-29,date=2023-02-29'),
+          fetchMock,
+          undefined,
+          undefined,
+          undefined,
+          0n,
+        );
+
+        handleExecutionVmResult(vmResult, 1, 0n);
+        expect(fetchMock).not.toHaveBeenCalled();
+      });
+
+      it("rejects a date that isn't in the YYYY-MM-DD format", async () => {
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+
+        const vmResult = await testOracleProgramExecution(
+          Buffer.from(oracleProgram),
+          Buffer.from('54782-29,date=2022-9-10'),
+          fetchMock,
+          undefined,
+          undefined,
+          undefined,
+          0n,
+        );
+
+        handleExecutionVmResult(vmResult, 1, 0n);
+        expect(fetchMock).not.toHaveBeenCalled();
+      });
+
+      it('rejects a date with a time', async () => {
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+
+        const vmResult = await testOracleProgramExecution(
+          Buffer.from(oracleProgram),
+          Buffer.from('54782-29,date=2022-09-10T00:00:00Z'),
+          fetchMock,
+          undefined,
+          undefined,
+          undefined,
+          0n,
+        );
+
+        handleExecutionVmResult(vmResult, 1, 0n);
+        expect(fetchMock).not.toHaveBeenCalled();
+      });
+    });
+
+    describe('data quality', () => {
+      it('reports a price within every threshold', async () => {
+        fetchMock.mockImplementation((_) => new Response(marketPriceResponse()));
+
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+
+        const vmResult = await testOracleProgramExecution(
+          Buffer.from(oracleProgram),
+          Buffer.from('54782-29,4,maxDaysSince=10,minPoints6mo=12,maxStdErrRatio=0.1'),
+          fetchMock,
+          undefined,
+          undefined,
+          undefined,
+          0n,
+        );
+
+        handleExecutionVmResult(vmResult, 0, 159000n);
+      });
+
+      it('refuses a price whose last data point is too old', async () => {
+        fetchMock.mockImplementation((_) => new Response(marketPriceResponse()));
+
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+
+        const vmResult = await testOracleProgramExecution(
+          Buffer.from(oracleProgram),
+          Buffer.from('54782-29,maxDaysSince=5'),
+          fetchMock,
+          undefined,
+          undefined,
+          undefined,
+          0n,
+        );
+
+        handleExecutionVmResult(vmResult, 2, 0n);
+        expect(Buffer.from(vmResult.result).toString()).toBe('Price data quality below threshold');
+      });
+
+      it('refuses a price with too few data points', async () => {
+        fetchMock.mockImplementation((_) => new Response(marketPriceResponse()));
+
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+
+        const vmResult = await testOracleProgramExecution(
+          Buffer.from(oracleProgram),
+          Buffer.from('54782-29,minPoints6mo=20'),
+          fetchMock,
+          undefined,
+          undefined,
+          undefined,
+          0n,
+        );
+
+        handleExecutionVmResult(vmResult, 2, 0n);
+        expect(Buffer.from(vmResult.result).toString()).toBe('Price data quality below threshold');
+      });
+
+      it('refuses a price with a too large standard error', async () => {
+        fetchMock.mockImplementation((_) => new Response(marketPriceResponse()));
+
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+
+        const vmResult = await testOracleProgramExecution(
+          Buffer.from(oracleProgram),
+          Buffer.from('54782-29,valuation,maxStdErrRatio=0.05'),
+          fetchMock,
+          undefined,
+          undefined,
+          undefined,
+          0n,
+        );
+
+        handleExecutionVmResult(vmResult, 2, 0n);
+        expect(Buffer.from(vmResult.result).toString()).toBe('Price data quality below threshold');
+      });
+
+      it('refuses a price missing a field needed by a threshold', async () => {
+        fetchMock.mockImplementation((_) => new Response(marketPriceResponse({ daysSinceLastDataPoint: null })));
+
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+
+        const vmResult = await testOracleProgramExecution(
+          Buffer.from(oracleProgram),
+          Buffer.from('54782-29,maxDaysSince=200'),
+          fetchMock,
+          undefined,
+          undefined,
+          undefined,
+          0n,
+        );
+
+        handleExecutionVmResult(vmResult, 2, 0n);
+        expect(Buffer.from(vmResult.result).toString()).toBe('Price data quality below threshold');
+      });
+
+      it('errors on an invalid threshold', async () => {
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+
+        const vmResult = await testOracleProgramExecution(
+          Buffer.from(oracleProgram),
+          Buffer.from('54782-29,maxStdErrRatio=-1'),
+          fetchMock,
+          undefined,
+          undefined,
+          undefined,
+          0n,
+        );
+
+        handleExecutionVmResult(vmResult, 1, 0n);
+        expect(fetchMock).not.toHaveBeenCalled();
+      });
+    });
+
+    describe('batch', () => {
+      it('fetches every pitchbook id one after the other', async () => {
+        fetchMock.mockImplementation((url) => {
+          if (url.toString().includes('12345-67')) {
+            return new Response(marketPriceResponse({ price: 42.5 }));
+          }
+          return new Response(marketPriceResponse());
+        });
+
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+
+        const vmResult = await testOracleProgramExecution(
+          Buffer.from(oracleProgram),
+          Buffer.from('54782-29;12345-67,4'),
+          fetchMock,
+          undefined,
+          undefined,
+          undefined,
+          0n,
+        );
+
+        expect(vmResult.exitCode).toBe(0);
+        expect(JSON.parse(Buffer.from(vmResult.result).toString())).toEqual({
+          ids: ['54782-29', '12345-67'],
+          prices: [159000, 425000],
+        });
+        expect(fetchMock.mock.calls.map(([url]) => url.toString())).toEqual([
+          'http://104.155.34.32:5384/proxy/market-price-fixed-eod?pitchbookId=54782-29&utcHour=4',
+          'http://104.155.34.32:5384/proxy/market-price-fixed-eod?pitchbookId=12345-67&utcHour=4',
+        ]);
+      });
+
+      it('reveals null for a pitchbook id that fails without aborting the others', async () => {
+        fetchMock.mockImplementation((url) => {
+          if (url.toString().includes('12345-67')) {
+            return new Response('Not found', { status: 404 });
+          }
+          if (url.toString().includes('99999-99')) {
+            return new Response(marketPriceResponse({ daysSinceLastDataPoint: 30 }));
+          }
+          return new Response(marketPriceResponse());
+        });
+
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+
+        const vmResult = await testOracleProgramExecution(
+          Buffer.from(oracleProgram),
+          Buffer.from('12345-67;54782-29;99999-99,maxDaysSince=20'),
+          fetchMock,
+          undefined,
+          undefined,
+          undefined,
+          0n,
+        );
+
+        expect(vmResult.exitCode).toBe(0);
+        expect(JSON.parse(Buffer.from(vmResult.result).toString())).toEqual({
+          ids: ['12345-67', '54782-29', '99999-99'],
+          prices: [null, 159000, null],
+        });
+        expect(fetchMock).toHaveBeenCalledTimes(3);
+      });
+
+      it('reveals the date of every response', async () => {
+        fetchMock.mockImplementation((url) => {
+          if (url.toString().includes('12345-67')) {
+            return new Response(marketPriceResponse({ date: '2022-09-09' }));
+          }
+          return new Response(marketPriceResponse());
+        });
+
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+
+        const vmResult = await testOracleProgramExecution(
+          Buffer.from(oracleProgram),
+          Buffer.from('54782-29;12345-67,date=2022-09-10'),
+          fetchMock,
+          undefined,
+          undefined,
+          undefined,
+          0n,
+        );
+
+        expect(vmResult.exitCode).toBe(0);
+        expect(JSON.parse(Buffer.from(vmResult.result).toString())).toEqual({
+          ids: ['54782-29', '12345-67'],
+          prices: [159000, 159000],
+          dates: [20220910, 20220909],
+        });
+      });
+
+      it('errors if every pitchbook id fails', async () => {
+        fetchMock.mockImplementation((_) => new Response('Not found', { status: 404 }));
+
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+
+        const vmResult = await testOracleProgramExecution(
+          Buffer.from(oracleProgram),
+          Buffer.from('54782-29;12345-67'),
+          fetchMock,
+          undefined,
+          undefined,
+          undefined,
+          0n,
+        );
+
+        handleExecutionVmResult(vmResult, 1, 0n);
+      });
+
+      it('rejects the full valuation payload for several pitchbook ids', async () => {
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+
+        const vmResult = await testOracleProgramExecution(
+          Buffer.from(oracleProgram),
+          Buffer.from('54782-29;12345-67,valuation'),
+          fetchMock,
+          undefined,
+          undefined,
+          undefined,
+          0n,
+        );
+
+        handleExecutionVmResult(vmResult, 1, 0n);
+        expect(fetchMock).not.toHaveBeenCalled();
+      });
+
+      it('rejects an empty pitchbook id', async () => {
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+
+        const vmResult = await testOracleProgramExecution(
+          Buffer.from(oracleProgram),
+          Buffer.from('54782-29;;12345-67'),
+          fetchMock,
+          undefined,
+          undefined,
+          undefined,
+          0n,
+        );
+
+        handleExecutionVmResult(vmResult, 1, 0n);
+        expect(fetchMock).not.toHaveBeenCalled();
+      });
     });
   });
 
@@ -132,6 +581,264 @@ describe('caplight eod market price', () => {
       handleVmResult(vmResult, 0, 250n);
     });
 
+    describe('full valuation', () => {
+      it('returns the median of every field as a tuple', async () => {
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+        const vmResult = await testOracleProgramTally(
+          Buffer.from(oracleProgram),
+          Buffer.from('tally-inputs'),
+          createRevealArray([
+            [RevealKind.BigIntArray, [159000n, 21500000000000n, 13500n, 10n, 12n, 2800n]],
+            [RevealKind.BigIntArray, [160000n, 21600000000000n, 14000n, 11n, 12n, BigInt.asUintN(128, -1000n)]],
+            [RevealKind.BigIntArray, [158000n, 21400000000000n, 13000n, 10n, 13n, BigInt.asUintN(128, -3000n)]],
+          ]),
+        );
+        handleTupleTallyVmResult(vmResult, 0, [159000n, 21500000000000n, 13500n, 10n, 12n, -1000n], VALUATION_CODECS);
+      });
+
+      it('ignores the reveals with a different payload', async () => {
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+        const vmResult = await testOracleProgramTally(
+          Buffer.from(oracleProgram),
+          Buffer.from('tally-inputs'),
+          createRevealArray([
+            [RevealKind.BigIntArray, [159000n, 21500000000000n, 13500n, 10n, 12n, 2800n]],
+            [RevealKind.BigInt, 100n],
+            [RevealKind.BigIntArray, [161000n, 21700000000000n, 14500n, 12n, 14n, 3800n]],
+          ]),
+        );
+        handleTupleTallyVmResult(vmResult, 0, [160000n, 21600000000000n, 14000n, 11n, 13n, 3300n], VALUATION_CODECS);
+      });
+
+      it('keeps the payload most reveals agree on when the first reveal is the outlier', async () => {
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+        const vmResult = await testOracleProgramTally(
+          Buffer.from(oracleProgram),
+          Buffer.from('tally-inputs'),
+          createRevealArray([
+            [RevealKind.BigInt, 100n],
+            [RevealKind.BigIntArray, [159000n, 21500000000000n, 13500n, 10n, 12n, 2800n]],
+            [RevealKind.BigIntArray, [161000n, 21700000000000n, 14500n, 12n, 14n, 3800n]],
+          ]),
+        );
+        handleTupleTallyVmResult(vmResult, 0, [160000n, 21600000000000n, 14000n, 11n, 13n, 3300n], VALUATION_CODECS);
+      });
+
+      it('ignores reveals with counts that do not fit in a uint32', async () => {
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+        const vmResult = await testOracleProgramTally(
+          Buffer.from(oracleProgram),
+          Buffer.from('tally-inputs'),
+          createRevealArray([
+            [RevealKind.BigIntArray, [159000n, 21500000000000n, 13500n, 10n, 12n, 2800n]],
+            [RevealKind.BigIntArray, [159000n, 21500000000000n, 13500n, 1n << 32n, 12n, 2800n]],
+          ]),
+        );
+        handleTupleTallyVmResult(vmResult, 0, [159000n, 21500000000000n, 13500n, 10n, 12n, 2800n], VALUATION_CODECS);
+      });
+    });
+
+    describe('historical date', () => {
+      it('returns the median price as of the requested date', async () => {
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+        const vmResult = await testOracleProgramTally(
+          Buffer.from(oracleProgram),
+          Buffer.from('date=2022-09-10'),
+          createRevealArray([
+            [RevealKind.BigIntArray, [100n, 20220910n, 20220910n]],
+            [RevealKind.BigIntArray, [200n, 20220910n, 20220910n]],
+          ]),
+        );
+        handleVmResult(vmResult, 0, 150n);
+      });
+
+      it('ignores reveals whose response date does not match the requested date', async () => {
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+        const vmResult = await testOracleProgramTally(
+          Buffer.from(oracleProgram),
+          Buffer.from('date=2022-09-10'),
+          createRevealArray([
+            [RevealKind.BigIntArray, [200n, 20220910n, 20220909n]],
+            [RevealKind.BigIntArray, [100n, 20220910n, 20220910n]],
+            [RevealKind.BigIntArray, [300n, 20220910n, 20220910n]],
+          ]),
+        );
+        handleVmResult(vmResult, 0, 200n);
+      });
+
+      it('errors if no response date matches the requested date', async () => {
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+        const vmResult = await testOracleProgramTally(
+          Buffer.from(oracleProgram),
+          Buffer.from('date=2022-09-10'),
+          createRevealArray([
+            [RevealKind.BigIntArray, [100n, 20220910n, 20220909n]],
+            [RevealKind.BigIntArray, [200n, 20220910n, 20220908n]],
+          ]),
+        );
+        handleVmResult(vmResult, 1, 0n);
+        expect(Buffer.from(vmResult.result).toString()).toBe("Response date doesn't match the requested date");
+      });
+
+      it('ignores dated reveals without a date in the tally inputs', async () => {
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+        const vmResult = await testOracleProgramTally(
+          Buffer.from(oracleProgram),
+          Buffer.from('tally-inputs'),
+          createRevealArray([
+            [RevealKind.BigIntArray, [100n, 20220910n, 20220910n]],
+            [RevealKind.BigInt, 400n],
+          ]),
+        );
+        handleVmResult(vmResult, 0, 400n);
+      });
+
+      it('errors on an invalid date in the tally inputs', async () => {
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+        const vmResult = await testOracleProgramTally(
+          Buffer.from(oracleProgram),
+          Buffer.from('date=2022-9-10'),
+          createRevealArray([[RevealKind.BigIntArray, [100n, 20220910n, 20220910n]]]),
+        );
+        handleVmResult(vmResult, 1, 0n);
+        expect(Buffer.from(vmResult.result).toString()).toBe('Invalid tally inputs');
+      });
+
+      it('ignores reveals for another requested date', async () => {
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+        const vmResult = await testOracleProgramTally(
+          Buffer.from(oracleProgram),
+          Buffer.from('date=2022-09-10'),
+          createRevealArray([
+            [RevealKind.BigIntArray, [100n, 20220910n, 20220910n]],
+            [RevealKind.BigIntArray, [200n, 20220909n, 20220909n]],
+            [RevealKind.BigIntArray, [300n, 20220910n, 20220910n]],
+            [RevealKind.BigInt, 400n],
+          ]),
+        );
+        handleVmResult(vmResult, 0, 200n);
+      });
+
+      it('returns the full valuation as of the requested date', async () => {
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+        const vmResult = await testOracleProgramTally(
+          Buffer.from(oracleProgram),
+          Buffer.from('date=2022-09-10'),
+          createRevealArray([
+            [RevealKind.BigIntArray, [159000n, 21500000000000n, 13500n, 10n, 12n, 2800n, 20220910n, 20220910n]],
+          ]),
+        );
+        handleTupleTallyVmResult(vmResult, 0, [159000n, 21500000000000n, 13500n, 10n, 12n, 2800n], VALUATION_CODECS);
+      });
+    });
+
+    describe('batch', () => {
+      it('returns the median price of every pitchbook id', async () => {
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+        const vmResult = await testOracleProgramTally(
+          Buffer.from(oracleProgram),
+          Buffer.from('tally-inputs'),
+          createRevealArray([
+            [RevealKind.Json, '{"ids":["54782-29","12345-67"],"prices":[100,1000]}'],
+            [RevealKind.Json, '{"ids":["54782-29","12345-67"],"prices":[200,3000]}'],
+            [RevealKind.Json, '{"ids":["54782-29","12345-67"],"prices":[300,2000]}'],
+          ]),
+        );
+        handleBatchTallyVmResult(vmResult, 0, [200n, 2000n], [true, true]);
+      });
+
+      it('skips the failed prices of a pitchbook id and flags it if none succeeded', async () => {
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+        const vmResult = await testOracleProgramTally(
+          Buffer.from(oracleProgram),
+          Buffer.from('tally-inputs'),
+          createRevealArray([
+            [RevealKind.Json, '{"ids":["54782-29","12345-67","99999-99"],"prices":[100,null,null]}'],
+            [RevealKind.Json, '{"ids":["54782-29","12345-67","99999-99"],"prices":[200,3000,null]}'],
+            [RevealKind.Failed],
+          ]),
+        );
+        handleBatchTallyVmResult(vmResult, 0, [150n, 3000n, 0n], [true, true, false]);
+      });
+
+      it('tells a zero price apart from a pitchbook id without a price', async () => {
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+        const vmResult = await testOracleProgramTally(
+          Buffer.from(oracleProgram),
+          Buffer.from('tally-inputs'),
+          createRevealArray([[RevealKind.Json, '{"ids":["54782-29","12345-67","99999-99"],"prices":[0,null,100]}']]),
+        );
+        handleBatchTallyVmResult(vmResult, 0, [0n, 0n, 100n], [true, false, true]);
+      });
+
+      it('ignores reveals for other pitchbook ids', async () => {
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+        const vmResult = await testOracleProgramTally(
+          Buffer.from(oracleProgram),
+          Buffer.from('tally-inputs'),
+          createRevealArray([
+            [RevealKind.Json, '{"ids":["54782-29","12345-67"],"prices":[100,1000]}'],
+            [RevealKind.Json, '{"ids":["12345-67","54782-29"],"prices":[5000,5000]}'],
+            [RevealKind.Json, '{"ids":["54782-29"],"prices":[5000]}'],
+            [RevealKind.BigInt, 5000n],
+            [RevealKind.Json, '{"ids":["54782-29","12345-67"],"prices":[300,3000]}'],
+          ]),
+        );
+        handleBatchTallyVmResult(vmResult, 0, [200n, 2000n], [true, true]);
+      });
+
+      it('keeps the pitchbook ids most reveals agree on when the first reveal is the outlier', async () => {
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+        const vmResult = await testOracleProgramTally(
+          Buffer.from(oracleProgram),
+          Buffer.from('tally-inputs'),
+          createRevealArray([
+            [RevealKind.Json, '{"ids":["12345-67","54782-29"],"prices":[5000,5000]}'],
+            [RevealKind.Json, '{"ids":["54782-29","12345-67"],"prices":[100,1000]}'],
+            [RevealKind.Json, '{"ids":["54782-29","12345-67"],"prices":[300,3000]}'],
+          ]),
+        );
+        handleBatchTallyVmResult(vmResult, 0, [200n, 2000n], [true, true]);
+      });
+
+      it('skips the prices of a pitchbook id that are not as of the requested date', async () => {
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+        const vmResult = await testOracleProgramTally(
+          Buffer.from(oracleProgram),
+          Buffer.from('date=2022-09-10'),
+          createRevealArray([
+            [RevealKind.Json, '{"ids":["54782-29","12345-67"],"prices":[100,1000],"dates":[20220910,20220909]}'],
+            [RevealKind.Json, '{"ids":["54782-29","12345-67"],"prices":[200,3000],"dates":[20220910,20220910]}'],
+            [RevealKind.Json, '{"ids":["54782-29","12345-67"],"prices":[300,2000],"dates":[20220910,null]}'],
+          ]),
+        );
+        handleBatchTallyVmResult(vmResult, 0, [200n, 3000n], [true, true]);
+      });
+
+      it('ignores dated batch reveals without a date in the tally inputs', async () => {
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+        const vmResult = await testOracleProgramTally(
+          Buffer.from(oracleProgram),
+          Buffer.from('tally-inputs'),
+          createRevealArray([
+            [RevealKind.Json, '{"ids":["54782-29","12345-67"],"prices":[100,1000],"dates":[20220910,20220910]}'],
+            [RevealKind.Json, '{"ids":["54782-29","12345-67"],"prices":[200,3000]}'],
+          ]),
+        );
+        handleBatchTallyVmResult(vmResult, 0, [200n, 3000n], [true, true]);
+      });
+
+      it('errors if no pitchbook id has a price', async () => {
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+        const vmResult = await testOracleProgramTally(
+          Buffer.from(oracleProgram),
+          Buffer.from('tally-inputs'),
+          createRevealArray([[RevealKind.Json, '{"ids":["54782-29","12345-67"],"prices":[null,null]}']]),
+        );
+        handleBatchTallyVmResult(vmResult, 1, [], []);
+      });
+    });
+
     describe('works with errored executions', () => {
       it('should ignore the errored execution', async () => {
         const oracleProgram = await file(WASM_PATH).arrayBuffer();
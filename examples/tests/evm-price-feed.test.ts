@@ -4,12 +4,14 @@ import { afterEach, describe, expect, it, mock } from 'bun:test';
 import { testOracleProgramExecution, testOracleProgramTally } from '@seda-protocol/dev-tools';
 import { match } from 'ts-pattern';
 import {
-  handleJsonBigIntArrayExecutionVmResult as handleExecutionVmResult,
-  handleBigIntArrayTallyVmResult as handleTallyVmResult,
-  createSuccessfulJsonBigIntArrayReveal as createSuccessfulReveal,
-  createFailedReveal,
+  handleJsonExecutionVmResult as handleExecutionVmResult,
+  handleTupleTallyVmResult,
+  createRevealArray,
+  RevealKind,
+  type RevealInput,
 } from './utils.js';
 import { ethers } from 'ethers';
+import type { VmResult } from '@seda-protocol/vm';
 
 const WASM_PATH = 'target/wasm32-wasip1/release/evm-price-feed.wasm';
 
@@ -19,13 +21,35 @@ afterEach(() => {
   fetchMock.mockRestore();
 });
 
+function tickerResponse(symbol: string, lastPrice: string, closeTime: number) {
+  return new Response(JSON.stringify({ symbol, lastPrice, openPrice: lastPrice, closeTime }));
+}
+
+function encodeExecInputs(tickers: [string, number][]) {
+  const execInputsStr = ethers.AbiCoder.defaultAbiCoder().encode(['(string,uint8)[]'], [tickers]);
+  // Remove '0x' prefix
+  return Buffer.from(execInputsStr.slice(2), 'hex');
+}
+
+function priceReveal(prices: (bigint | null)[], decimals: number[], observedAt = 1753806883): RevealInput {
+  const encodedPrices = `[${prices.map((v) => (v === null ? 'null' : v.toString())).join(',')}]`;
+  return [
+    RevealKind.Json,
+    `{"prices":${encodedPrices},"decimals":${JSON.stringify(decimals)},"observed_at":${observedAt}}`,
+  ];
+}
+
+function handleTallyVmResult(vmResult: VmResult, exitCode: number, prices: bigint[], ok: boolean[]) {
+  handleTupleTallyVmResult(vmResult, exitCode, [prices, ok], ['int256[]', 'bool[]']);
+}
+
 describe('evm price feed', () => {
   describe('execution phase', () => {
     it('should return the correct price', async () => {
       fetchMock.mockImplementation((url) => {
         return match(url.host)
           .with('api.binance.com', () => {
-            return new Response(JSON.stringify({ symbol: 'BTCUSDT', price: '117318.90000000' }));
+            return tickerResponse('BTCUSDT', '117318.90000000', 1753806883615);
           })
           .otherwise(() => {
             throw new Error(`Unexpected host: ${url.host}`);
@@ -33,13 +57,25 @@ describe('evm price feed', () => {
       });
 
       const oracleProgram = await file(WASM_PATH).arrayBuffer();
-      const tickers = ['BTC-USDT'];
-      const execInputsStr = ethers.AbiCoder.defaultAbiCoder().encode(['string[]'], [tickers]);
-      // Remove '0x' prefix
-      const execInputs = Buffer.from(execInputsStr.slice(2), 'hex');
+      const execInputs = encodeExecInputs([['BTC-USDT', 6]]);
 
       const vmResult = await testOracleProgramExecution(Buffer.from(oracleProgram), execInputs, fetchMock);
-      handleExecutionVmResult(vmResult, 0, [117318900000n]);
+      handleExecutionVmResult(vmResult, 0, { prices: [117318900000], decimals: [6], observed_at: 1753806883 });
+      expect(fetchMock.mock.calls.map(([url]) => url.toString())).toEqual([
+        'https://api.binance.com/api/v3/ticker/24hr?type=MINI&symbol=BTCUSDT',
+      ]);
+    });
+
+    it('percent-encodes the symbols in the query', async () => {
+      fetchMock.mockImplementation((_) => tickerResponse('BTCUSDT', '117318.90000000', 1753806883615));
+
+      const oracleProgram = await file(WASM_PATH).arrayBuffer();
+      const execInputs = encodeExecInputs([['BTC&x=1-US/DT', 6]]);
+
+      await testOracleProgramExecution(Buffer.from(oracleProgram), execInputs, fetchMock);
+      expect(fetchMock.mock.calls.map(([url]) => url.toString())).toEqual([
+        'https://api.binance.com/api/v3/ticker/24hr?type=MINI&symbol=BTC%26x%3D1US%2FDT',
+      ]);
     });
 
     it('should work with 2 prices', async () => {
@@ -48,10 +84,10 @@ describe('evm price feed', () => {
           .with('api.binance.com', () => {
             // Check searchParams separately since it's a URLSearchParams object
             if (url.searchParams.get('symbol') === 'BTCUSDT') {
-              return new Response(JSON.stringify({ symbol: 'BTCUSDT', price: '117318.90000000' }));
+              return tickerResponse('BTCUSDT', '117318.90000000', 1753806883615);
             }
             if (url.searchParams.get('symbol') === 'ETHUSDT') {
-              return new Response(JSON.stringify({ symbol: 'ETHUSDT', price: '3844' }));
+              return tickerResponse('ETHUSDT', '3844', 1753806880001);
             }
             return new Response(JSON.stringify({ error: 'Missing symbol parameter' }), { status: 400 });
           })
@@ -61,12 +97,107 @@ describe('evm price feed', () => {
       });
 
       const oracleProgram = await file(WASM_PATH).arrayBuffer();
-      const tickers = ['BTC-USDT', 'ETH-USDT'];
-      const execInputsStr = ethers.AbiCoder.defaultAbiCoder().encode(['string[]'], [tickers]);
-      const execInputs = Buffer.from(execInputsStr.slice(2), 'hex');
+      const execInputs = encodeExecInputs([
+        ['BTC-USDT', 6],
+        ['ETH-USDT', 6],
+      ]);
+
+      const vmResult = await testOracleProgramExecution(Buffer.from(oracleProgram), execInputs, fetchMock);
+      // The batch is observed at the oldest of its prices
+      handleExecutionVmResult(vmResult, 0, {
+        prices: [117318900000, 3844000000],
+        decimals: [6, 6],
+        observed_at: 1753806880,
+      });
+    });
+
+    it('should use the requested decimals per pair', async () => {
+      fetchMock.mockImplementation((url) => {
+        return match(url.host)
+          .with('api.binance.com', () => {
+            if (url.searchParams.get('symbol') === 'BTCUSDT') {
+              return tickerResponse('BTCUSDT', '117318.90000000', 1753806883615);
+            }
+            if (url.searchParams.get('symbol') === 'PEPEUSDT') {
+              return tickerResponse('PEPEUSDT', '0.00001052', 1753806883615);
+            }
+            return new Response(JSON.stringify({ error: 'Missing symbol parameter' }), { status: 400 });
+          })
+          .otherwise(() => {
+            throw new Error(`Unexpected host: ${url.host}`);
+          });
+      });
+
+      const oracleProgram = await file(WASM_PATH).arrayBuffer();
+      const execInputs = encodeExecInputs([
+        ['BTC-USDT', 2],
+        ['PEPE-USDT', 18],
+      ]);
+
+      const vmResult = await testOracleProgramExecution(Buffer.from(oracleProgram), execInputs, fetchMock);
+      handleExecutionVmResult(vmResult, 0, {
+        prices: [11731890, 10520000000000],
+        decimals: [2, 18],
+        observed_at: 1753806883,
+      });
+    });
+
+    it('should parse negative values', async () => {
+      fetchMock.mockImplementation((url) => {
+        return match(url.host)
+          .with('api.binance.com', () => {
+            return tickerResponse('BTCUSDT', '-0.00012500', 1753806883615);
+          })
+          .otherwise(() => {
+            throw new Error(`Unexpected host: ${url.host}`);
+          });
+      });
+
+      const oracleProgram = await file(WASM_PATH).arrayBuffer();
+      const execInputs = encodeExecInputs([['BTC-USDT', 8]]);
+
+      const vmResult = await testOracleProgramExecution(Buffer.from(oracleProgram), execInputs, fetchMock);
+      handleExecutionVmResult(vmResult, 0, { prices: [-12500], decimals: [8], observed_at: 1753806883 });
+    });
+
+    it('should reject decimals that do not fit a uint8', async () => {
+      // Encoded as a uint256, which shares the uint8 layout, since ethers refuses to encode 256 as a uint8.
+      const execInputs = ethers.AbiCoder.defaultAbiCoder().encode(['(string,uint256)[]'], [[['BTC-USDT', 256]]]);
+
+      const oracleProgram = await file(WASM_PATH).arrayBuffer();
+      const vmResult = await testOracleProgramExecution(
+        Buffer.from(oracleProgram),
+        Buffer.from(execInputs.slice(2), 'hex'),
+        fetchMock,
+      );
+
+      expect(vmResult.exitCode).toBe(1);
+      expect(Buffer.from(vmResult.result).toString()).toBe('Invalid decimals for BTC-USDT');
+      expect(fetchMock).not.toHaveBeenCalled();
+    });
+
+    it('should report a failed pair without dropping the others', async () => {
+      fetchMock.mockImplementation((url) => {
+        return match(url.host)
+          .with('api.binance.com', () => {
+            if (url.searchParams.get('symbol') === 'BTCUSDT') {
+              return tickerResponse('BTCUSDT', '117318.90000000', 1753806883615);
+            }
+            return new Response(JSON.stringify({ code: -1121, msg: 'Invalid symbol.' }), { status: 400 });
+          })
+          .otherwise(() => {
+            throw new Error(`Unexpected host: ${url.host}`);
+          });
+      });
+
+      const oracleProgram = await file(WASM_PATH).arrayBuffer();
+      const execInputs = encodeExecInputs([
+        ['BTC-USDT', 6],
+        ['LUNA-USDT', 6],
+      ]);
 
       const vmResult = await testOracleProgramExecution(Buffer.from(oracleProgram), execInputs, fetchMock);
-      handleExecutionVmResult(vmResult, 0, [117318900000n, 3844000000n]);
+      handleExecutionVmResult(vmResult, 0, { prices: [117318900000, null], decimals: [6, 6], observed_at: 1753806883 });
     });
 
     it('should not work if there are no prices', async () => {
@@ -89,56 +220,167 @@ describe('evm price feed', () => {
   describe('tally phase', () => {
     it('works with 1 price', async () => {
       const oracleProgram = await file(WASM_PATH).arrayBuffer();
-      const vmResult = await testOracleProgramTally(Buffer.from(oracleProgram), Buffer.from('tally-inputs'), [
-        createSuccessfulReveal([100n]),
-      ]);
-      handleTallyVmResult(vmResult, 0, [100n]);
+      const vmResult = await testOracleProgramTally(
+        Buffer.from(oracleProgram),
+        Buffer.from('tally-inputs'),
+        createRevealArray([priceReveal([100n], [6])]),
+      );
+      handleTallyVmResult(vmResult, 0, [100n], [true]);
     });
 
     it('works with 2 prices', async () => {
       const oracleProgram = await file(WASM_PATH).arrayBuffer();
-      const vmResult = await testOracleProgramTally(Buffer.from(oracleProgram), Buffer.from('tally-inputs'), [
-        createSuccessfulReveal([0n, 100n]),
-        createSuccessfulReveal([0n, 200n]),
-      ]);
-      handleTallyVmResult(vmResult, 0, [0n, 150n]);
+      const vmResult = await testOracleProgramTally(
+        Buffer.from(oracleProgram),
+        Buffer.from('tally-inputs'),
+        createRevealArray([priceReveal([0n, 100n], [6, 6]), priceReveal([0n, 200n], [6, 6])]),
+      );
+      handleTallyVmResult(vmResult, 0, [0n, 150n], [true, true]);
     });
 
     it('works with 5 prices', async () => {
       const oracleProgram = await file(WASM_PATH).arrayBuffer();
-      const vmResult = await testOracleProgramTally(Buffer.from(oracleProgram), Buffer.from('tally-inputs'), [
-        createSuccessfulReveal([100n, 0n, 0n, 0n, 0n]),
-        createSuccessfulReveal([300n, 0n, 0n, 0n, 0n]),
-        createSuccessfulReveal([200n, 0n, 0n, 0n, 0n]),
-        createSuccessfulReveal([500n, 0n, 0n, 0n, 0n]),
-        createSuccessfulReveal([400n, 0n, 0n, 0n, 0n]),
-      ]);
-      handleTallyVmResult(vmResult, 0, [300n, 0n, 0n, 0n, 0n]);
+      const decimals = [6, 6, 6, 6, 6];
+      const vmResult = await testOracleProgramTally(
+        Buffer.from(oracleProgram),
+        Buffer.from('tally-inputs'),
+        createRevealArray([
+          priceReveal([100n, 0n, 0n, 0n, 0n], decimals),
+          priceReveal([300n, 0n, 0n, 0n, 0n], decimals),
+          priceReveal([200n, 0n, 0n, 0n, 0n], decimals),
+          priceReveal([500n, 0n, 0n, 0n, 0n], decimals),
+          priceReveal([400n, 0n, 0n, 0n, 0n], decimals),
+        ]),
+      );
+      handleTallyVmResult(vmResult, 0, [300n, 0n, 0n, 0n, 0n], [true, true, true, true, true]);
+    });
+
+    it('takes the median of the pairs that were fetched', async () => {
+      const oracleProgram = await file(WASM_PATH).arrayBuffer();
+      const vmResult = await testOracleProgramTally(
+        Buffer.from(oracleProgram),
+        Buffer.from('tally-inputs'),
+        createRevealArray([
+          priceReveal([100n, null], [6, 6]),
+          priceReveal([null, 500n], [6, 6]),
+          priceReveal([200n, null], [6, 6]),
+        ]),
+      );
+      handleTallyVmResult(vmResult, 0, [150n, 500n], [true, true]);
+    });
+
+    it('flags pairs that no executor could fetch', async () => {
+      const oracleProgram = await file(WASM_PATH).arrayBuffer();
+      const vmResult = await testOracleProgramTally(
+        Buffer.from(oracleProgram),
+        Buffer.from('tally-inputs'),
+        createRevealArray([priceReveal([100n, null], [6, 6]), priceReveal([200n, null], [6, 6])]),
+      );
+      handleTallyVmResult(vmResult, 0, [150n, 0n], [true, false]);
+    });
+
+    it('keeps the decimals most reveals agree on', async () => {
+      const oracleProgram = await file(WASM_PATH).arrayBuffer();
+      const vmResult = await testOracleProgramTally(
+        Buffer.from(oracleProgram),
+        Buffer.from('tally-inputs'),
+        createRevealArray([
+          priceReveal([999n], [18]),
+          priceReveal([100n], [6]),
+          priceReveal([200n], [6]),
+          priceReveal([300n], [6]),
+        ]),
+      );
+      handleTallyVmResult(vmResult, 0, [200n], [true]);
+    });
+
+    it('ignores reveals with a different number of prices than decimals', async () => {
+      const oracleProgram = await file(WASM_PATH).arrayBuffer();
+      const vmResult = await testOracleProgramTally(
+        Buffer.from(oracleProgram),
+        Buffer.from('tally-inputs'),
+        createRevealArray([
+          priceReveal([999n], [6, 6]),
+          priceReveal([100n, 1000n], [6, 6]),
+          priceReveal([999n, 999n, 999n], [6, 6]),
+          priceReveal([300n, 3000n], [6, 6]),
+        ]),
+      );
+      handleTallyVmResult(vmResult, 0, [200n, 2000n], [true, true]);
+    });
+
+    it("encodes negative values as two's complement", async () => {
+      const oracleProgram = await file(WASM_PATH).arrayBuffer();
+      const vmResult = await testOracleProgramTally(
+        Buffer.from(oracleProgram),
+        Buffer.from('tally-inputs'),
+        createRevealArray([
+          priceReveal([-100n, 5n], [8, 8]),
+          priceReveal([-300n, -5n], [8, 8]),
+          priceReveal([-200n, -15n], [8, 8]),
+        ]),
+      );
+      handleTallyVmResult(vmResult, 0, [-200n, -5n], [true, true]);
     });
 
     it('should ignore multiple errored executions', async () => {
       const oracleProgram = await file(WASM_PATH).arrayBuffer();
 
-      const vmResult = await testOracleProgramTally(Buffer.from(oracleProgram), Buffer.from('tally-inputs'), [
-        createSuccessfulReveal([100n]),
-        createFailedReveal(),
-        createSuccessfulReveal([200n]),
-        createFailedReveal(),
-        createSuccessfulReveal([300n]),
-      ]);
+      const vmResult = await testOracleProgramTally(
+        Buffer.from(oracleProgram),
+        Buffer.from('tally-inputs'),
+        createRevealArray([
+          priceReveal([100n], [6]),
+          [RevealKind.Failed],
+          priceReveal([200n], [6]),
+          [RevealKind.Failed],
+          priceReveal([300n], [6]),
+        ]),
+      );
 
-      handleTallyVmResult(vmResult, 0, [200n]);
+      handleTallyVmResult(vmResult, 0, [200n], [true]);
     });
 
     it('should error if all executions errored', async () => {
       const oracleProgram = await file(WASM_PATH).arrayBuffer();
-      const vmResult = await testOracleProgramTally(Buffer.from(oracleProgram), Buffer.from('tally-inputs'), [
-        createFailedReveal(),
-        createFailedReveal(),
-        createFailedReveal(),
-      ]);
+      const vmResult = await testOracleProgramTally(
+        Buffer.from(oracleProgram),
+        Buffer.from('tally-inputs'),
+        createRevealArray([[RevealKind.Failed], [RevealKind.Failed], [RevealKind.Failed]]),
+      );
+
+      handleTallyVmResult(vmResult, 1, [], []);
+    });
 
-      handleTallyVmResult(vmResult, 1, [0n]);
+    describe('timestamped layout', () => {
+      const codecs = ['int256[]', 'bool[]', 'uint64', 'uint8', 'uint16'];
+
+      it('returns the median observation time and the number of reveals', async () => {
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+        const vmResult = await testOracleProgramTally(
+          Buffer.from(oracleProgram),
+          Buffer.from('timestamped'),
+          createRevealArray([
+            priceReveal([100n, null], [8, 8], 1753806880),
+            priceReveal([200n, 400n], [8, 8], 1753806890),
+            [RevealKind.Failed],
+            priceReveal([300n, 600n], [8, 8], 1753806800),
+          ]),
+        );
+
+        handleTupleTallyVmResult(vmResult, 0, [[200n, 500n], [true, true], 1753806880n, 8n, 3n], codecs);
+      });
+
+      it('should error if the pairs use different decimals', async () => {
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+        const vmResult = await testOracleProgramTally(
+          Buffer.from(oracleProgram),
+          Buffer.from('timestamped'),
+          createRevealArray([priceReveal([100n, 200n], [6, 8])]),
+        );
+
+        handleTupleTallyVmResult(vmResult, 1, [], codecs);
+      });
     });
   });
 });
@@ -1,18 +1,77 @@
 // biome-ignore assist/source/organizeImports: biome is lying
 import { file } from 'bun';
-import { afterEach, describe, it, mock } from 'bun:test';
+import { afterEach, describe, expect, it, mock } from 'bun:test';
 import { testOracleProgramExecution, testOracleProgramTally } from '@seda-protocol/dev-tools';
 import {
   handleBigIntTallyVmResult as handleVmResult,
   handleBigIntExecutionVmResult as handleExecutionVmResult,
+  handleBigIntArrayExecutionVmResult,
+  handleBigIntArrayTallyVmResult,
+  handleTupleTallyVmResult,
+  handleJsonExecutionVmResult,
+  handleHttpFetchResponseExecutionVmResult,
   createRevealArray,
   RevealKind,
+  type RevealInput,
+  AbiCoder,
+  makeDataProxyResponse,
+  unexpectedDataProxy,
+  withUpperCasePublicKey,
 } from './utils.js';
+import type { HttpFetchResponseData } from '@seda-protocol/vm';
 
 const WASM_PATH = 'target/wasm32-wasip1/release/generic-dxfeed.wasm';
 
 const fetchMock = mock();
 
+const PROXY_URL = 'http://98.84.79.123:5384/proxy';
+
+const QUOTE_RESPONSE = JSON.stringify({
+  Quote: {
+    'XAU/USD:BFX': {
+      askExchangeCode: '',
+      askPrice: 3313.99,
+      askSize: 100,
+      askTime: 1753710744000,
+      bidExchangeCode: '',
+      bidPrice: 3313.83,
+      bidSize: 100,
+      bidTime: 1753710744000,
+      eventSymbol: 'XAU/USD:BFX',
+      eventTime: 0,
+      sequence: 0,
+      timeNanoPart: 0,
+    },
+  },
+  status: 'OK',
+});
+
+const TRADE_RESPONSE = JSON.stringify({
+  Trade: {
+    AAPL: {
+      change: 0,
+      dayId: 20297,
+      dayTurnover: 12405806.53,
+      dayVolume: 57773,
+      eventSymbol: 'AAPL',
+      eventTime: 0,
+      exchangeCode: 'V',
+      extendedTradingHours: true,
+      price: 213.89,
+      sequence: 1071,
+      size: 100,
+      tickDirection: 'ZERO_DOWN',
+      time: 1753473599903,
+      timeNanoPart: 0,
+    },
+  },
+  status: 'OK',
+});
+
+const QUOTE_BODY = JSON.parse(QUOTE_RESPONSE);
+
+const TRADE_BODY = JSON.parse(TRADE_RESPONSE);
+
 afterEach(() => {
   fetchMock.mockRestore();
 });
@@ -20,35 +79,13 @@ afterEach(() => {
 describe('single commodity price', () => {
   describe('execution phase', () => {
     it('commodity works', async () => {
-      fetchMock.mockImplementation((_) => {
-        return new Response(
-          JSON.stringify({
-            Quote: {
-              'XAU/USD:BFX': {
-                askExchangeCode: '',
-                askPrice: 3313.99,
-                askSize: 100,
-                askTime: 1753710744000,
-                bidExchangeCode: '',
-                bidPrice: 3313.83,
-                bidSize: 100,
-                bidTime: 1753710744000,
-                eventSymbol: 'XAU/USD:BFX',
-                eventTime: 0,
-                sequence: 0,
-                timeNanoPart: 0,
-              },
-            },
-            status: 'OK',
-          }),
-        );
-      });
+      fetchMock.mockImplementation((_) => new Response(QUOTE_RESPONSE));
 
       const oracleProgram = await file(WASM_PATH).arrayBuffer();
 
       const vmResult = await testOracleProgramExecution(
         Buffer.from(oracleProgram),
-        Buffer.from('fx/XAU/USD'),
+        Buffer.from('cfd/XAU/USD'),
         fetchMock,
         undefined,
         undefined,
@@ -56,33 +93,34 @@ describe('single commodity price', () => {
         0n,
       );
 
-      handleExecutionVmResult(vmResult, 0, 331399n);
+      handleBigIntArrayExecutionVmResult(vmResult, 0, [1n, 1n, 2n, 331399n, 1753710744000n]);
+      expect(fetchMock.mock.calls.map(([url]) => url.toString())).toEqual([
+        'http://98.84.79.123:5384/proxy/cfd/XAU/USD',
+      ]);
+    });
+
+    it('percent-encodes every part of the symbol in the path', async () => {
+      fetchMock.mockImplementation((_) => new Response(TRADE_RESPONSE));
+
+      const oracleProgram = await file(WASM_PATH).arrayBuffer();
+
+      await testOracleProgramExecution(
+        Buffer.from(oracleProgram),
+        Buffer.from('equity/BRK B?x=1&y/A%'),
+        fetchMock,
+        undefined,
+        undefined,
+        undefined,
+        0n,
+      );
+
+      expect(fetchMock.mock.calls.map(([url]) => url.toString())).toEqual([
+        'http://98.84.79.123:5384/proxy/equity/BRK%20B%3Fx%3D1%26y/A%25',
+      ]);
     });
 
     it('equity works', async () => {
-      fetchMock.mockImplementation((_) => {
-        return new Response(
-          JSON.stringify({
-            Quote: {
-              'AAPL:USLF24': {
-                askExchangeCode: 'U',
-                askPrice: 214.44,
-                askSize: 123,
-                askTime: 1753707742000,
-                bidExchangeCode: 'U',
-                bidPrice: 214.2,
-                bidSize: 157,
-                bidTime: 1753707657000,
-                eventSymbol: 'AAPL:USLF24',
-                eventTime: 0,
-                sequence: 0,
-                timeNanoPart: 0,
-              },
-            },
-            status: 'OK',
-          }),
-        );
-      });
+      fetchMock.mockImplementation((_) => new Response(TRADE_RESPONSE));
 
       const oracleProgram = await file(WASM_PATH).arrayBuffer();
 
@@ -96,7 +134,240 @@ describe('single commodity price', () => {
         0n,
       );
 
-      handleExecutionVmResult(vmResult, 0, 21444n);
+      handleBigIntArrayExecutionVmResult(vmResult, 0, [1n, 3n, 2n, 21389n, 1753473599903n]);
+      expect(fetchMock.mock.calls.map(([url]) => url.toString())).toEqual([
+        'http://98.84.79.123:5384/proxy/equity/AAPL',
+      ]);
+    });
+
+    it('reveals the requested quote fields in order', async () => {
+      fetchMock.mockImplementation((_) => new Response(QUOTE_RESPONSE));
+
+      const oracleProgram = await file(WASM_PATH).arrayBuffer();
+
+      const vmResult = await testOracleProgramExecution(
+        Buffer.from(oracleProgram),
+        Buffer.from('cfd/XAU/USD-bid,ask,mid'),
+        fetchMock,
+        undefined,
+        undefined,
+        undefined,
+        0n,
+      );
+
+      handleBigIntArrayExecutionVmResult(vmResult, 0, [3n, 0n, 1n, 2n, 2n, 331383n, 331399n, 331391n, 1753710744000n]);
+      expect(fetchMock.mock.calls.map(([url]) => url.toString())).toEqual([
+        'http://98.84.79.123:5384/proxy/cfd/XAU/USD',
+      ]);
+    });
+
+    it('reveals the last trade price', async () => {
+      fetchMock.mockImplementation((_) => new Response(TRADE_RESPONSE));
+
+      const oracleProgram = await file(WASM_PATH).arrayBuffer();
+
+      const vmResult = await testOracleProgramExecution(
+        Buffer.from(oracleProgram),
+        Buffer.from('equity/AAPL-last'),
+        fetchMock,
+        undefined,
+        undefined,
+        undefined,
+        0n,
+      );
+
+      handleBigIntArrayExecutionVmResult(vmResult, 0, [1n, 3n, 2n, 21389n, 1753473599903n]);
+    });
+
+    it('rejects quote fields for trade events', async () => {
+      const oracleProgram = await file(WASM_PATH).arrayBuffer();
+
+      const vmResult = await testOracleProgramExecution(
+        Buffer.from(oracleProgram),
+        Buffer.from('equity/AAPL-last,bid,mid'),
+        fetchMock,
+        undefined,
+        undefined,
+        undefined,
+        0n,
+      );
+
+      handleExecutionVmResult(vmResult, 1, 0n);
+      expect(Buffer.from(vmResult.result).toString()).toBe('Invalid fields: ["bid", "mid"]');
+      expect(fetchMock).not.toHaveBeenCalled();
+    });
+
+    it('rejects the last trade price for quote events', async () => {
+      const oracleProgram = await file(WASM_PATH).arrayBuffer();
+
+      const vmResult = await testOracleProgramExecution(
+        Buffer.from(oracleProgram),
+        Buffer.from('fx/EUR-last'),
+        fetchMock,
+        undefined,
+        undefined,
+        undefined,
+        0n,
+      );
+
+      handleExecutionVmResult(vmResult, 1, 0n);
+      expect(Buffer.from(vmResult.result).toString()).toBe('Invalid fields: ["last"]');
+      expect(fetchMock).not.toHaveBeenCalled();
+    });
+
+    it('reveals every symbol of a batch in input order', async () => {
+      fetchMock.mockImplementation((url) =>
+        url.toString().includes('/equity/') ? new Response(TRADE_RESPONSE) : new Response(QUOTE_RESPONSE),
+      );
+
+      const oracleProgram = await file(WASM_PATH).arrayBuffer();
+
+      const vmResult = await testOracleProgramExecution(
+        Buffer.from(oracleProgram),
+        Buffer.from('equity/AAPL,cfd/XAU/USD:4'),
+        fetchMock,
+        undefined,
+        undefined,
+        undefined,
+        0n,
+      );
+
+      handleBigIntArrayExecutionVmResult(vmResult, 0, [
+        1n,
+        3n,
+        2n,
+        21389n,
+        1753473599903n,
+        1n,
+        1n,
+        4n,
+        // The float scaled price is truncated, like the other dxFeed programs do.
+        33139899n,
+        1753710744000n,
+      ]);
+      expect(fetchMock.mock.calls.map(([url]) => url.toString())).toEqual([
+        'http://98.84.79.123:5384/proxy/equity/AAPL',
+        'http://98.84.79.123:5384/proxy/cfd/XAU/USD',
+      ]);
+    });
+
+    it('rejects invalid decimals', async () => {
+      const oracleProgram = await file(WASM_PATH).arrayBuffer();
+
+      const vmResult = await testOracleProgramExecution(
+        Buffer.from(oracleProgram),
+        Buffer.from('equity/AAPL,cfd/XAU/USD:19'),
+        fetchMock,
+        undefined,
+        undefined,
+        undefined,
+        0n,
+      );
+
+      handleExecutionVmResult(vmResult, 1, 0n);
+      expect(Buffer.from(vmResult.result).toString()).toBe('Invalid decimals');
+      expect(fetchMock).not.toHaveBeenCalled();
+    });
+
+    it('rejects fields missing from any symbol of a batch', async () => {
+      const oracleProgram = await file(WASM_PATH).arrayBuffer();
+
+      const vmResult = await testOracleProgramExecution(
+        Buffer.from(oracleProgram),
+        Buffer.from('cfd/XAU/USD,equity/AAPL-bid'),
+        fetchMock,
+        undefined,
+        undefined,
+        undefined,
+        0n,
+      );
+
+      handleExecutionVmResult(vmResult, 1, 0n);
+      expect(Buffer.from(vmResult.result).toString()).toBe('Invalid fields: ["bid"]');
+      expect(fetchMock).not.toHaveBeenCalled();
+    });
+
+    it('fails the batch if any symbol fails', async () => {
+      fetchMock.mockImplementation((url) =>
+        url.toString().includes('/equity/') ? new Response('Not found', { status: 404 }) : new Response(QUOTE_RESPONSE),
+      );
+
+      const oracleProgram = await file(WASM_PATH).arrayBuffer();
+
+      const vmResult = await testOracleProgramExecution(
+        Buffer.from(oracleProgram),
+        Buffer.from('cfd/XAU/USD,equity/AAPL'),
+        fetchMock,
+        undefined,
+        undefined,
+        undefined,
+        0n,
+      );
+
+      handleExecutionVmResult(vmResult, 1, 0n);
+      expect(Buffer.from(vmResult.result).toString()).toBe('Error while fetching commodity price');
+    });
+
+    it('reveals the session of the last trade', async () => {
+      fetchMock.mockImplementation((_) => new Response(TRADE_RESPONSE));
+
+      const oracleProgram = await file(WASM_PATH).arrayBuffer();
+
+      const vmResult = await testOracleProgramExecution(
+        Buffer.from(oracleProgram),
+        Buffer.from('equity/AAPL-session'),
+        fetchMock,
+        undefined,
+        undefined,
+        undefined,
+        0n,
+      );
+
+      handleJsonExecutionVmResult(vmResult, 0, {
+        decimals: 2,
+        price: 21389,
+        time: 1753473599903,
+        extendedHours: true,
+        dayVolume: 57773,
+      });
+    });
+
+    it('rejects the session for quote events', async () => {
+      const oracleProgram = await file(WASM_PATH).arrayBuffer();
+
+      const vmResult = await testOracleProgramExecution(
+        Buffer.from(oracleProgram),
+        Buffer.from('cfd/XAU/USD-session'),
+        fetchMock,
+        undefined,
+        undefined,
+        undefined,
+        0n,
+      );
+
+      handleExecutionVmResult(vmResult, 1, 0n);
+      expect(Buffer.from(vmResult.result).toString()).toBe('Invalid fields: ["session"]');
+      expect(fetchMock).not.toHaveBeenCalled();
+    });
+
+    it('rejects the session along with other fields or symbols', async () => {
+      const oracleProgram = await file(WASM_PATH).arrayBuffer();
+
+      for (const input of ['equity/AAPL-last,session', 'equity/AAPL,equity/TSLA-session']) {
+        const vmResult = await testOracleProgramExecution(
+          Buffer.from(oracleProgram),
+          Buffer.from(input),
+          fetchMock,
+          undefined,
+          undefined,
+          undefined,
+          0n,
+        );
+
+        handleExecutionVmResult(vmResult, 1, 0n);
+        expect(Buffer.from(vmResult.result).toString()).toBe('Invalid input format');
+      }
+      expect(fetchMock).not.toHaveBeenCalled();
     });
   });
 
@@ -106,7 +377,7 @@ describe('single commodity price', () => {
       const vmResult = await testOracleProgramTally(
         Buffer.from(oracleProgram),
         Buffer.from('tally-inputs'),
-        createRevealArray([[RevealKind.BigInt, 100n]]),
+        createRevealArray([[RevealKind.BigIntArray, [1n, 1n, 2n, 100n, 1753710744000n]]]),
       );
       handleVmResult(vmResult, 0, 100n);
     });
@@ -117,8 +388,8 @@ describe('single commodity price', () => {
         Buffer.from(oracleProgram),
         Buffer.from('tally-inputs'),
         createRevealArray([
-          [RevealKind.BigInt, 100n],
-          [RevealKind.BigInt, 200n],
+          [RevealKind.BigIntArray, [1n, 1n, 2n, 100n, 1753710744000n]],
+          [RevealKind.BigIntArray, [1n, 1n, 2n, 200n, 1753710744000n]],
         ]),
       );
       handleVmResult(vmResult, 0, 150n);
@@ -130,11 +401,11 @@ describe('single commodity price', () => {
         Buffer.from(oracleProgram),
         Buffer.from('tally-inputs'),
         createRevealArray([
-          [RevealKind.BigInt, 100n],
-          [RevealKind.BigInt, 200n],
-          [RevealKind.BigInt, 300n],
-          [RevealKind.BigInt, 400n],
-          [RevealKind.BigInt, 500n],
+          [RevealKind.BigIntArray, [1n, 1n, 2n, 100n, 1753710744000n]],
+          [RevealKind.BigIntArray, [1n, 1n, 2n, 200n, 1753710744000n]],
+          [RevealKind.BigIntArray, [1n, 1n, 2n, 300n, 1753710744000n]],
+          [RevealKind.BigIntArray, [1n, 1n, 2n, 400n, 1753710744000n]],
+          [RevealKind.BigIntArray, [1n, 1n, 2n, 500n, 1753710744000n]],
         ]),
       );
       handleVmResult(vmResult, 0, 300n);
@@ -146,16 +417,16 @@ describe('single commodity price', () => {
         Buffer.from(oracleProgram),
         Buffer.from('tally-inputs'),
         createRevealArray([
-          [RevealKind.BigInt, 100n],
-          [RevealKind.BigInt, 200n],
-          [RevealKind.BigInt, 300n],
-          [RevealKind.BigInt, 400n],
-          [RevealKind.BigInt, 500n],
-          [RevealKind.BigInt, 600n],
-          [RevealKind.BigInt, 700n],
-          [RevealKind.BigInt, 800n],
-          [RevealKind.BigInt, 900n],
-          [RevealKind.BigInt, 1000n],
+          [RevealKind.BigIntArray, [1n, 1n, 2n, 100n, 1753710744000n]],
+          [RevealKind.BigIntArray, [1n, 1n, 2n, 200n, 1753710744000n]],
+          [RevealKind.BigIntArray, [1n, 1n, 2n, 300n, 1753710744000n]],
+          [RevealKind.BigIntArray, [1n, 1n, 2n, 400n, 1753710744000n]],
+          [RevealKind.BigIntArray, [1n, 1n, 2n, 500n, 1753710744000n]],
+          [RevealKind.BigIntArray, [1n, 1n, 2n, 600n, 1753710744000n]],
+          [RevealKind.BigIntArray, [1n, 1n, 2n, 700n, 1753710744000n]],
+          [RevealKind.BigIntArray, [1n, 1n, 2n, 800n, 1753710744000n]],
+          [RevealKind.BigIntArray, [1n, 1n, 2n, 900n, 1753710744000n]],
+          [RevealKind.BigIntArray, [1n, 1n, 2n, 1000n, 1753710744000n]],
         ]),
       );
       handleVmResult(vmResult, 0, 550n);
@@ -167,17 +438,300 @@ describe('single commodity price', () => {
         Buffer.from(oracleProgram),
         Buffer.from('tally-inputs'),
         createRevealArray([
-          [RevealKind.BigInt, 500n],
-          [RevealKind.BigInt, 100n],
-          [RevealKind.BigInt, 300n],
-          [RevealKind.BigInt, 200n],
-          [RevealKind.BigInt, 200n],
-          [RevealKind.BigInt, 400n],
+          [RevealKind.BigIntArray, [1n, 1n, 2n, 500n, 1753710744000n]],
+          [RevealKind.BigIntArray, [1n, 1n, 2n, 100n, 1753710744000n]],
+          [RevealKind.BigIntArray, [1n, 1n, 2n, 300n, 1753710744000n]],
+          [RevealKind.BigIntArray, [1n, 1n, 2n, 200n, 1753710744000n]],
+          [RevealKind.BigIntArray, [1n, 1n, 2n, 200n, 1753710744000n]],
+          [RevealKind.BigIntArray, [1n, 1n, 2n, 400n, 1753710744000n]],
         ]),
       );
       handleVmResult(vmResult, 0, 250n);
     });
 
+    describe('price fields', () => {
+      it('returns the median of every field as a uint256[]', async () => {
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+        const vmResult = await testOracleProgramTally(
+          Buffer.from(oracleProgram),
+          Buffer.from('tally-inputs'),
+          createRevealArray([
+            [RevealKind.BigIntArray, [3n, 0n, 1n, 2n, 2n, 100n, 110n, 105n, 1753710744000n]],
+            [RevealKind.BigIntArray, [3n, 0n, 1n, 2n, 2n, 200n, 220n, 210n, 1753710744000n]],
+            [RevealKind.BigIntArray, [3n, 0n, 1n, 2n, 2n, 300n, 330n, 315n, 1753710744000n]],
+          ]),
+        );
+        handleBigIntArrayTallyVmResult(vmResult, 0, [200n, 220n, 210n]);
+      });
+
+      it('ignores the reveals with different fields', async () => {
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+        const vmResult = await testOracleProgramTally(
+          Buffer.from(oracleProgram),
+          Buffer.from('tally-inputs'),
+          createRevealArray([
+            [RevealKind.BigIntArray, [2n, 0n, 1n, 2n, 100n, 110n, 1753710744000n]],
+            [RevealKind.BigIntArray, [2n, 1n, 0n, 2n, 900n, 900n, 1753710744000n]],
+            [RevealKind.BigIntArray, [1n, 0n, 2n, 900n, 1753710744000n]],
+            [RevealKind.BigIntArray, [2n, 0n, 1n, 2n, 200n, 220n, 1753710744000n]],
+          ]),
+        );
+        handleBigIntArrayTallyVmResult(vmResult, 0, [150n, 165n]);
+      });
+
+      it('keeps the fields most reveals agree on when the first reveal is the outlier', async () => {
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+        const vmResult = await testOracleProgramTally(
+          Buffer.from(oracleProgram),
+          Buffer.from('tally-inputs'),
+          createRevealArray([
+            [RevealKind.BigIntArray, [1n, 1n, 2n, 900n, 1753710744000n]],
+            [RevealKind.BigIntArray, [2n, 0n, 1n, 2n, 100n, 110n, 1753710744000n]],
+            [RevealKind.BigIntArray, [2n, 0n, 1n, 2n, 200n, 220n, 1753710744000n]],
+          ]),
+        );
+        handleBigIntArrayTallyVmResult(vmResult, 0, [150n, 165n]);
+      });
+
+      it('ignores malformed reveals', async () => {
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+        const vmResult = await testOracleProgramTally(
+          Buffer.from(oracleProgram),
+          Buffer.from('tally-inputs'),
+          createRevealArray([
+            [RevealKind.BigInt, 100n],
+            [RevealKind.BigIntArray, [1n, 4n, 2n, 900n, 1753710744000n]],
+            [RevealKind.BigIntArray, [2n, 0n, 1n, 900n, 1753710744000n]],
+            [RevealKind.BigIntArray, [1n, 1n, 2n, 200n, 1753710744000n]],
+          ]),
+        );
+        handleVmResult(vmResult, 0, 200n);
+      });
+    });
+
+    describe('batches', () => {
+      it('returns the median of every symbol along with their decimals', async () => {
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+        const vmResult = await testOracleProgramTally(
+          Buffer.from(oracleProgram),
+          Buffer.from('tally-inputs'),
+          createRevealArray([
+            [RevealKind.BigIntArray, [1n, 3n, 2n, 100n, 1753710744000n, 2n, 0n, 1n, 4n, 1000n, 1100n, 1753710744000n]],
+            [RevealKind.BigIntArray, [1n, 3n, 2n, 200n, 1753710744000n, 2n, 0n, 1n, 4n, 2000n, 2200n, 1753710744000n]],
+            [RevealKind.BigIntArray, [1n, 3n, 2n, 300n, 1753710744000n, 2n, 0n, 1n, 4n, 3000n, 3300n, 1753710744000n]],
+          ]),
+        );
+        expect(vmResult.exitCode).toBe(0);
+        const [prices, decimals] = AbiCoder.decode(['uint256[]', 'uint8[]'], vmResult.result);
+        expect([...prices]).toEqual([200n, 2000n, 2200n]);
+        expect([...decimals]).toEqual([2n, 4n]);
+      });
+
+      it('ignores the reveals with different symbols', async () => {
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+        const vmResult = await testOracleProgramTally(
+          Buffer.from(oracleProgram),
+          Buffer.from('tally-inputs'),
+          createRevealArray([
+            [RevealKind.BigIntArray, [1n, 3n, 2n, 100n, 1753710744000n, 1n, 1n, 2n, 1000n, 1753710744000n]],
+            [RevealKind.BigIntArray, [1n, 3n, 2n, 900n, 1753710744000n]],
+            [RevealKind.BigIntArray, [1n, 3n, 2n, 900n, 1753710744000n, 1n, 1n, 4n, 900n, 1753710744000n]],
+            [RevealKind.BigIntArray, [1n, 3n, 2n, 200n, 1753710744000n, 1n, 1n, 2n, 2000n, 1753710744000n]],
+          ]),
+        );
+        expect(vmResult.exitCode).toBe(0);
+        const [prices, decimals] = AbiCoder.decode(['uint256[]', 'uint8[]'], vmResult.result);
+        expect([...prices]).toEqual([150n, 1500n]);
+        expect([...decimals]).toEqual([2n, 2n]);
+      });
+
+      it('keeps the symbols most reveals agree on when the first reveal is the outlier', async () => {
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+        const vmResult = await testOracleProgramTally(
+          Buffer.from(oracleProgram),
+          Buffer.from('tally-inputs'),
+          createRevealArray([
+            [RevealKind.BigIntArray, [1n, 3n, 4n, 900n, 1753710744000n, 1n, 1n, 2n, 900n, 1753710744000n]],
+            [RevealKind.BigIntArray, [1n, 3n, 2n, 100n, 1753710744000n, 1n, 1n, 2n, 1000n, 1753710744000n]],
+            [RevealKind.BigIntArray, [1n, 3n, 2n, 200n, 1753710744000n, 1n, 1n, 2n, 2000n, 1753710744000n]],
+          ]),
+        );
+        expect(vmResult.exitCode).toBe(0);
+        const [prices, decimals] = AbiCoder.decode(['uint256[]', 'uint8[]'], vmResult.result);
+        expect([...prices]).toEqual([150n, 1500n]);
+        expect([...decimals]).toEqual([2n, 2n]);
+      });
+
+      it('is as fresh as the oldest symbol of a reveal', async () => {
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+        const vmResult = await testOracleProgramTally(
+          Buffer.from(oracleProgram),
+          Buffer.from('maxAge=60000,now=1753710800000'),
+          createRevealArray([
+            [RevealKind.BigIntArray, [1n, 3n, 2n, 100n, 1753710790000n, 1n, 1n, 2n, 1000n, 1753710700000n]],
+            [RevealKind.BigIntArray, [1n, 3n, 2n, 200n, 1753710790000n, 1n, 1n, 2n, 2000n, 1753710790000n]],
+          ]),
+        );
+        expect(vmResult.exitCode).toBe(0);
+        const [prices] = AbiCoder.decode(['uint256[]', 'uint8[]'], vmResult.result);
+        expect([...prices]).toEqual([200n, 2000n]);
+      });
+    });
+
+    describe('session', () => {
+      const session = (price: number, time: number, extendedHours: boolean, dayVolume: number) =>
+        JSON.stringify({ decimals: 2, price, time, extendedHours, dayVolume });
+
+      it('returns the medians of the last trade along with the session flag', async () => {
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+        const vmResult = await testOracleProgramTally(
+          Buffer.from(oracleProgram),
+          Buffer.from('tally-inputs'),
+          createRevealArray([
+            [RevealKind.Json, session(21300, 1753473599000, false, 57000)],
+            [RevealKind.Json, session(21389, 1753473599903, false, 57773)],
+            [RevealKind.Json, session(21400, 1753473600000, false, 58000)],
+          ]),
+        );
+        handleTupleTallyVmResult(
+          vmResult,
+          0,
+          [21389n, 1753473599903n, false, 57773n],
+          ['uint256', 'uint64', 'bool', 'uint256'],
+        );
+      });
+
+      it('keeps the session decimals most reveals agree on when the first reveal is the outlier', async () => {
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+        const vmResult = await testOracleProgramTally(
+          Buffer.from(oracleProgram),
+          Buffer.from('tally-inputs'),
+          createRevealArray([
+            [RevealKind.Json, JSON.stringify({ decimals: 4, price: 1, time: 1, extendedHours: true, dayVolume: 1 })],
+            [RevealKind.Json, session(21389, 1753473599903, false, 57773)],
+            [RevealKind.Json, session(21389, 1753473599903, false, 57773)],
+          ]),
+        );
+        handleTupleTallyVmResult(
+          vmResult,
+          0,
+          [21389n, 1753473599903n, false, 57773n],
+          ['uint256', 'uint64', 'bool', 'uint256'],
+        );
+      });
+
+      it('keeps the sessions when the first reveal is made of prices', async () => {
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+        const vmResult = await testOracleProgramTally(
+          Buffer.from(oracleProgram),
+          Buffer.from('tally-inputs'),
+          createRevealArray([
+            [RevealKind.BigIntArray, [1n, 3n, 2n, 900n, 1753473599903n]],
+            [RevealKind.Json, session(21389, 1753473599903, false, 57773)],
+            [RevealKind.Json, session(21389, 1753473599903, false, 57773)],
+          ]),
+        );
+        handleTupleTallyVmResult(
+          vmResult,
+          0,
+          [21389n, 1753473599903n, false, 57773n],
+          ['uint256', 'uint64', 'bool', 'uint256'],
+        );
+      });
+
+      it('errors if the reveals disagree on the session flag', async () => {
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+        const vmResult = await testOracleProgramTally(
+          Buffer.from(oracleProgram),
+          Buffer.from('tally-inputs'),
+          createRevealArray([
+            [RevealKind.Json, session(21389, 1753473599903, true, 57773)],
+            [RevealKind.Json, session(21389, 1753473599903, false, 57773)],
+            [RevealKind.Json, session(21389, 1753473599903, true, 57773)],
+          ]),
+        );
+        handleVmResult(vmResult, 1, 0n);
+        expect(Buffer.from(vmResult.result).toString()).toBe('No consensus on the trading session');
+      });
+
+      it('only requires the fresh reveals to agree', async () => {
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+        const vmResult = await testOracleProgramTally(
+          Buffer.from(oracleProgram),
+          Buffer.from('maxAge=60000,now=1753473600000'),
+          createRevealArray([
+            [RevealKind.Json, session(21389, 1753473599903, true, 57773)],
+            [RevealKind.Json, session(21000, 1753300800000, false, 40000)],
+            [RevealKind.Json, session(21389, 1753473599903, true, 57773)],
+          ]),
+        );
+        handleTupleTallyVmResult(
+          vmResult,
+          0,
+          [21389n, 1753473599903n, true, 57773n],
+          ['uint256', 'uint64', 'bool', 'uint256'],
+        );
+      });
+    });
+
+    describe('freshness', () => {
+      it('discards the reveals older than the maximum age from the median time', async () => {
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+        const vmResult = await testOracleProgramTally(
+          Buffer.from(oracleProgram),
+          Buffer.from('maxAge=60000'),
+          createRevealArray([
+            [RevealKind.BigIntArray, [1n, 1n, 2n, 100n, 1753710744000n]],
+            [RevealKind.BigIntArray, [1n, 1n, 2n, 200n, 1753710744000n]],
+            [RevealKind.BigIntArray, [1n, 1n, 2n, 900n, 1753537944000n]],
+          ]),
+        );
+        handleVmResult(vmResult, 0, 150n);
+      });
+
+      it('measures the age from the reference time in the tally inputs', async () => {
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+        const vmResult = await testOracleProgramTally(
+          Buffer.from(oracleProgram),
+          Buffer.from('maxAge=60000,now=1753710800000'),
+          createRevealArray([
+            [RevealKind.BigIntArray, [1n, 1n, 2n, 100n, 1753710700000n]],
+            [RevealKind.BigIntArray, [1n, 1n, 2n, 200n, 1753710744000n]],
+            [RevealKind.BigIntArray, [1n, 1n, 2n, 300n, 1753710790000n]],
+          ]),
+        );
+        handleVmResult(vmResult, 0, 250n);
+      });
+
+      it('errors if every reveal is stale', async () => {
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+        const vmResult = await testOracleProgramTally(
+          Buffer.from(oracleProgram),
+          Buffer.from('maxAge=60000,now=1753970000000'),
+          createRevealArray([
+            [RevealKind.BigIntArray, [1n, 1n, 2n, 100n, 1753710744000n]],
+            [RevealKind.BigIntArray, [1n, 1n, 2n, 200n, 1753710744000n]],
+          ]),
+        );
+        handleVmResult(vmResult, 1, 0n);
+        expect(Buffer.from(vmResult.result).toString()).toBe('All revealed prices are stale');
+      });
+
+      it('reports the median observation time when timestamped', async () => {
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+        const vmResult = await testOracleProgramTally(
+          Buffer.from(oracleProgram),
+          Buffer.from('timestamped'),
+          createRevealArray([
+            [RevealKind.BigIntArray, [1n, 1n, 2n, 100n, 1753710700000n]],
+            [RevealKind.BigIntArray, [1n, 1n, 2n, 200n, 1753710744000n]],
+            [RevealKind.BigIntArray, [1n, 1n, 2n, 300n, 1753710790000n]],
+          ]),
+        );
+        handleTupleTallyVmResult(vmResult, 0, [200n, 1753710744000n], ['uint256', 'uint64']);
+      });
+    });
+
     describe('works with errored executions', () => {
       it('should ignore the errored execution', async () => {
         const oracleProgram = await file(WASM_PATH).arrayBuffer();
@@ -185,7 +739,11 @@ describe('single commodity price', () => {
         const vmResult = await testOracleProgramTally(
           Buffer.from(oracleProgram),
           Buffer.from('tally-inputs'),
-          createRevealArray([[RevealKind.BigInt, 100n], [RevealKind.Failed], [RevealKind.BigInt, 200n]]),
+          createRevealArray([
+            [RevealKind.BigIntArray, [1n, 1n, 2n, 100n, 1753710744000n]],
+            [RevealKind.Failed],
+            [RevealKind.BigIntArray, [1n, 1n, 2n, 200n, 1753710744000n]],
+          ]),
         );
 
         handleVmResult(vmResult, 0, 150n);
@@ -198,11 +756,11 @@ describe('single commodity price', () => {
           Buffer.from(oracleProgram),
           Buffer.from('tally-inputs'),
           createRevealArray([
-            [RevealKind.BigInt, 100n],
+            [RevealKind.BigIntArray, [1n, 1n, 2n, 100n, 1753710744000n]],
             [RevealKind.Failed],
-            [RevealKind.BigInt, 200n],
+            [RevealKind.BigIntArray, [1n, 1n, 2n, 200n, 1753710744000n]],
             [RevealKind.Failed],
-            [RevealKind.BigInt, 300n],
+            [RevealKind.BigIntArray, [1n, 1n, 2n, 300n, 1753710744000n]],
           ]),
         );
 
@@ -221,4 +779,346 @@ describe('single commodity price', () => {
       });
     });
   });
+
+  describe('verification', () => {
+    describe('execution phase', () => {
+      it('ships the proxy response of the symbol', async () => {
+        let expectedResponse: HttpFetchResponseData;
+        fetchMock.mockImplementation(async (url) => {
+          const response_info = await makeDataProxyResponse(url, QUOTE_BODY);
+          expectedResponse = response_info.dataProxyResponse;
+          return response_info.mockedResponse;
+        });
+
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+        const vmResult = await testOracleProgramExecution(
+          Buffer.from(oracleProgram),
+          Buffer.from('verify:cfd/XAU/USD-bid,ask'),
+          fetchMock,
+          undefined,
+          undefined,
+          undefined,
+          0n,
+        );
+
+        const verificationResponse = {
+          asset_type: 'cfd',
+          symbol: 'XAU/USD',
+          fields: ['bid', 'ask'],
+          decimals: 2,
+          response: expectedResponse,
+        };
+        handleHttpFetchResponseExecutionVmResult(vmResult, 0, verificationResponse, QUOTE_BODY);
+        expect(fetchMock.mock.calls.map(([url]) => url.toString())).toEqual([
+          'http://98.84.79.123:5384/proxy/cfd/XAU/USD',
+        ]);
+      });
+
+      it('rejects batches', async () => {
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+        const vmResult = await testOracleProgramExecution(
+          Buffer.from(oracleProgram),
+          Buffer.from('verify:equity/AAPL,cfd/XAU/USD'),
+          fetchMock,
+          undefined,
+          undefined,
+          undefined,
+          0n,
+        );
+
+        expect(vmResult.exitCode).toBe(1);
+        expect(Buffer.from(vmResult.result).toString()).toBe('Invalid input format');
+        expect(fetchMock).not.toHaveBeenCalled();
+      });
+    });
+
+    describe('tally phase', () => {
+      it('returns the verified quote fields', async () => {
+        const proxyResponse = await makeDataProxyResponse(`${PROXY_URL}/cfd/XAU/USD`, QUOTE_BODY);
+
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+        const vmResult = await testOracleProgramTally(
+          Buffer.from(oracleProgram),
+          Buffer.from('verify:symbol=cfd/XAU/USD,fields=bid;ask;mid'),
+          createRevealArray([
+            [
+              RevealKind.HttpFetchResponse,
+              {
+                asset_type: 'cfd',
+                symbol: 'XAU/USD',
+                fields: ['bid', 'ask', 'mid'],
+                decimals: 2,
+                response: proxyResponse.dataProxyResponse,
+              },
+            ],
+          ]),
+        );
+
+        handleBigIntArrayTallyVmResult(vmResult, 0, [331383n, 331399n, 331391n]);
+      });
+
+      it('takes the median over several verified reveals', async () => {
+        const proxyResponses = await Promise.all(
+          [3313.9, 3313.99, 3314.1].map((askPrice) =>
+            makeDataProxyResponse(`${PROXY_URL}/cfd/XAU/USD`, {
+              ...QUOTE_BODY,
+              Quote: { 'XAU/USD:BFX': { ...QUOTE_BODY.Quote['XAU/USD:BFX'], askPrice } },
+            }),
+          ),
+        );
+        // A response whose body was changed after the proxy signed it.
+        const tampered = { ...proxyResponses[0].dataProxyResponse, bytes: Array.from(Buffer.from('{}')) };
+
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+        const vmResult = await testOracleProgramTally(
+          Buffer.from(oracleProgram),
+          Buffer.from('verify:symbol=cfd/XAU/USD,fields=ask'),
+          createRevealArray([
+            ...[...proxyResponses.map((response) => response.dataProxyResponse), tampered].map(
+              (response): RevealInput => [
+                RevealKind.HttpFetchResponse,
+                { asset_type: 'cfd', symbol: 'XAU/USD', fields: ['ask'], decimals: 2, response },
+              ],
+            ),
+            [RevealKind.Failed],
+          ]),
+        );
+
+        handleVmResult(vmResult, 0, 331399n);
+      });
+
+      it('defaults to the first field of the asset type', async () => {
+        const proxyResponse = await makeDataProxyResponse(`${PROXY_URL}/cfd/XAU/USD`, QUOTE_BODY);
+
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+        const vmResult = await testOracleProgramTally(
+          Buffer.from(oracleProgram),
+          Buffer.from('verify:symbol=cfd/XAU/USD'),
+          createRevealArray([
+            [
+              RevealKind.HttpFetchResponse,
+              {
+                asset_type: 'cfd',
+                symbol: 'XAU/USD',
+                fields: ['bid'],
+                decimals: 2,
+                response: proxyResponse.dataProxyResponse,
+              },
+            ],
+          ]),
+        );
+
+        handleVmResult(vmResult, 0, 331383n);
+      });
+
+      it('returns the verified session of the last trade', async () => {
+        const proxyResponse = await makeDataProxyResponse(`${PROXY_URL}/equity/AAPL`, TRADE_BODY);
+
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+        const vmResult = await testOracleProgramTally(
+          Buffer.from(oracleProgram),
+          Buffer.from('verify:symbol=equity/AAPL,fields=session'),
+          createRevealArray([
+            [
+              RevealKind.HttpFetchResponse,
+              {
+                asset_type: 'equity',
+                symbol: 'AAPL',
+                fields: ['session'],
+                decimals: 2,
+                response: proxyResponse.dataProxyResponse,
+              },
+            ],
+          ]),
+        );
+
+        handleTupleTallyVmResult(
+          vmResult,
+          0,
+          [21389n, 1753473599903n, true, 57773n],
+          ['uint256', 'uint64', 'bool', 'uint256'],
+        );
+      });
+
+      it('discards responses for another URL than the requested symbol', async () => {
+        // A genuinely signed response, but for another symbol than the one of the tally inputs.
+        const proxyResponse = await makeDataProxyResponse(`${PROXY_URL}/cfd/XAG/USD`, QUOTE_BODY);
+
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+        const vmResult = await testOracleProgramTally(
+          Buffer.from(oracleProgram),
+          Buffer.from('verify:symbol=cfd/XAU/USD,fields=ask'),
+          createRevealArray([
+            [
+              RevealKind.HttpFetchResponse,
+              {
+                asset_type: 'cfd',
+                symbol: 'XAU/USD',
+                fields: ['ask'],
+                decimals: 2,
+                response: proxyResponse.dataProxyResponse,
+              },
+            ],
+          ]),
+        );
+
+        handleVmResult(vmResult, 1, 0n);
+        expect(Buffer.from(vmResult.result).toString()).toBe('No consensus among revealed results');
+      });
+
+      it('discards reveals for other fields or decimals than the requested ones', async () => {
+        const proxyResponse = await makeDataProxyResponse(`${PROXY_URL}/cfd/XAU/USD`, QUOTE_BODY);
+        const reveal = (fields: string[], decimals: number): RevealInput => [
+          RevealKind.HttpFetchResponse,
+          { asset_type: 'cfd', symbol: 'XAU/USD', fields, decimals, response: proxyResponse.dataProxyResponse },
+        ];
+
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+        const vmResult = await testOracleProgramTally(
+          Buffer.from(oracleProgram),
+          Buffer.from('verify:symbol=cfd/XAU/USD,fields=ask,decimals=4'),
+          createRevealArray([reveal(['bid'], 4), reveal(['ask'], 2), reveal(['ask', 'bid'], 4), reveal(['ask'], 4)]),
+        );
+
+        handleVmResult(vmResult, 0, 33139899n);
+      });
+
+      it('errors without a symbol in the tally inputs', async () => {
+        const proxyResponse = await makeDataProxyResponse(`${PROXY_URL}/cfd/XAU/USD`, QUOTE_BODY);
+
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+        const vmResult = await testOracleProgramTally(
+          Buffer.from(oracleProgram),
+          Buffer.from('verify:fields=ask'),
+          createRevealArray([
+            [
+              RevealKind.HttpFetchResponse,
+              {
+                asset_type: 'cfd',
+                symbol: 'XAU/USD',
+                fields: ['ask'],
+                decimals: 2,
+                response: proxyResponse.dataProxyResponse,
+              },
+            ],
+          ]),
+        );
+
+        handleVmResult(vmResult, 1, 0n);
+        expect(Buffer.from(vmResult.result).toString()).toBe('No symbol provided');
+      });
+
+      it('errors on an invalid request in the tally inputs', async () => {
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+        const vmResult = await testOracleProgramTally(
+          Buffer.from(oracleProgram),
+          Buffer.from('verify:symbol=cfd/XAU/USD,fields=session'),
+          createRevealArray([[RevealKind.Failed]]),
+        );
+
+        handleVmResult(vmResult, 1, 0n);
+        expect(Buffer.from(vmResult.result).toString()).toBe('Invalid tally inputs');
+      });
+
+      it('does not verify reveals without the verify marker in the tally inputs', async () => {
+        const proxyResponse = await makeDataProxyResponse(`${PROXY_URL}/cfd/XAU/USD`, QUOTE_BODY);
+
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+        const vmResult = await testOracleProgramTally(
+          Buffer.from(oracleProgram),
+          Buffer.from('symbol=cfd/XAU/USD,fields=ask'),
+          createRevealArray([
+            [
+              RevealKind.HttpFetchResponse,
+              {
+                asset_type: 'cfd',
+                symbol: 'XAU/USD',
+                fields: ['ask'],
+                decimals: 2,
+                response: proxyResponse.dataProxyResponse,
+              },
+            ],
+          ]),
+        );
+
+        handleVmResult(vmResult, 1, 0n);
+        expect(Buffer.from(vmResult.result).toString()).toBe('No consensus among revealed results');
+      });
+
+      it('errors if the prices are older than the maximum age', async () => {
+        const proxyResponse = await makeDataProxyResponse(`${PROXY_URL}/cfd/XAU/USD`, QUOTE_BODY);
+
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+        const vmResult = await testOracleProgramTally(
+          Buffer.from(oracleProgram),
+          Buffer.from('verify:symbol=cfd/XAU/USD,fields=ask,maxAge=60000,now=1753710900000'),
+          createRevealArray([
+            [
+              RevealKind.HttpFetchResponse,
+              {
+                asset_type: 'cfd',
+                symbol: 'XAU/USD',
+                fields: ['ask'],
+                decimals: 2,
+                response: proxyResponse.dataProxyResponse,
+              },
+            ],
+          ]),
+        );
+
+        handleVmResult(vmResult, 1, 0n);
+        expect(Buffer.from(vmResult.result).toString()).toBe('All revealed prices are stale');
+      });
+
+      it('accepts a proxy public key in upper case hex', async () => {
+        const proxyResponse = await makeDataProxyResponse(`${PROXY_URL}/cfd/XAU/USD`, QUOTE_BODY);
+
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+        const vmResult = await testOracleProgramTally(
+          Buffer.from(oracleProgram),
+          Buffer.from('verify:symbol=cfd/XAU/USD,fields=ask'),
+          createRevealArray([
+            [
+              RevealKind.HttpFetchResponse,
+              {
+                asset_type: 'cfd',
+                symbol: 'XAU/USD',
+                fields: ['ask'],
+                decimals: 2,
+                response: withUpperCasePublicKey(proxyResponse.dataProxyResponse),
+              },
+            ],
+          ]),
+        );
+
+        handleVmResult(vmResult, 0, 331399n);
+      });
+
+      it('rejects responses signed by an unexpected proxy public key', async () => {
+        const url = `${PROXY_URL}/cfd/XAU/USD`;
+        const proxyResponse = await makeDataProxyResponse(url, QUOTE_BODY, 'GET', undefined, unexpectedDataProxy);
+
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+        const vmResult = await testOracleProgramTally(
+          Buffer.from(oracleProgram),
+          Buffer.from('verify:symbol=cfd/XAU/USD,fields=ask'),
+          createRevealArray([
+            [
+              RevealKind.HttpFetchResponse,
+              {
+                asset_type: 'cfd',
+                symbol: 'XAU/USD',
+                fields: ['ask'],
+                decimals: 2,
+                response: proxyResponse.dataProxyResponse,
+              },
+            ],
+          ]),
+        );
+
+        handleVmResult(vmResult, 1, 0n);
+        expect(Buffer.from(vmResult.result).toString()).toBe('Unexpected proxy public key');
+      });
+    });
+  });
 });
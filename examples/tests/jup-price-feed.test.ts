@@ -0,0 +1,400 @@
+// biome-ignore assist/source/organizeImports: biome is lying
+import { file } from 'bun';
+import { afterEach, describe, expect, it, mock } from 'bun:test';
+import { testOracleProgramExecution, testOracleProgramTally } from '@seda-protocol/dev-tools';
+import {
+  handleBigIntArrayTallyVmResult as handleVmResult,
+  handleBigIntArrayExecutionVmResult as handleExecutionVmResult,
+  createRevealArray,
+  RevealKind,
+} from './utils.js';
+
+const WASM_PATH = 'target/wasm32-wasip1/release/jup-price-feed.wasm';
+
+const SOL = 'So11111111111111111111111111111111111111112';
+const USDC = 'EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v';
+
+const fetchMock = mock();
+
+afterEach(() => {
+  fetchMock.mockRestore();
+});
+
+function mockJupiterResponse(overrides: Record<string, unknown> = {}) {
+  fetchMock.mockImplementation((_) => {
+    return new Response(
+      JSON.stringify({
+        [SOL]: {
+          usdPrice: 245.67,
+          blockId: 365626874,
+          decimals: 9,
+          priceChange24h: -1.2,
+        },
+        [USDC]: {
+          usdPrice: 0.996,
+          blockId: 365626870,
+          decimals: 6,
+          priceChange24h: 0.03667753493441,
+        },
+        ...overrides,
+      }),
+    );
+  });
+}
+
+describe('jup price feed', () => {
+  describe('execution phase', () => {
+    it('should return the price with the default decimals', async () => {
+      mockJupiterResponse();
+
+      const oracleProgram = await file(WASM_PATH).arrayBuffer();
+      const vmResult = await testOracleProgramExecution(Buffer.from(oracleProgram), Buffer.from(SOL), fetchMock);
+
+      // The block id the prices were observed at comes first
+      handleExecutionVmResult(vmResult, 0, [365626874n, 245670000n]);
+      expect(fetchMock).toHaveBeenCalledTimes(1);
+      expect(fetchMock.mock.calls[0][0].toString()).toBe(`https://lite-api.jup.ag/price/v3?ids=${SOL}`);
+    });
+
+    it('should return the prices in input order with the requested decimals', async () => {
+      mockJupiterResponse();
+
+      const oracleProgram = await file(WASM_PATH).arrayBuffer();
+      const vmResult = await testOracleProgramExecution(
+        Buffer.from(oracleProgram),
+        Buffer.from(`${USDC},${SOL}-8`),
+        fetchMock,
+      );
+
+      // The oldest block id of the batch is revealed
+      handleExecutionVmResult(vmResult, 0, [365626870n, 99600000n, 24567000000n]);
+    });
+
+    it('should convert the raw price text without a float round trip', async () => {
+      // JSON.stringify would round the price to a float, so the body is written out by hand.
+      fetchMock.mockImplementation(
+        () => new Response(`{"${SOL}":{"usdPrice":0.12345678901234567891,"blockId":365626874,"decimals":9}}`),
+      );
+
+      const oracleProgram = await file(WASM_PATH).arrayBuffer();
+      const vmResult = await testOracleProgramExecution(
+        Buffer.from(oracleProgram),
+        Buffer.from(`${SOL}-18`),
+        fetchMock,
+      );
+
+      handleExecutionVmResult(vmResult, 0, [365626874n, 123456789012345678n]);
+    });
+
+    it('should convert prices in exponent notation', async () => {
+      mockJupiterResponse({ [SOL]: { usdPrice: 1.5e-7, blockId: 365626874, decimals: 9, priceChange24h: 0 } });
+
+      const oracleProgram = await file(WASM_PATH).arrayBuffer();
+      const vmResult = await testOracleProgramExecution(Buffer.from(oracleProgram), Buffer.from(`${SOL}-9`), fetchMock);
+
+      handleExecutionVmResult(vmResult, 0, [365626874n, 150n]);
+    });
+
+    it('should reject a zero price', async () => {
+      mockJupiterResponse({ [SOL]: { usdPrice: 0, blockId: 365626874, decimals: 9, priceChange24h: 0 } });
+
+      const oracleProgram = await file(WASM_PATH).arrayBuffer();
+      const vmResult = await testOracleProgramExecution(Buffer.from(oracleProgram), Buffer.from(SOL), fetchMock);
+
+      handleExecutionVmResult(vmResult, 1, []);
+    });
+
+    it('should reject a price that is missing', async () => {
+      mockJupiterResponse({ [SOL]: { blockId: 365626874, decimals: 9, priceChange24h: 0 } });
+
+      const oracleProgram = await file(WASM_PATH).arrayBuffer();
+      const vmResult = await testOracleProgramExecution(Buffer.from(oracleProgram), Buffer.from(SOL), fetchMock);
+
+      handleExecutionVmResult(vmResult, 1, []);
+    });
+
+    it('should accept prices within the thresholds', async () => {
+      mockJupiterResponse();
+
+      const oracleProgram = await file(WASM_PATH).arrayBuffer();
+      const vmResult = await testOracleProgramExecution(
+        Buffer.from(oracleProgram),
+        Buffer.from(`${SOL},${USDC}-maxChange24h=1.2-minBlockId=365626870`),
+        fetchMock,
+      );
+
+      handleExecutionVmResult(vmResult, 0, [365626870n, 245670000n, 996000n]);
+    });
+
+    it('should reject a price that changed more than the maximum 24h change', async () => {
+      mockJupiterResponse();
+
+      const oracleProgram = await file(WASM_PATH).arrayBuffer();
+      const vmResult = await testOracleProgramExecution(
+        Buffer.from(oracleProgram),
+        Buffer.from(`${SOL}-8-maxChange24h=1`),
+        fetchMock,
+      );
+
+      handleExecutionVmResult(vmResult, 1, []);
+    });
+
+    it('should reject a price observed before the minimum block id', async () => {
+      mockJupiterResponse();
+
+      const oracleProgram = await file(WASM_PATH).arrayBuffer();
+      const vmResult = await testOracleProgramExecution(
+        Buffer.from(oracleProgram),
+        Buffer.from(`${SOL},${USDC}-minBlockId=365626871`),
+        fetchMock,
+      );
+
+      handleExecutionVmResult(vmResult, 1, []);
+    });
+
+    it('should reject an unknown option', async () => {
+      mockJupiterResponse();
+
+      const oracleProgram = await file(WASM_PATH).arrayBuffer();
+      const vmResult = await testOracleProgramExecution(
+        Buffer.from(oracleProgram),
+        Buffer.from(`${SOL}-minLiquidity=1000`),
+        fetchMock,
+      );
+
+      handleExecutionVmResult(vmResult, 1, []);
+      expect(fetchMock).not.toHaveBeenCalled();
+    });
+
+    it('should return the cross price in the quote token rounded down', async () => {
+      mockJupiterResponse();
+
+      const oracleProgram = await file(WASM_PATH).arrayBuffer();
+      const vmResult = await testOracleProgramExecution(
+        Buffer.from(oracleProgram),
+        Buffer.from(`${SOL}-vsToken=${USDC}`),
+        fetchMock,
+      );
+
+      // 245.67 / 0.996 = 246.6566265...
+      handleExecutionVmResult(vmResult, 0, [365626870n, 246656626n]);
+      expect(fetchMock).toHaveBeenCalledTimes(1);
+      expect(fetchMock.mock.calls[0][0].toString()).toBe(`https://lite-api.jup.ag/price/v3?ids=${SOL},${USDC}`);
+    });
+
+    it('should return the cross price with the requested decimals', async () => {
+      mockJupiterResponse();
+
+      const oracleProgram = await file(WASM_PATH).arrayBuffer();
+      const vmResult = await testOracleProgramExecution(
+        Buffer.from(oracleProgram),
+        Buffer.from(`${USDC},${SOL}-9-vsToken=${SOL}`),
+        fetchMock,
+      );
+
+      // 0.996 / 245.67 = 0.0040542190...
+      handleExecutionVmResult(vmResult, 0, [365626870n, 4054219n, 1000000000n]);
+      expect(fetchMock.mock.calls[0][0].toString()).toBe(`https://lite-api.jup.ag/price/v3?ids=${USDC},${SOL}`);
+    });
+
+    it('should reject a cross price that rounds down to zero', async () => {
+      mockJupiterResponse();
+
+      const oracleProgram = await file(WASM_PATH).arrayBuffer();
+      const vmResult = await testOracleProgramExecution(
+        Buffer.from(oracleProgram),
+        Buffer.from(`${USDC}-2-vsToken=${SOL}`),
+        fetchMock,
+      );
+
+      handleExecutionVmResult(vmResult, 1, []);
+    });
+
+    it('should error if the quote token has no price', async () => {
+      mockJupiterResponse();
+
+      const oracleProgram = await file(WASM_PATH).arrayBuffer();
+      const vmResult = await testOracleProgramExecution(
+        Buffer.from(oracleProgram),
+        Buffer.from(`${SOL}-vsToken=JUPyiwrYJFskUPiHa7hkeR8VUtAeFoSYbKedZNsDvCN`),
+        fetchMock,
+      );
+
+      handleExecutionVmResult(vmResult, 1, []);
+    });
+
+    it('should error if a token has no price', async () => {
+      mockJupiterResponse();
+
+      const oracleProgram = await file(WASM_PATH).arrayBuffer();
+      const vmResult = await testOracleProgramExecution(
+        Buffer.from(oracleProgram),
+        Buffer.from(`${SOL},JUPyiwrYJFskUPiHa7hkeR8VUtAeFoSYbKedZNsDvCN`),
+        fetchMock,
+      );
+
+      handleExecutionVmResult(vmResult, 1, []);
+    });
+
+    it('should reject a malformed mint before fetching', async () => {
+      mockJupiterResponse();
+
+      const oracleProgram = await file(WASM_PATH).arrayBuffer();
+      const vmResult = await testOracleProgramExecution(
+        Buffer.from(oracleProgram),
+        Buffer.from(`${SOL},${USDC}&ids=0OIl`),
+        fetchMock,
+      );
+
+      handleExecutionVmResult(vmResult, 1, []);
+      expect(fetchMock).not.toHaveBeenCalled();
+    });
+
+    it('should reject invalid decimals', async () => {
+      mockJupiterResponse();
+
+      const oracleProgram = await file(WASM_PATH).arrayBuffer();
+      const vmResult = await testOracleProgramExecution(Buffer.from(oracleProgram), Buffer.from(`${SOL}-x`), fetchMock);
+
+      handleExecutionVmResult(vmResult, 1, []);
+      expect(fetchMock).not.toHaveBeenCalled();
+    });
+  });
+
+  describe('tally phase', () => {
+    it('works with 1 price', async () => {
+      const oracleProgram = await file(WASM_PATH).arrayBuffer();
+      const vmResult = await testOracleProgramTally(
+        Buffer.from(oracleProgram),
+        Buffer.from('tally-inputs'),
+        createRevealArray([[RevealKind.BigIntArray, [1000n, 100n]]]),
+      );
+      handleVmResult(vmResult, 0, [100n]);
+    });
+
+    it('takes the median of each token', async () => {
+      const oracleProgram = await file(WASM_PATH).arrayBuffer();
+      const vmResult = await testOracleProgramTally(
+        Buffer.from(oracleProgram),
+        Buffer.from('tally-inputs'),
+        createRevealArray([
+          [RevealKind.BigIntArray, [1000n, 100n, 5n]],
+          [RevealKind.BigIntArray, [1000n, 300n, 1n]],
+          [RevealKind.BigIntArray, [1000n, 200n, 3n]],
+          [RevealKind.BigIntArray, [1000n, 400n, 2n]],
+        ]),
+      );
+      handleVmResult(vmResult, 0, [250n, 2n]);
+    });
+
+    it('should ignore errored executions', async () => {
+      const oracleProgram = await file(WASM_PATH).arrayBuffer();
+      const vmResult = await testOracleProgramTally(
+        Buffer.from(oracleProgram),
+        Buffer.from('tally-inputs'),
+        createRevealArray([
+          [RevealKind.BigIntArray, [1000n, 100n]],
+          [RevealKind.Failed],
+          [RevealKind.BigIntArray, [1000n, 300n]],
+        ]),
+      );
+      handleVmResult(vmResult, 0, [200n]);
+    });
+
+    it('should error if the reveals have a different number of tokens', async () => {
+      const oracleProgram = await file(WASM_PATH).arrayBuffer();
+      const vmResult = await testOracleProgramTally(
+        Buffer.from(oracleProgram),
+        Buffer.from('tally-inputs'),
+        createRevealArray([
+          [RevealKind.BigIntArray, [1000n, 100n, 5n]],
+          [RevealKind.BigIntArray, [1000n, 300n]],
+        ]),
+      );
+      handleVmResult(vmResult, 1, []);
+    });
+
+    it('should drop reveals observed at a much older slot', async () => {
+      const oracleProgram = await file(WASM_PATH).arrayBuffer();
+      const vmResult = await testOracleProgramTally(
+        Buffer.from(oracleProgram),
+        Buffer.from('tally-inputs'),
+        createRevealArray([
+          [RevealKind.BigIntArray, [1000n, 100n]],
+          [RevealKind.BigIntArray, [1000n, 200n]],
+          [RevealKind.BigIntArray, [1000n, 300n]],
+          [RevealKind.BigIntArray, [850n, 400n]],
+          [RevealKind.BigIntArray, [849n, 900n]],
+        ]),
+      );
+      handleVmResult(vmResult, 0, [250n]);
+    });
+
+    it('measures the slot lag from the median slot', async () => {
+      const oracleProgram = await file(WASM_PATH).arrayBuffer();
+      const vmResult = await testOracleProgramTally(
+        Buffer.from(oracleProgram),
+        Buffer.from('tally-inputs'),
+        createRevealArray([
+          [RevealKind.BigIntArray, [1000n, 100n]],
+          [RevealKind.BigIntArray, [1001n, 200n]],
+          // A far future slot must not make the other reveals look stale.
+          [RevealKind.BigIntArray, [999999999999n, 900n]],
+        ]),
+      );
+      handleVmResult(vmResult, 0, [200n]);
+    });
+
+    it('should drop reveals whose block id does not fit a u64', async () => {
+      const oracleProgram = await file(WASM_PATH).arrayBuffer();
+      const vmResult = await testOracleProgramTally(
+        Buffer.from(oracleProgram),
+        Buffer.from('tally-inputs'),
+        createRevealArray([
+          [RevealKind.BigIntArray, [2n ** 64n + 1000n, 900n]],
+          [RevealKind.BigIntArray, [1000n, 200n]],
+        ]),
+      );
+      handleVmResult(vmResult, 0, [200n]);
+    });
+
+    it('uses the maximum slot lag from the tally inputs', async () => {
+      const oracleProgram = await file(WASM_PATH).arrayBuffer();
+      const vmResult = await testOracleProgramTally(
+        Buffer.from(oracleProgram),
+        Buffer.from('maxSlotLag=10'),
+        createRevealArray([
+          [RevealKind.BigIntArray, [1000n, 100n]],
+          [RevealKind.BigIntArray, [1000n, 200n]],
+          [RevealKind.BigIntArray, [1000n, 300n]],
+          [RevealKind.BigIntArray, [990n, 400n]],
+          [RevealKind.BigIntArray, [989n, 900n]],
+        ]),
+      );
+      handleVmResult(vmResult, 0, [250n]);
+    });
+
+    it('errors if the maximum slot lag is not a number', async () => {
+      const oracleProgram = await file(WASM_PATH).arrayBuffer();
+      const vmResult = await testOracleProgramTally(
+        Buffer.from(oracleProgram),
+        Buffer.from('maxSlotLag=ten'),
+        createRevealArray([
+          [RevealKind.BigIntArray, [1000n, 100n]],
+          [RevealKind.BigIntArray, [1000n, 200n]],
+        ]),
+      );
+      handleVmResult(vmResult, 1, []);
+    });
+
+    it('should error if all executions errored', async () => {
+      const oracleProgram = await file(WASM_PATH).arrayBuffer();
+      const vmResult = await testOracleProgramTally(
+        Buffer.from(oracleProgram),
+        Buffer.from('tally-inputs'),
+        createRevealArray([[RevealKind.Failed], [RevealKind.Failed]]),
+      );
+      handleVmResult(vmResult, 1, []);
+    });
+  });
+});
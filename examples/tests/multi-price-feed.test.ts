@@ -1,6 +1,6 @@
 // biome-ignore assist/source/organizeImports: biome is lying
 import { file } from 'bun';
-import { afterEach, describe, it, mock } from 'bun:test';
+import { afterEach, describe, expect, it, mock } from 'bun:test';
 import { testOracleProgramExecution, testOracleProgramTally } from '@seda-protocol/dev-tools';
 import { match } from 'ts-pattern';
 import {
@@ -18,80 +18,164 @@ afterEach(() => {
   fetchMock.mockRestore();
 });
 
+function mockExchangeResponses({ binanceQuoteVolume = '3000000.50000000', okxVol24h = '3924.03745314' } = {}) {
+  fetchMock.mockImplementation((url) => {
+    return match(url.host)
+      .with('api.binance.com', () => {
+        return new Response(
+          JSON.stringify({
+            symbol: 'BTCUSDT',
+            lastPrice: '117318.90000000',
+            volume: '25.57142000',
+            quoteVolume: binanceQuoteVolume,
+          }),
+        );
+      })
+      .with('data.gateapi.io', () => {
+        return new Response(JSON.stringify({ last: '2451.763000' }));
+      })
+      .with('api.kucoin.com', () => {
+        return new Response(JSON.stringify({ data: { price: '2452.300000' } }));
+      })
+      .with('www.mexc.com', () => {
+        return new Response(
+          JSON.stringify({
+            code: 200,
+            data: [
+              {
+                symbol: 'BTC_USDT',
+                volume: '3332.47675483',
+                amount: '394182197.63',
+                high: '119272.73',
+                low: '117235.65',
+                bid: '117313.8',
+                ask: '117313.81',
+                open: '118185.71',
+                last: '117313.8',
+                time: 1753806782035,
+                change_rate: '-0.0073',
+              },
+            ],
+          }),
+        );
+      })
+      .with('www.okx.com', () => {
+        return new Response(
+          JSON.stringify({
+            code: '0',
+            msg: '',
+            data: [
+              {
+                instType: 'SPOT',
+                instId: 'BTC-USDT',
+                last: '117216.1',
+                lastSz: '0.00002474',
+                askPx: '117216.1',
+                askSz: '1.04574961',
+                bidPx: '117216',
+                bidSz: '0.72049894',
+                open24h: '118255.7',
+                high24h: '119300',
+                low24h: '117155',
+                volCcy24h: '463828560.42979406',
+                vol24h: okxVol24h,
+                ts: '1753806883615',
+                sodUtc0: '118073.9',
+                sodUtc8: '117384.1',
+              },
+            ],
+          }),
+        );
+      })
+      .otherwise(() => {
+        throw new Error(`Unexpected host: ${url.host}`);
+      });
+  });
+}
+
 describe('multi price feed', () => {
   describe('execution phase', () => {
     it('should return the correct price', async () => {
-      fetchMock.mockImplementation((url) => {
-        return match(url.host)
-          .with('api.binance.com', () => {
-            return new Response(JSON.stringify({ symbol: 'BTCUSDT', price: '117318.90000000' }));
-          })
-          .with('data.gateapi.io', () => {
-            return new Response(JSON.stringify({ last: '2451.763000' }));
-          })
-          .with('api.kucoin.com', () => {
-            return new Response(JSON.stringify({ data: { price: '2452.300000' } }));
-          })
-          .with('www.mexc.com', () => {
-            return new Response(
-              JSON.stringify({
-                code: 200,
-                data: [
-                  {
-                    symbol: 'BTC_USDT',
-                    volume: '3332.47675483',
-                    amount: '394182197.63',
-                    high: '119272.73',
-                    low: '117235.65',
-                    bid: '117313.8',
-                    ask: '117313.81',
-                    open: '118185.71',
-                    last: '117313.8',
-                    time: 1753806782035,
-                    change_rate: '-0.0073',
-                  },
-                ],
-              }),
-            );
-          })
-          .with('www.okx.com', () => {
-            return new Response(
-              JSON.stringify({
-                code: '0',
-                msg: '',
-                data: [
-                  {
-                    instType: 'SPOT',
-                    instId: 'BTC-USDT',
-                    last: '117216.1',
-                    lastSz: '0.00002474',
-                    askPx: '117216.1',
-                    askSz: '1.04574961',
-                    bidPx: '117216',
-                    bidSz: '0.72049894',
-                    open24h: '118255.7',
-                    high24h: '119300',
-                    low24h: '117155',
-                    volCcy24h: '463828560.42979406',
-                    vol24h: '3924.03745314',
-                    ts: '1753806883615',
-                    sodUtc0: '118073.9',
-                    sodUtc8: '117384.1',
-                  },
-                ],
-              }),
-            );
-          })
-          .otherwise(() => {
-            throw new Error(`Unexpected host: ${url.host}`);
-          });
-      });
+      mockExchangeResponses();
 
       const oracleProgram = await file(WASM_PATH).arrayBuffer();
 
       const vmResult = await testOracleProgramExecution(Buffer.from(oracleProgram), Buffer.from('BTC-USDT'), fetchMock);
 
       handleExecutionVmResult(vmResult, 0, 117313798144n);
+      expect(fetchMock.mock.calls.map(([url]) => url.toString())).toEqual([
+        'https://api.binance.com/api/v3/ticker/24hr?symbol=BTCUSDT',
+        'https://www.mexc.com/open/api/v2/market/ticker?symbol=BTC_USDT',
+        'https://www.okx.com/api/v5/market/ticker?instId=BTC-USDT',
+      ]);
+    });
+
+    it('percent-encodes the symbols in the query of every exchange', async () => {
+      mockExchangeResponses();
+
+      const oracleProgram = await file(WASM_PATH).arrayBuffer();
+
+      await testOracleProgramExecution(Buffer.from(oracleProgram), Buffer.from('BTC&x=1-US/DT'), fetchMock);
+
+      expect(fetchMock.mock.calls.map(([url]) => url.toString())).toEqual([
+        'https://api.binance.com/api/v3/ticker/24hr?symbol=BTC%26x%3D1US%2FDT',
+        'https://www.mexc.com/open/api/v2/market/ticker?symbol=BTC%26x%3D1_US%2FDT',
+        'https://www.okx.com/api/v5/market/ticker?instId=BTC%26x%3D1-US%2FDT',
+      ]);
+    });
+
+    it('should return the median price when requested explicitly', async () => {
+      mockExchangeResponses();
+
+      const oracleProgram = await file(WASM_PATH).arrayBuffer();
+      const vmResult = await testOracleProgramExecution(
+        Buffer.from(oracleProgram),
+        Buffer.from('BTC-USDT,median'),
+        fetchMock,
+      );
+
+      handleExecutionVmResult(vmResult, 0, 117313798144n);
+    });
+
+    it('should return the volume weighted price', async () => {
+      mockExchangeResponses();
+
+      const oracleProgram = await file(WASM_PATH).arrayBuffer();
+      const vmResult = await testOracleProgramExecution(
+        Buffer.from(oracleProgram),
+        Buffer.from('BTC-USDT,vwap'),
+        fetchMock,
+      );
+
+      // Only Binance (3000000.5) and OKX (3924.03745314 * 117216.1) report a volume, MEXC is left out.
+      handleExecutionVmResult(vmResult, 0, 117216766508n);
+    });
+
+    it('should weigh in the volumes below 1 of illiquid venues', async () => {
+      mockExchangeResponses({ binanceQuoteVolume: '0.5', okxVol24h: '0.000004' });
+
+      const oracleProgram = await file(WASM_PATH).arrayBuffer();
+      const vmResult = await testOracleProgramExecution(
+        Buffer.from(oracleProgram),
+        Buffer.from('BTC-USDT,vwap'),
+        fetchMock,
+      );
+
+      // Binance (0.5) and OKX (0.000004 * 117216.1) weigh in with their fixed point volumes.
+      handleExecutionVmResult(vmResult, 0, 117269152900n);
+    });
+
+    it('should error on an unknown aggregation mode', async () => {
+      mockExchangeResponses();
+
+      const oracleProgram = await file(WASM_PATH).arrayBuffer();
+      const vmResult = await testOracleProgramExecution(
+        Buffer.from(oracleProgram),
+        Buffer.from('BTC-USDT,mean'),
+        fetchMock,
+      );
+
+      handleExecutionVmResult(vmResult, 1, 0n);
     });
   });
 
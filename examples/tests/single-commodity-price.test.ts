@@ -1,10 +1,13 @@
 // biome-ignore assist/source/organizeImports: biome is lying
 import { file } from 'bun';
-import { afterEach, describe, it, mock } from 'bun:test';
+import { afterEach, describe, expect, it, mock } from 'bun:test';
 import { testOracleProgramExecution, testOracleProgramTally } from '@seda-protocol/dev-tools';
 import {
   handleBigIntTallyVmResult as handleVmResult,
   handleBigIntExecutionVmResult as handleExecutionVmResult,
+  handleBigIntArrayExecutionVmResult,
+  handleBigIntArrayTallyVmResult,
+  handleTupleTallyVmResult,
   createRevealArray,
   RevealKind,
 } from './utils.js';
@@ -13,6 +16,26 @@ const WASM_PATH = 'target/wasm32-wasip1/release/single-commodity-price.wasm';
 
 const fetchMock = mock();
 
+const QUOTE_RESPONSE = JSON.stringify({
+  Quote: {
+    'XAU/USD:BFX': {
+      askExchangeCode: '',
+      askPrice: 3313.99,
+      askSize: 100,
+      askTime: 1753710744000,
+      bidExchangeCode: '',
+      bidPrice: 3313.83,
+      bidSize: 100,
+      bidTime: 1753710744000,
+      eventSymbol: 'XAU/USD:BFX',
+      eventTime: 0,
+      sequence: 0,
+      timeNanoPart: 0,
+    },
+  },
+  status: 'OK',
+});
+
 afterEach(() => {
   fetchMock.mockRestore();
 });
@@ -56,7 +79,69 @@ describe('single commodity price', () => {
         0n,
       );
 
-      handleExecutionVmResult(vmResult, 0, 331399n);
+      handleBigIntArrayExecutionVmResult(vmResult, 0, [1n, 1n, 331399n, 1753710744000n]);
+      expect(fetchMock.mock.calls.map(([url]) => url.toString())).toEqual([
+        'http://98.84.79.123:5384/proxy/cfd/XAU/USD',
+      ]);
+    });
+
+    it('percent-encodes the symbol in the path', async () => {
+      fetchMock.mockImplementation((_) => new Response(QUOTE_RESPONSE));
+
+      const oracleProgram = await file(WASM_PATH).arrayBuffer();
+
+      await testOracleProgramExecution(
+        Buffer.from(oracleProgram),
+        Buffer.from('XAU/EUR?x'),
+        fetchMock,
+        undefined,
+        undefined,
+        undefined,
+        0n,
+      );
+
+      expect(fetchMock.mock.calls.map(([url]) => url.toString())).toEqual([
+        'http://98.84.79.123:5384/proxy/cfd/XAU%2FEUR%3Fx/USD',
+      ]);
+    });
+
+    it('reveals the requested price fields in order', async () => {
+      fetchMock.mockImplementation((_) => new Response(QUOTE_RESPONSE));
+
+      const oracleProgram = await file(WASM_PATH).arrayBuffer();
+
+      const vmResult = await testOracleProgramExecution(
+        Buffer.from(oracleProgram),
+        Buffer.from('XAU-mid,bid'),
+        fetchMock,
+        undefined,
+        undefined,
+        undefined,
+        0n,
+      );
+
+      handleBigIntArrayExecutionVmResult(vmResult, 0, [2n, 2n, 0n, 331391n, 331383n, 1753710744000n]);
+      expect(fetchMock.mock.calls.map(([url]) => url.toString())).toEqual([
+        'http://98.84.79.123:5384/proxy/cfd/XAU/USD',
+      ]);
+    });
+
+    it('rejects fields that are not available', async () => {
+      const oracleProgram = await file(WASM_PATH).arrayBuffer();
+
+      const vmResult = await testOracleProgramExecution(
+        Buffer.from(oracleProgram),
+        Buffer.from('XAU-bid,close'),
+        fetchMock,
+        undefined,
+        undefined,
+        undefined,
+        0n,
+      );
+
+      handleExecutionVmResult(vmResult, 1, 0n);
+      expect(Buffer.from(vmResult.result).toString()).toBe('Invalid fields: ["close"]');
+      expect(fetchMock).not.toHaveBeenCalled();
     });
   });
 
@@ -66,7 +151,7 @@ describe('single commodity price', () => {
       const vmResult = await testOracleProgramTally(
         Buffer.from(oracleProgram),
         Buffer.from('tally-inputs'),
-        createRevealArray([[RevealKind.BigInt, 100n]]),
+        createRevealArray([[RevealKind.BigIntArray, [1n, 1n, 100n, 1753710744000n]]]),
       );
       handleVmResult(vmResult, 0, 100n);
     });
@@ -77,8 +162,8 @@ describe('single commodity price', () => {
         Buffer.from(oracleProgram),
         Buffer.from('tally-inputs'),
         createRevealArray([
-          [RevealKind.BigInt, 100n],
-          [RevealKind.BigInt, 200n],
+          [RevealKind.BigIntArray, [1n, 1n, 100n, 1753710744000n]],
+          [RevealKind.BigIntArray, [1n, 1n, 200n, 1753710744000n]],
         ]),
       );
       handleVmResult(vmResult, 0, 150n);
@@ -90,11 +175,11 @@ describe('single commodity price', () => {
         Buffer.from(oracleProgram),
         Buffer.from('tally-inputs'),
         createRevealArray([
-          [RevealKind.BigInt, 100n],
-          [RevealKind.BigInt, 200n],
-          [RevealKind.BigInt, 300n],
-          [RevealKind.BigInt, 400n],
-          [RevealKind.BigInt, 500n],
+          [RevealKind.BigIntArray, [1n, 1n, 100n, 1753710744000n]],
+          [RevealKind.BigIntArray, [1n, 1n, 200n, 1753710744000n]],
+          [RevealKind.BigIntArray, [1n, 1n, 300n, 1753710744000n]],
+          [RevealKind.BigIntArray, [1n, 1n, 400n, 1753710744000n]],
+          [RevealKind.BigIntArray, [1n, 1n, 500n, 1753710744000n]],
         ]),
       );
       handleVmResult(vmResult, 0, 300n);
@@ -106,16 +191,16 @@ describe('single commodity price', () => {
         Buffer.from(oracleProgram),
         Buffer.from('tally-inputs'),
         createRevealArray([
-          [RevealKind.BigInt, 100n],
-          [RevealKind.BigInt, 200n],
-          [RevealKind.BigInt, 300n],
-          [RevealKind.BigInt, 400n],
-          [RevealKind.BigInt, 500n],
-          [RevealKind.BigInt, 600n],
-          [RevealKind.BigInt, 700n],
-          [RevealKind.BigInt, 800n],
-          [RevealKind.BigInt, 900n],
-          [RevealKind.BigInt, 1000n],
+          [RevealKind.BigIntArray, [1n, 1n, 100n, 1753710744000n]],
+          [RevealKind.BigIntArray, [1n, 1n, 200n, 1753710744000n]],
+          [RevealKind.BigIntArray, [1n, 1n, 300n, 1753710744000n]],
+          [RevealKind.BigIntArray, [1n, 1n, 400n, 1753710744000n]],
+          [RevealKind.BigIntArray, [1n, 1n, 500n, 1753710744000n]],
+          [RevealKind.BigIntArray, [1n, 1n, 600n, 1753710744000n]],
+          [RevealKind.BigIntArray, [1n, 1n, 700n, 1753710744000n]],
+          [RevealKind.BigIntArray, [1n, 1n, 800n, 1753710744000n]],
+          [RevealKind.BigIntArray, [1n, 1n, 900n, 1753710744000n]],
+          [RevealKind.BigIntArray, [1n, 1n, 1000n, 1753710744000n]],
         ]),
       );
       handleVmResult(vmResult, 0, 550n);
@@ -127,17 +212,135 @@ describe('single commodity price', () => {
         Buffer.from(oracleProgram),
         Buffer.from('tally-inputs'),
         createRevealArray([
-          [RevealKind.BigInt, 500n],
-          [RevealKind.BigInt, 100n],
-          [RevealKind.BigInt, 300n],
-          [RevealKind.BigInt, 200n],
-          [RevealKind.BigInt, 200n],
-          [RevealKind.BigInt, 400n],
+          [RevealKind.BigIntArray, [1n, 1n, 500n, 1753710744000n]],
+          [RevealKind.BigIntArray, [1n, 1n, 100n, 1753710744000n]],
+          [RevealKind.BigIntArray, [1n, 1n, 300n, 1753710744000n]],
+          [RevealKind.BigIntArray, [1n, 1n, 200n, 1753710744000n]],
+          [RevealKind.BigIntArray, [1n, 1n, 200n, 1753710744000n]],
+          [RevealKind.BigIntArray, [1n, 1n, 400n, 1753710744000n]],
         ]),
       );
       handleVmResult(vmResult, 0, 250n);
     });
 
+    describe('price fields', () => {
+      it('returns the median of every field as a uint256[]', async () => {
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+        const vmResult = await testOracleProgramTally(
+          Buffer.from(oracleProgram),
+          Buffer.from('tally-inputs'),
+          createRevealArray([
+            [RevealKind.BigIntArray, [3n, 0n, 1n, 2n, 100n, 110n, 105n, 1753710744000n]],
+            [RevealKind.BigIntArray, [3n, 0n, 1n, 2n, 200n, 220n, 210n, 1753710744000n]],
+            [RevealKind.BigIntArray, [3n, 0n, 1n, 2n, 300n, 330n, 315n, 1753710744000n]],
+          ]),
+        );
+        handleBigIntArrayTallyVmResult(vmResult, 0, [200n, 220n, 210n]);
+      });
+
+      it('ignores the reveals with different fields', async () => {
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+        const vmResult = await testOracleProgramTally(
+          Buffer.from(oracleProgram),
+          Buffer.from('tally-inputs'),
+          createRevealArray([
+            [RevealKind.BigIntArray, [2n, 0n, 1n, 100n, 110n, 1753710744000n]],
+            [RevealKind.BigIntArray, [2n, 1n, 0n, 900n, 900n, 1753710744000n]],
+            [RevealKind.BigIntArray, [1n, 0n, 900n, 1753710744000n]],
+            [RevealKind.BigIntArray, [2n, 0n, 1n, 200n, 220n, 1753710744000n]],
+          ]),
+        );
+        handleBigIntArrayTallyVmResult(vmResult, 0, [150n, 165n]);
+      });
+
+      it('keeps the fields most reveals agree on when the first reveal is the outlier', async () => {
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+        const vmResult = await testOracleProgramTally(
+          Buffer.from(oracleProgram),
+          Buffer.from('tally-inputs'),
+          createRevealArray([
+            [RevealKind.BigIntArray, [1n, 2n, 900n, 1753710744000n]],
+            [RevealKind.BigIntArray, [2n, 0n, 1n, 100n, 110n, 1753710744000n]],
+            [RevealKind.BigIntArray, [2n, 0n, 1n, 200n, 220n, 1753710744000n]],
+          ]),
+        );
+        handleBigIntArrayTallyVmResult(vmResult, 0, [150n, 165n]);
+      });
+
+      it('ignores malformed reveals', async () => {
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+        const vmResult = await testOracleProgramTally(
+          Buffer.from(oracleProgram),
+          Buffer.from('tally-inputs'),
+          createRevealArray([
+            [RevealKind.BigInt, 100n],
+            [RevealKind.BigIntArray, [1n, 4n, 900n, 1753710744000n]],
+            [RevealKind.BigIntArray, [2n, 0n, 1n, 900n, 1753710744000n]],
+            [RevealKind.BigIntArray, [1n, 1n, 200n, 1753710744000n]],
+          ]),
+        );
+        handleVmResult(vmResult, 0, 200n);
+      });
+    });
+
+    describe('freshness', () => {
+      it('discards the reveals older than the maximum age from the median time', async () => {
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+        const vmResult = await testOracleProgramTally(
+          Buffer.from(oracleProgram),
+          Buffer.from('maxAge=60000'),
+          createRevealArray([
+            [RevealKind.BigIntArray, [1n, 1n, 100n, 1753710744000n]],
+            [RevealKind.BigIntArray, [1n, 1n, 200n, 1753710744000n]],
+            [RevealKind.BigIntArray, [1n, 1n, 900n, 1753537944000n]],
+          ]),
+        );
+        handleVmResult(vmResult, 0, 150n);
+      });
+
+      it('measures the age from the reference time in the tally inputs', async () => {
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+        const vmResult = await testOracleProgramTally(
+          Buffer.from(oracleProgram),
+          Buffer.from('maxAge=60000,now=1753710800000'),
+          createRevealArray([
+            [RevealKind.BigIntArray, [1n, 1n, 100n, 1753710700000n]],
+            [RevealKind.BigIntArray, [1n, 1n, 200n, 1753710744000n]],
+            [RevealKind.BigIntArray, [1n, 1n, 300n, 1753710790000n]],
+          ]),
+        );
+        handleVmResult(vmResult, 0, 250n);
+      });
+
+      it('errors if every reveal is stale', async () => {
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+        const vmResult = await testOracleProgramTally(
+          Buffer.from(oracleProgram),
+          Buffer.from('maxAge=60000,now=1753970000000'),
+          createRevealArray([
+            [RevealKind.BigIntArray, [1n, 1n, 100n, 1753710744000n]],
+            [RevealKind.BigIntArray, [1n, 1n, 200n, 1753710744000n]],
+          ]),
+        );
+        handleVmResult(vmResult, 1, 0n);
+        expect(Buffer.from(vmResult.result).toString()).toBe('All revealed prices are stale');
+      });
+
+      it('reports the median observation time when timestamped', async () => {
+        const oracleProgram = await file(WASM_PATH).arrayBuffer();
+        const vmResult = await testOracleProgramTally(
+          Buffer.from(oracleProgram),
+          Buffer.from('timestamped'),
+          createRevealArray([
+            [RevealKind.BigIntArray, [1n, 1n, 100n, 1753710700000n]],
+            [RevealKind.BigIntArray, [1n, 1n, 200n, 1753710744000n]],
+            [RevealKind.BigIntArray, [1n, 1n, 300n, 1753710790000n]],
+          ]),
+        );
+        handleTupleTallyVmResult(vmResult, 0, [200n, 1753710744000n], ['uint256', 'uint64']);
+      });
+    });
+
     describe('works with errored executions', () => {
       it('should ignore the errored execution', async () => {
         const oracleProgram = await file(WASM_PATH).arrayBuffer();
@@ -145,7 +348,11 @@ describe('single commodity price', () => {
         const vmResult = await testOracleProgramTally(
           Buffer.from(oracleProgram),
           Buffer.from('tally-inputs'),
-          createRevealArray([[RevealKind.BigInt, 100n], [RevealKind.Failed], [RevealKind.BigInt, 200n]]),
+          createRevealArray([
+            [RevealKind.BigIntArray, [1n, 1n, 100n, 1753710744000n]],
+            [RevealKind.Failed],
+            [RevealKind.BigIntArray, [1n, 1n, 200n, 1753710744000n]],
+          ]),
         );
 
         handleVmResult(vmResult, 0, 150n);
@@ -158,11 +365,11 @@ describe('single commodity price', () => {
           Buffer.from(oracleProgram),
           Buffer.from('tally-inputs'),
           createRevealArray([
-            [RevealKind.BigInt, 100n],
+            [RevealKind.BigIntArray, [1n, 1n, 100n, 1753710744000n]],
             [RevealKind.Failed],
-            [RevealKind.BigInt, 200n],
+            [RevealKind.BigIntArray, [1n, 1n, 200n, 1753710744000n]],
             [RevealKind.Failed],
-            [RevealKind.BigInt, 300n],
+            [RevealKind.BigIntArray, [1n, 1n, 300n, 1753710744000n]],
           ]),
         );
 
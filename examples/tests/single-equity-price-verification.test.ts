@@ -1,13 +1,18 @@
 // biome-ignore assist/source/organizeImports: biome is lying
 import { file } from 'bun';
-import { afterEach, describe, it, mock } from 'bun:test';
+import { afterEach, describe, expect, it, mock } from 'bun:test';
 import { testOracleProgramExecution, testOracleProgramTally } from '@seda-protocol/dev-tools';
 import {
   handleBigIntTallyVmResult as handleVmResult,
+  handleBigIntArrayTallyVmResult,
+  handleTupleTallyVmResult,
   handleHttpFetchResponseExecutionVmResult as handleExecutionVmResult,
   createRevealArray,
   RevealKind,
+  type RevealInput,
   makeDataProxyResponse,
+  unexpectedDataProxy,
+  withUpperCasePublicKey,
 } from './utils.js';
 import type { HttpFetchResponseData } from '@seda-protocol/vm';
 
@@ -15,6 +20,34 @@ const WASM_PATH = 'target/wasm32-wasip1/release/single-equity-price-verification
 
 const fetchMock = mock();
 
+// The URL the tally expects the responses of `symbol=AAPL` to answer.
+const PROXY_URL = 'http://98.84.79.123:5384/proxy/uslf_q/AAPL';
+
+const QUOTE_BODY = {
+  Quote: {
+    'AAPL:USLF24': {
+      askExchangeCode: 'U',
+      askPrice: 214.44,
+      askSize: 123,
+      askTime: 1753707742000,
+      bidExchangeCode: 'U',
+      bidPrice: 214.2,
+      bidSize: 157,
+      bidTime: 1753707657000,
+      eventSymbol: 'AAPL:USLF24',
+      eventTime: 0,
+      sequence: 0,
+      timeNanoPart: 0,
+    },
+  },
+  status: 'OK',
+};
+
+const quoteBody = (askPrice: number, askTime = 1753707742000) => ({
+  ...QUOTE_BODY,
+  Quote: { 'AAPL:USLF24': { ...QUOTE_BODY.Quote['AAPL:USLF24'], askPrice, askTime } },
+});
+
 afterEach(() => {
   fetchMock.mockRestore();
 });
@@ -59,8 +92,74 @@ describe('single equity price verification', () => {
         0n,
       );
 
-      const verificationResponse = { response: expectedResponse, symbol: 'AAPL' };
+      const verificationResponse = { response: expectedResponse, symbol: 'AAPL', fields: ['ask'] };
       handleExecutionVmResult(vmResult, 0, verificationResponse, responseBody);
+      expect(fetchMock.mock.calls.map(([url]) => url.toString())).toEqual([
+        'http://98.84.79.123:5384/proxy/uslf_q/AAPL',
+      ]);
+    });
+
+    it('percent-encodes the symbol in the path', async () => {
+      fetchMock.mockImplementation((_) => new Response('{}'));
+
+      const oracleProgram = await file(WASM_PATH).arrayBuffer();
+
+      await testOracleProgramExecution(
+        Buffer.from(oracleProgram),
+        Buffer.from('BRK/B?x=1'),
+        fetchMock,
+        undefined,
+        undefined,
+        undefined,
+        0n,
+      );
+
+      expect(fetchMock.mock.calls.map(([url]) => url.toString())).toEqual([
+        'http://98.84.79.123:5384/proxy/uslf_q/BRK%2FB%3Fx%3D1',
+      ]);
+    });
+
+    it('passes the requested price fields to the tally', async () => {
+      let expectedResponse: HttpFetchResponseData;
+      fetchMock.mockImplementation(async (url) => {
+        const response_info = await makeDataProxyResponse(url, QUOTE_BODY);
+        expectedResponse = response_info.dataProxyResponse;
+        return response_info.mockedResponse;
+      });
+
+      const oracleProgram = await file(WASM_PATH).arrayBuffer();
+      const vmResult = await testOracleProgramExecution(
+        Buffer.from(oracleProgram),
+        Buffer.from('AAPL-bid,mid'),
+        fetchMock,
+        undefined,
+        undefined,
+        undefined,
+        0n,
+      );
+
+      const verificationResponse = { response: expectedResponse, symbol: 'AAPL', fields: ['bid', 'mid'] };
+      handleExecutionVmResult(vmResult, 0, verificationResponse, QUOTE_BODY);
+      expect(fetchMock.mock.calls.map(([url]) => url.toString())).toEqual([
+        'http://98.84.79.123:5384/proxy/uslf_q/AAPL',
+      ]);
+    });
+
+    it('rejects fields that are not available', async () => {
+      const oracleProgram = await file(WASM_PATH).arrayBuffer();
+      const vmResult = await testOracleProgramExecution(
+        Buffer.from(oracleProgram),
+        Buffer.from('AAPL-last'),
+        fetchMock,
+        undefined,
+        undefined,
+        undefined,
+        0n,
+      );
+
+      expect(vmResult.exitCode).toBe(1);
+      expect(Buffer.from(vmResult.result).toString()).toBe('Invalid fields: ["last"]');
+      expect(fetchMock).not.toHaveBeenCalled();
     });
   });
 
@@ -85,26 +184,232 @@ describe('single equity price verification', () => {
         },
         status: 'OK',
       };
-      const proxyResponse = await makeDataProxyResponse('http://test.dummy:5384/proxy/usd/AAPL', requestBody);
+      const proxyResponse = await makeDataProxyResponse(PROXY_URL, requestBody);
+
+      const oracleProgram = await file(WASM_PATH).arrayBuffer();
+      const vmResult = await testOracleProgramTally(
+        Buffer.from(oracleProgram),
+        Buffer.from('symbol=AAPL'),
+        createRevealArray([
+          [
+            RevealKind.HttpFetchResponse,
+            { response: proxyResponse.dataProxyResponse, symbol: 'AAPL', fields: ['ask'] },
+          ],
+        ]),
+      );
+
+      handleVmResult(vmResult, 0, 21444n);
+    });
+
+    it('returns every requested price field as a uint256[]', async () => {
+      const proxyResponse = await makeDataProxyResponse(PROXY_URL, QUOTE_BODY);
+
+      const oracleProgram = await file(WASM_PATH).arrayBuffer();
+      const vmResult = await testOracleProgramTally(
+        Buffer.from(oracleProgram),
+        Buffer.from('symbol=AAPL,fields=bid;ask;mid'),
+        createRevealArray([
+          [
+            RevealKind.HttpFetchResponse,
+            { response: proxyResponse.dataProxyResponse, symbol: 'AAPL', fields: ['bid', 'ask', 'mid'] },
+          ],
+        ]),
+      );
+
+      handleBigIntArrayTallyVmResult(vmResult, 0, [21420n, 21444n, 21432n]);
+    });
+
+    it('errors if the prices are older than the maximum age', async () => {
+      const proxyResponse = await makeDataProxyResponse(PROXY_URL, QUOTE_BODY);
+
+      const oracleProgram = await file(WASM_PATH).arrayBuffer();
+      const vmResult = await testOracleProgramTally(
+        Buffer.from(oracleProgram),
+        Buffer.from('symbol=AAPL,fields=bid;ask,maxAge=60000,now=1753707900000'),
+        createRevealArray([
+          [
+            RevealKind.HttpFetchResponse,
+            { response: proxyResponse.dataProxyResponse, symbol: 'AAPL', fields: ['bid', 'ask'] },
+          ],
+        ]),
+      );
+
+      handleVmResult(vmResult, 1, 0n);
+      expect(Buffer.from(vmResult.result).toString()).toBe('All revealed prices are stale');
+    });
+
+    it('reports the observation time when timestamped', async () => {
+      const proxyResponse = await makeDataProxyResponse(PROXY_URL, QUOTE_BODY);
+
+      const oracleProgram = await file(WASM_PATH).arrayBuffer();
+      const vmResult = await testOracleProgramTally(
+        Buffer.from(oracleProgram),
+        Buffer.from('symbol=AAPL,timestamped,maxAge=60000,now=1753707750000'),
+        createRevealArray([
+          [
+            RevealKind.HttpFetchResponse,
+            { response: proxyResponse.dataProxyResponse, symbol: 'AAPL', fields: ['ask'] },
+          ],
+        ]),
+      );
+
+      handleTupleTallyVmResult(vmResult, 0, [21444n, 1753707742000n], ['uint256', 'uint64']);
+    });
+
+    it('takes the median over several verified reveals', async () => {
+      const proxyResponses = await Promise.all([
+        makeDataProxyResponse(PROXY_URL, quoteBody(214.4)),
+        makeDataProxyResponse(PROXY_URL, quoteBody(214.44)),
+        makeDataProxyResponse(PROXY_URL, quoteBody(214.5)),
+      ]);
+
+      const oracleProgram = await file(WASM_PATH).arrayBuffer();
+      const vmResult = await testOracleProgramTally(
+        Buffer.from(oracleProgram),
+        Buffer.from('symbol=AAPL'),
+        createRevealArray(
+          proxyResponses.map((response): RevealInput => [
+            RevealKind.HttpFetchResponse,
+            { response: response.dataProxyResponse, symbol: 'AAPL', fields: ['ask'] },
+          ]),
+        ),
+      );
+
+      handleVmResult(vmResult, 0, 21444n);
+    });
+
+    it('discards the unverifiable and stale reveals', async () => {
+      const first = await makeDataProxyResponse(PROXY_URL, quoteBody(214.4));
+      const second = await makeDataProxyResponse(PROXY_URL, quoteBody(214.5));
+      const stale = await makeDataProxyResponse(PROXY_URL, quoteBody(1, 1753600000000));
+      // A response whose body was changed after the proxy signed it.
+      const tampered = { ...first.dataProxyResponse, bytes: Array.from(Buffer.from(JSON.stringify(quoteBody(1)))) };
 
       const oracleProgram = await file(WASM_PATH).arrayBuffer();
       const vmResult = await testOracleProgramTally(
         Buffer.from(oracleProgram),
-        Buffer.from('tally-inputs'),
+        Buffer.from('symbol=AAPL,maxAge=60000,now=1753707750000'),
+        createRevealArray(
+          [first.dataProxyResponse, tampered, stale.dataProxyResponse, second.dataProxyResponse].map(
+            (response): RevealInput => [RevealKind.HttpFetchResponse, { response, symbol: 'AAPL', fields: ['ask'] }],
+          ),
+        ),
+      );
+
+      handleVmResult(vmResult, 0, 21445n);
+    });
+
+    it('accepts a proxy public key in upper case hex', async () => {
+      const proxyResponse = await makeDataProxyResponse(PROXY_URL, QUOTE_BODY);
+
+      const oracleProgram = await file(WASM_PATH).arrayBuffer();
+      const vmResult = await testOracleProgramTally(
+        Buffer.from(oracleProgram),
+        Buffer.from('symbol=AAPL'),
         createRevealArray([
-          [RevealKind.HttpFetchResponse, { response: proxyResponse.dataProxyResponse, symbol: 'AAPL' }],
+          [
+            RevealKind.HttpFetchResponse,
+            { response: withUpperCasePublicKey(proxyResponse.dataProxyResponse), symbol: 'AAPL', fields: ['ask'] },
+          ],
         ]),
       );
 
       handleVmResult(vmResult, 0, 21444n);
     });
 
+    it('rejects responses signed by an unexpected proxy public key', async () => {
+      const proxyResponse = await makeDataProxyResponse(PROXY_URL, QUOTE_BODY, 'GET', undefined, unexpectedDataProxy);
+
+      const oracleProgram = await file(WASM_PATH).arrayBuffer();
+      const vmResult = await testOracleProgramTally(
+        Buffer.from(oracleProgram),
+        Buffer.from('symbol=AAPL'),
+        createRevealArray([
+          [
+            RevealKind.HttpFetchResponse,
+            { response: proxyResponse.dataProxyResponse, symbol: 'AAPL', fields: ['ask'] },
+          ],
+        ]),
+      );
+
+      handleVmResult(vmResult, 1, 0n);
+      expect(Buffer.from(vmResult.result).toString()).toBe('Unexpected proxy public key');
+    });
+
+    it('rejects responses for another symbol', async () => {
+      // A validly signed response for TSLA, revealed as if it answered the requested AAPL.
+      const proxyResponse = await makeDataProxyResponse('http://98.84.79.123:5384/proxy/uslf_q/TSLA', QUOTE_BODY);
+
+      const oracleProgram = await file(WASM_PATH).arrayBuffer();
+      const vmResult = await testOracleProgramTally(
+        Buffer.from(oracleProgram),
+        Buffer.from('symbol=AAPL'),
+        createRevealArray([
+          [
+            RevealKind.HttpFetchResponse,
+            { response: proxyResponse.dataProxyResponse, symbol: 'AAPL', fields: ['ask'] },
+          ],
+        ]),
+      );
+
+      handleVmResult(vmResult, 1, 0n);
+      expect(Buffer.from(vmResult.result).toString()).toBe('No verified reveals');
+    });
+
+    it('rejects reveals for other fields than the requested ones', async () => {
+      const proxyResponse = await makeDataProxyResponse(PROXY_URL, QUOTE_BODY);
+      const reveal = (fields: string[]): RevealInput => [
+        RevealKind.HttpFetchResponse,
+        { response: proxyResponse.dataProxyResponse, symbol: 'AAPL', fields },
+      ];
+
+      const oracleProgram = await file(WASM_PATH).arrayBuffer();
+      const vmResult = await testOracleProgramTally(
+        Buffer.from(oracleProgram),
+        Buffer.from('symbol=AAPL,fields=bid;mid'),
+        createRevealArray([reveal(['ask']), reveal(['mid', 'bid']), reveal(['bid', 'mid'])]),
+      );
+
+      handleBigIntArrayTallyVmResult(vmResult, 0, [21420n, 21432n]);
+    });
+
+    it('rejects the reveals of the default field when other fields are requested', async () => {
+      const proxyResponse = await makeDataProxyResponse(PROXY_URL, QUOTE_BODY);
+
+      const oracleProgram = await file(WASM_PATH).arrayBuffer();
+      const vmResult = await testOracleProgramTally(
+        Buffer.from(oracleProgram),
+        Buffer.from('symbol=AAPL,fields=bid'),
+        createRevealArray([
+          [
+            RevealKind.HttpFetchResponse,
+            { response: proxyResponse.dataProxyResponse, symbol: 'AAPL', fields: ['ask'] },
+          ],
+        ]),
+      );
+
+      handleVmResult(vmResult, 1, 0n);
+      expect(Buffer.from(vmResult.result).toString()).toBe('No verified reveals');
+    });
+
+    it('errors on fields that are not available', async () => {
+      const oracleProgram = await file(WASM_PATH).arrayBuffer();
+      const vmResult = await testOracleProgramTally(
+        Buffer.from(oracleProgram),
+        Buffer.from('symbol=AAPL,fields=bid;last'),
+        createRevealArray([[RevealKind.Failed]]),
+      );
+
+      handleVmResult(vmResult, 1, 0n);
+      expect(Buffer.from(vmResult.result).toString()).toBe('Invalid tally inputs');
+    });
+
     describe('works with errored executions', () => {
       it('should error if all executions errored', async () => {
         const oracleProgram = await file(WASM_PATH).arrayBuffer();
         const vmResult = await testOracleProgramTally(
           Buffer.from(oracleProgram),
-          Buffer.from('tally-inputs'),
+          Buffer.from('symbol=AAPL'),
           createRevealArray([[RevealKind.Failed]]),
         );
 
@@ -1,13 +1,16 @@
 // biome-ignore assist/source/organizeImports: biome is lying
 import { file } from 'bun';
-import { afterEach, describe, it, mock } from 'bun:test';
+import { afterEach, describe, expect, it, mock } from 'bun:test';
 import { testOracleProgramExecution, testOracleProgramTally } from '@seda-protocol/dev-tools';
 import {
   handleBigIntArrayTallyVmResult as handleVmResult,
   handleHttpFetchResponseExecutionVmResult as handleExecutionVmResult,
   createRevealArray,
   RevealKind,
+  type RevealInput,
   makeDataProxyResponse,
+  unexpectedDataProxy,
+  withUpperCasePublicKey,
 } from './utils.js';
 import type { HttpFetchResponseData } from '@seda-protocol/vm';
 
@@ -15,6 +18,9 @@ const WASM_PATH = 'target/wasm32-wasip1/release/single-price-feed-verification.w
 
 const fetchMock = mock();
 
+// The URL the tally expects the responses of `symbol=BTC;ETH` to answer.
+const PROXY_URL = 'http://34.78.7.237:5384/proxy/usd/BTC,ETH';
+
 afterEach(() => {
   fetchMock.mockRestore();
 });
@@ -45,6 +51,29 @@ describe('single price feed verification', () => {
       );
 
       handleExecutionVmResult(vmResult, 0, expectedResponse, responseBody);
+      expect(fetchMock.mock.calls.map(([url]) => url.toString())).toEqual([
+        'http://34.78.7.237:5384/proxy/usd/BTC,ETH',
+      ]);
+    });
+
+    it('percent-encodes every symbol of the list in the path', async () => {
+      fetchMock.mockImplementation((_) => new Response('{}'));
+
+      const oracleProgram = await file(WASM_PATH).arrayBuffer();
+
+      await testOracleProgramExecution(
+        Buffer.from(oracleProgram),
+        Buffer.from('BTC,ETH/USD?x=1'),
+        fetchMock,
+        undefined,
+        undefined,
+        undefined,
+        0n,
+      );
+
+      expect(fetchMock.mock.calls.map(([url]) => url.toString())).toEqual([
+        'http://34.78.7.237:5384/proxy/usd/BTC,ETH%2FUSD%3Fx%3D1',
+      ]);
     });
   });
 
@@ -54,24 +83,205 @@ describe('single price feed verification', () => {
         btc: { usd: 113301 },
         eth: { usd: 4151.3 },
       };
-      const proxyResponse = await makeDataProxyResponse('http://test.dummy:5384/proxy/usd/BTC,ETH', requestBody);
+      const proxyResponse = await makeDataProxyResponse(PROXY_URL, requestBody);
 
       const oracleProgram = await file(WASM_PATH).arrayBuffer();
       const vmResult = await testOracleProgramTally(
         Buffer.from(oracleProgram),
-        Buffer.from('tally-inputs'),
+        Buffer.from('symbol=BTC;ETH'),
         createRevealArray([[RevealKind.HttpFetchResponse, proxyResponse.dataProxyResponse]]),
       );
 
       handleVmResult(vmResult, 0, [113301000000n, 4151300000n]);
     });
 
+    it('takes the median over several verified reveals', async () => {
+      const proxyResponses = await Promise.all([
+        makeDataProxyResponse(PROXY_URL, { btc: { usd: 113300 }, eth: { usd: 4151.3 } }),
+        makeDataProxyResponse(PROXY_URL, { btc: { usd: 113301 }, eth: { usd: 4151.5 } }),
+        makeDataProxyResponse(PROXY_URL, { btc: { usd: 113302 }, eth: { usd: 4151.7 } }),
+      ]);
+
+      const oracleProgram = await file(WASM_PATH).arrayBuffer();
+      const vmResult = await testOracleProgramTally(
+        Buffer.from(oracleProgram),
+        Buffer.from('symbol=BTC;ETH'),
+        createRevealArray(
+          proxyResponses.map((response): RevealInput => [RevealKind.HttpFetchResponse, response.dataProxyResponse]),
+        ),
+      );
+
+      handleVmResult(vmResult, 0, [113301000000n, 4151500000n]);
+    });
+
+    it('discards the unverifiable reveals', async () => {
+      const first = await makeDataProxyResponse(PROXY_URL, { btc: { usd: 113300 }, eth: { usd: 4151.3 } });
+      const second = await makeDataProxyResponse(PROXY_URL, { btc: { usd: 113302 }, eth: { usd: 4151.5 } });
+      // A response whose body was changed after the proxy signed it.
+      const tampered = {
+        ...first.dataProxyResponse,
+        bytes: Array.from(Buffer.from(JSON.stringify({ btc: { usd: 1 }, eth: { usd: 1 } }))),
+      };
+
+      const oracleProgram = await file(WASM_PATH).arrayBuffer();
+      const vmResult = await testOracleProgramTally(
+        Buffer.from(oracleProgram),
+        Buffer.from('symbol=BTC;ETH'),
+        createRevealArray([
+          [RevealKind.HttpFetchResponse, first.dataProxyResponse],
+          [RevealKind.HttpFetchResponse, tampered],
+          [RevealKind.Failed],
+          [RevealKind.HttpFetchResponse, second.dataProxyResponse],
+        ]),
+      );
+
+      handleVmResult(vmResult, 0, [113301000000n, 4151400000n]);
+    });
+
+    it('accepts a proxy public key in upper case hex', async () => {
+      const proxyResponse = await makeDataProxyResponse(PROXY_URL, { btc: { usd: 113300 }, eth: { usd: 4151.3 } });
+
+      const oracleProgram = await file(WASM_PATH).arrayBuffer();
+      const vmResult = await testOracleProgramTally(
+        Buffer.from(oracleProgram),
+        Buffer.from('symbol=BTC;ETH'),
+        createRevealArray([[RevealKind.HttpFetchResponse, withUpperCasePublicKey(proxyResponse.dataProxyResponse)]]),
+      );
+
+      handleVmResult(vmResult, 0, [113300000000n, 4151300000n]);
+    });
+
+    it('ignores the responses signed by an unexpected proxy public key', async () => {
+      const expected = await makeDataProxyResponse(PROXY_URL, { btc: { usd: 113300 }, eth: { usd: 4151.3 } });
+      const unexpected = await makeDataProxyResponse(
+        PROXY_URL,
+        { btc: { usd: 1 }, eth: { usd: 1 } },
+        'GET',
+        undefined,
+        unexpectedDataProxy,
+      );
+
+      const oracleProgram = await file(WASM_PATH).arrayBuffer();
+      const vmResult = await testOracleProgramTally(
+        Buffer.from(oracleProgram),
+        Buffer.from('symbol=BTC;ETH'),
+        createRevealArray([
+          [RevealKind.HttpFetchResponse, unexpected.dataProxyResponse],
+          [RevealKind.HttpFetchResponse, expected.dataProxyResponse],
+        ]),
+      );
+
+      handleVmResult(vmResult, 0, [113300000000n, 4151300000n]);
+    });
+
+    it('ignores the responses for other symbols', async () => {
+      const expected = await makeDataProxyResponse(PROXY_URL, { btc: { usd: 113300 }, eth: { usd: 4151.3 } });
+      // A validly signed response, but for symbols other than the requested ones.
+      const replayed = await makeDataProxyResponse('http://34.78.7.237:5384/proxy/usd/BTC,SOL', {
+        btc: { usd: 1 },
+        sol: { usd: 1 },
+      });
+
+      const oracleProgram = await file(WASM_PATH).arrayBuffer();
+      const vmResult = await testOracleProgramTally(
+        Buffer.from(oracleProgram),
+        Buffer.from('symbol=BTC;ETH'),
+        createRevealArray([
+          [RevealKind.HttpFetchResponse, replayed.dataProxyResponse],
+          [RevealKind.HttpFetchResponse, expected.dataProxyResponse],
+        ]),
+      );
+
+      handleVmResult(vmResult, 0, [113300000000n, 4151300000n]);
+    });
+
+    it('ignores the responses without a price for every requested symbol', async () => {
+      const expected = await makeDataProxyResponse(PROXY_URL, { btc: { usd: 113300 }, eth: { usd: 4151.3 } });
+      const partial = await makeDataProxyResponse(PROXY_URL, { btc: { usd: 1 } });
+
+      const oracleProgram = await file(WASM_PATH).arrayBuffer();
+      const vmResult = await testOracleProgramTally(
+        Buffer.from(oracleProgram),
+        Buffer.from('symbol=BTC;ETH'),
+        createRevealArray([
+          [RevealKind.HttpFetchResponse, partial.dataProxyResponse],
+          [RevealKind.HttpFetchResponse, expected.dataProxyResponse],
+        ]),
+      );
+
+      handleVmResult(vmResult, 0, [113300000000n, 4151300000n]);
+    });
+
+    it('returns the prices in the requested order rather than the order of the response', async () => {
+      const url = 'http://34.78.7.237:5384/proxy/usd/ETH,BTC';
+      const proxyResponse = await makeDataProxyResponse(url, { btc: { usd: 113300 }, eth: { usd: 4151.3 } });
+
+      const oracleProgram = await file(WASM_PATH).arrayBuffer();
+      const vmResult = await testOracleProgramTally(
+        Buffer.from(oracleProgram),
+        Buffer.from('symbol=ETH;BTC'),
+        createRevealArray([[RevealKind.HttpFetchResponse, proxyResponse.dataProxyResponse]]),
+      );
+
+      handleVmResult(vmResult, 0, [4151300000n, 113300000000n]);
+    });
+
+    it('ignores the responses with a missing, non-numeric or zero usd price', async () => {
+      const expected = await makeDataProxyResponse(PROXY_URL, { btc: { usd: 113300 }, eth: { usd: 4151.3 } });
+      const invalid = await Promise.all([
+        makeDataProxyResponse(PROXY_URL, { btc: { usd: 1 }, eth: { eur: 1 } }),
+        makeDataProxyResponse(PROXY_URL, { btc: { usd: 1 }, eth: { usd: 'n/a' } }),
+        makeDataProxyResponse(PROXY_URL, { btc: { usd: 1 }, eth: { usd: 0 } }),
+        makeDataProxyResponse(PROXY_URL, { btc: { usd: 1 }, sol: { usd: 1 } }),
+      ]);
+
+      const oracleProgram = await file(WASM_PATH).arrayBuffer();
+      const vmResult = await testOracleProgramTally(
+        Buffer.from(oracleProgram),
+        Buffer.from('symbol=BTC;ETH'),
+        createRevealArray([
+          ...invalid.map((response): RevealInput => [RevealKind.HttpFetchResponse, response.dataProxyResponse]),
+          [RevealKind.HttpFetchResponse, expected.dataProxyResponse],
+        ]),
+      );
+
+      handleVmResult(vmResult, 0, [113300000000n, 4151300000n]);
+    });
+
+    it('errors if no response has a valid usd price for every symbol', async () => {
+      const proxyResponse = await makeDataProxyResponse(PROXY_URL, { btc: { usd: 113300 }, eth: {} });
+
+      const oracleProgram = await file(WASM_PATH).arrayBuffer();
+      const vmResult = await testOracleProgramTally(
+        Buffer.from(oracleProgram),
+        Buffer.from('symbol=BTC;ETH'),
+        createRevealArray([[RevealKind.HttpFetchResponse, proxyResponse.dataProxyResponse]]),
+      );
+
+      handleVmResult(vmResult, 1, [0n]);
+      expect(Buffer.from(vmResult.result).toString()).toBe('No verified reveals');
+    });
+
+    it('should error without symbols in the tally inputs', async () => {
+      const proxyResponse = await makeDataProxyResponse(PROXY_URL, { btc: { usd: 113300 }, eth: { usd: 4151.3 } });
+
+      const oracleProgram = await file(WASM_PATH).arrayBuffer();
+      const vmResult = await testOracleProgramTally(
+        Buffer.from(oracleProgram),
+        Buffer.from('BTC,ETH'),
+        createRevealArray([[RevealKind.HttpFetchResponse, proxyResponse.dataProxyResponse]]),
+      );
+
+      handleVmResult(vmResult, 1, [0n]);
+      expect(Buffer.from(vmResult.result).toString()).toBe('No symbol provided');
+    });
+
     describe('works with errored executions', () => {
       it('should error if all executions errored', async () => {
         const oracleProgram = await file(WASM_PATH).arrayBuffer();
         const vmResult = await testOracleProgramTally(
           Buffer.from(oracleProgram),
-          Buffer.from('tally-inputs'),
+          Buffer.from('symbol=BTC;ETH'),
           createRevealArray([[RevealKind.Failed]]),
         );
 
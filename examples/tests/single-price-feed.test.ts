@@ -1,6 +1,6 @@
 // biome-ignore assist/source/organizeImports: biome is lying
 import { file } from 'bun';
-import { afterEach, describe, it, mock, test } from 'bun:test';
+import { afterEach, describe, expect, it, mock, test } from 'bun:test';
 import { testOracleProgramExecution, testOracleProgramTally } from '@seda-protocol/dev-tools';
 import {
   handleBigIntArrayTallyVmResult as handleVmResult,
@@ -37,6 +37,29 @@ describe('single price feed', () => {
       );
 
       handleExecutionVmResult(vmResult, 0, [121239000000n, 4658030000n]);
+      expect(fetchMock.mock.calls.map(([url]) => url.toString())).toEqual([
+        'http://34.78.7.237:5384/proxy/usd/BTC,ETH',
+      ]);
+    });
+
+    it('percent-encodes every symbol of the list in the path', async () => {
+      fetchMock.mockImplementation((_) => new Response('{}'));
+
+      const oracleProgram = await file(WASM_PATH).arrayBuffer();
+
+      await testOracleProgramExecution(
+        Buffer.from(oracleProgram),
+        Buffer.from('BTC,ETH/USD?x=1'),
+        fetchMock,
+        undefined,
+        undefined,
+        undefined,
+        0n,
+      );
+
+      expect(fetchMock.mock.calls.map(([url]) => url.toString())).toEqual([
+        'http://34.78.7.237:5384/proxy/usd/BTC,ETH%2FUSD%3Fx%3D1',
+      ]);
     });
   });
 
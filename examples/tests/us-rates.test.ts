@@ -1,6 +1,6 @@
 // biome-ignore assist/source/organizeImports: biome is lying
 import { file } from 'bun';
-import { afterEach, describe, it, mock, test } from 'bun:test';
+import { afterEach, describe, expect, it, mock, test } from 'bun:test';
 import { testOracleProgramExecution, testOracleProgramTally } from '@seda-protocol/dev-tools';
 import {
   handleBigIntArrayTallyVmResult as handleVmResult,
@@ -63,6 +63,30 @@ describe('us rates', () => {
       );
 
       handleExecutionVmResult(vmResult, 0, [4276774n, 119149710596n]);
+      expect(fetchMock.mock.calls.map(([url]) => url.toString())).toEqual([
+        'http://43.157.108.162:5384/proxy/price?code=Rates%3AUS%3AUS10Y',
+        'http://43.157.108.162:5384/proxy/price?code=Crypto%3AALL%3ABTC%2FUSDT',
+      ]);
+    });
+
+    it('percent-encodes the code in the query', async () => {
+      fetchMock.mockImplementation((_) => new Response('{}'));
+
+      const oracleProgram = await file(WASM_PATH).arrayBuffer();
+
+      await testOracleProgramExecution(
+        Buffer.from(oracleProgram),
+        Buffer.from('Rates:US:US10Y&code=X Y'),
+        fetchMock,
+        undefined,
+        undefined,
+        undefined,
+        0n,
+      );
+
+      expect(fetchMock.mock.calls.map(([url]) => url.toString())).toEqual([
+        'http://43.157.108.162:5384/proxy/price?code=Rates%3AUS%3AUS10Y%26code%3DX%20Y',
+      ]);
     });
   });
 
@@ -27,6 +27,10 @@ function createSuccessfulJsonBigIntArrayReveal(values: bigint[]): RevealResult {
   return genericCreateSuccessfulReveal(Buffer.from(encoded));
 }
 
+function createSuccessfulJsonReveal(json: string): RevealResult {
+  return genericCreateSuccessfulReveal(Buffer.from(json));
+}
+
 function createSuccessfulJsonBigIntReveal(value: bigint): RevealResult {
   const buf = Buffer.alloc(16);
   buf.writeBigUInt64LE(value & ((1n << 64n) - 1n), 0);
@@ -67,6 +71,7 @@ function createFailedReveal(): RevealResult {
 
 export enum RevealKind {
   JsonBigIntArray,
+  Json,
   JsonBigInt,
   BigInt,
   BigIntArray,
@@ -80,6 +85,7 @@ export type RevealInput =
   | [RevealKind.BigIntArray, bigint[]]
   | [RevealKind.JsonBigInt, bigint]
   | [RevealKind.JsonBigIntArray, bigint[]]
+  | [RevealKind.Json, string]
   | [RevealKind.HttpFetchResponse, HttpFetchResponseData | unknown];
 
 export function createRevealArray(values: RevealInput[]): RevealResult[] {
@@ -95,19 +101,25 @@ export function createRevealArray(values: RevealInput[]): RevealResult[] {
         return createSuccessfulJsonBigIntReveal(val as bigint);
       case RevealKind.JsonBigIntArray:
         return createSuccessfulJsonBigIntArrayReveal(val as bigint[]);
+      case RevealKind.Json:
+        return createSuccessfulJsonReveal(val as string);
       case RevealKind.HttpFetchResponse:
         return createSuccessfulHttpFetchResponseReveal(val as HttpFetchResponseData);
     }
   });
 }
 
-const data_proxy = new TestDataProxy();
+// The tallies only accept responses signed by the proxy public key of the private key `1` in tests.
+const data_proxy = new TestDataProxy(Buffer.from('01'.padStart(64, '0'), 'hex'));
+// A Data Proxy the tallies don't know about, whose responses are signed but not accepted.
+export const unexpectedDataProxy = new TestDataProxy(Buffer.from('02'.padStart(64, '0'), 'hex'));
 
 export async function makeDataProxyResponse(
   url: string | URL,
   responseBody: unknown,
   method: string = 'GET',
   requestBody?: unknown,
+  dataProxy: TestDataProxy = data_proxy,
 ): Promise<{
   dataProxyResponse: HttpFetchResponseData;
   mockedResponse: Response;
@@ -119,7 +131,7 @@ export async function makeDataProxyResponse(
   const urlStr = typeof url === 'string' ? url : url.href;
   const responseBodyBuffer = Buffer.from(JSON.stringify(responseBody));
   const requestBodyBuffer = requestBody ? Buffer.from(JSON.stringify(requestBody)) : undefined;
-  const dataProxyResponse = await data_proxy.createResponse(urlStr, method, 200, responseBodyBuffer, requestBodyBuffer);
+  const dataProxyResponse = await dataProxy.createResponse(urlStr, method, 200, responseBodyBuffer, requestBodyBuffer);
   const headersObj =
     dataProxyResponse.headers instanceof Headers
       ? Object.fromEntries(dataProxyResponse.headers.entries())
@@ -136,6 +148,12 @@ export async function makeDataProxyResponse(
   };
 }
 
+// The same signed response, with the proxy public key header in upper case hex.
+export function withUpperCasePublicKey(response: HttpFetchResponseData): HttpFetchResponseData {
+  const headers = response.headers as Record<string, string>;
+  return { ...response, headers: { ...headers, 'x-seda-publickey': headers['x-seda-publickey'].toUpperCase() } };
+}
+
 function genericHandleTallyVmResult<T>(vmResult: VmResult, exitCode: number, expected: T, codec?: string) {
   console.debug('Stdout:', vmResult.stdout);
   console.debug('Stderr:', vmResult.stderr);
@@ -229,6 +247,17 @@ export function handleBigIntArrayTallyVmResult(vmResult: VmResult, exitCode: num
   genericHandleTallyVmResult(vmResult, exitCode, expected, 'uint256[]');
 }
 
+export function handleTupleTallyVmResult(vmResult: VmResult, exitCode: number, expected: unknown[], codecs: string[]) {
+  genericHandleTallyVmResult(vmResult, exitCode, expected);
+  if (vmResult.exitCode !== 0) return;
+
+  // Decode each top-level value of the tuple separately using ethers' AbiCoder
+  const decoded = AbiCoder.decode(codecs, vmResult.result);
+  codecs.forEach((_, i) => {
+    expect(decoded[i]).toEqual(expected[i]);
+  });
+}
+
 export function handleJsonBigIntArrayExecutionVmResult(vmResult: VmResult, exitCode: number, expected: bigint[]) {
   genericHandleTallyVmResult(vmResult, exitCode, expected);
   if (vmResult.exitCode !== 0) return;
@@ -245,3 +274,14 @@ export function handleJsonBigIntArrayExecutionVmResult(vmResult: VmResult, exitC
   const values = jsonArray.map((v: string) => BigInt(v));
   expect(values).toEqual(expected);
 }
+
+export function handleJsonExecutionVmResult(vmResult: VmResult, exitCode: number, expected: unknown) {
+  genericHandleTallyVmResult(vmResult, exitCode, expected);
+  if (vmResult.exitCode !== 0) return;
+
+  // Parse the JSON result (serde_json::to_vec) and compare its whole shape
+  const jsonString = Buffer.from(vmResult.result).toString('utf-8');
+  expect(jsonString).toBeDefined();
+  expect(jsonString.length).toBeGreaterThan(0);
+  expect(JSON.parse(jsonString)).toEqual(expected);
+}
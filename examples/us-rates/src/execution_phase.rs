@@ -1,17 +1,19 @@
 use anyhow::Result;
 #[cfg(any(feature = "testnet", feature = "mainnet"))]
 use seda_sdk_rs::{Process, elog, log, proxy_http_fetch};
+#[cfg(any(feature = "testnet", feature = "mainnet"))]
+use shared::UrlBuilder;
 
 // Version 3.0 - Dynamic symbol support for any Nobi Labs endpoint
 const ORACLE_VERSION: &str = "3.0";
 
 #[cfg(feature = "testnet")]
-const API_URL: &str = "http://43.157.108.162:5384/proxy/price?code=";
+const API_URL: &str = "http://43.157.108.162:5384/proxy/price";
 #[cfg(feature = "testnet")]
 const PROXY_PUBLIC_KEY: &str = "0268da5dbf3c31908884c0c95096ad50c5d1a98fd3846529f9513ddcc08d37e06c";
 
 #[cfg(feature = "mainnet")]
-const API_URL: &str = "http://seda.labs.usenobi.com:5384/proxy/price?code=";
+const API_URL: &str = "http://seda.labs.usenobi.com:5384/proxy/price";
 #[cfg(feature = "mainnet")]
 const PROXY_PUBLIC_KEY: &str = "03aa3acda2feea7f55c7cfdfc1b906c741cb98d1ad80653b0a199555021134ee22";
 
@@ -45,7 +47,9 @@ pub fn execution_phase() -> Result<()> {
         use seda_sdk_rs::HttpFetchOptions;
 
         let trimmed_symbol = symbol.trim();
-        let url = [API_URL, trimmed_symbol].concat();
+        let url = UrlBuilder::new(API_URL)
+            .query("code", trimmed_symbol)
+            .build();
         let response = proxy_http_fetch(
             url,
             Some(PROXY_PUBLIC_KEY.to_string()),
@@ -5,7 +5,7 @@ import {
   type PostDataRequestInput,
   Signer,
 } from '@seda-protocol/dev-tools';
-import { AbiCoder } from 'ethers';
+import { AbiCoder, ParamType } from 'ethers';
 import { Command } from 'commander';
 
 function truncate(str: string, maxLen: number = 50): string {
@@ -108,7 +108,21 @@ async function main() {
     const buf = Buffer.from(data, 'hex');
 
     const coder = AbiCoder.defaultAbiCoder();
-    const [bnArray] = coder.decode([options.decodeAbi], buf) as unknown as [bigint[]];
+    const paramType = ParamType.from(options.decodeAbi);
+
+    // A tuple type (e.g. "(int256[],bool[])") describes several top-level values, decode each of them.
+    if (paramType.isTuple()) {
+      const decoded = coder.decode(paramType.components, buf);
+
+      console.log('Decoded result:');
+      paramType.components.forEach((component, i) => {
+        console.log(`${component.format()}:`);
+        console.table(decoded[i]);
+      });
+      return;
+    }
+
+    const [bnArray] = coder.decode([paramType], buf) as unknown as [bigint[]];
 
     console.log('Decoded result:');
     console.table(bnArray);
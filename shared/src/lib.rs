@@ -0,0 +1,5 @@
+//! Code shared by the oracle programs.
+
+mod url;
+
+pub use url::UrlBuilder;
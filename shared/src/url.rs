@@ -0,0 +1,200 @@
+/// Builds an upstream URL out of a base URL, path segments and query parameters, percent-encoding
+/// the user input so it can't change the structure of the URL.
+///
+/// ```
+/// use shared::UrlBuilder;
+///
+/// let url = UrlBuilder::new("https://example.com/proxy/")
+///     .segment("BTC/USD")
+///     .query("utcHour", "4")
+///     .build();
+/// assert_eq!(url, "https://example.com/proxy/BTC%2FUSD?utcHour=4");
+/// ```
+#[derive(Debug, Clone)]
+pub struct UrlBuilder {
+    url: String,
+    has_query: bool,
+}
+
+impl UrlBuilder {
+    /// Starts a URL from a base URL, which is used as is.
+    pub fn new(base: &str) -> Self {
+        Self {
+            url: base.to_string(),
+            has_query: base.contains('?'),
+        }
+    }
+
+    /// Appends a percent-encoded path segment, separated from the previous one by a `/`.
+    /// Segments must be added before any query parameter.
+    pub fn segment(self, segment: &str) -> Self {
+        self.segment_list(&[segment])
+    }
+
+    /// Appends a path segment made of a comma separated list of percent-encoded values.
+    pub fn segment_list(mut self, values: &[&str]) -> Self {
+        debug_assert!(!self.has_query, "path segments must come before the query");
+        if !self.url.ends_with('/') {
+            self.url.push('/');
+        }
+        push_list(&mut self.url, values);
+        self
+    }
+
+    /// Appends a query parameter with a percent-encoded value.
+    pub fn query(self, key: &str, value: &str) -> Self {
+        self.query_list(key, &[value])
+    }
+
+    /// Appends a query parameter with a comma separated list of percent-encoded values.
+    pub fn query_list(mut self, key: &str, values: &[&str]) -> Self {
+        self.url.push(if self.has_query { '&' } else { '?' });
+        self.has_query = true;
+        percent_encode_into(&mut self.url, key);
+        self.url.push('=');
+        push_list(&mut self.url, values);
+        self
+    }
+
+    /// Returns the URL.
+    pub fn build(self) -> String {
+        self.url
+    }
+}
+
+/// Appends the percent-encoded values separated by commas.
+fn push_list(output: &mut String, values: &[&str]) {
+    for (index, value) in values.iter().enumerate() {
+        if index > 0 {
+            output.push(',');
+        }
+        percent_encode_into(output, value);
+    }
+}
+
+/// Percent-encodes every byte of `input` except the unreserved characters of RFC 3986.
+fn percent_encode_into(output: &mut String, input: &str) {
+    const HEX: &[u8; 16] = b"0123456789ABCDEF";
+
+    for byte in input.bytes() {
+        if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'.' | b'_' | b'~') {
+            output.push(byte as char);
+        } else {
+            output.push('%');
+            output.push(HEX[(byte >> 4) as usize] as char);
+            output.push(HEX[(byte & 0x0F) as usize] as char);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::UrlBuilder;
+
+    const BASE: &str = "https://example.com/proxy";
+
+    #[test]
+    fn encodes_reserved_characters_in_a_segment() {
+        let url = UrlBuilder::new(BASE).segment("a/b?c&d%e:f").build();
+        assert_eq!(url, "https://example.com/proxy/a%2Fb%3Fc%26d%25e%3Af");
+    }
+
+    #[test]
+    fn encodes_reserved_characters_in_a_query() {
+        let url = UrlBuilder::new(BASE)
+            .query("code", "Rates:US:US10Y&code=a/b?c%d")
+            .build();
+        assert_eq!(
+            url,
+            "https://example.com/proxy?code=Rates%3AUS%3AUS10Y%26code%3Da%2Fb%3Fc%25d"
+        );
+    }
+
+    #[test]
+    fn encodes_the_query_key() {
+        let url = UrlBuilder::new(BASE).query("a&b=c", "d").build();
+        assert_eq!(url, "https://example.com/proxy?a%26b%3Dc=d");
+    }
+
+    #[test]
+    fn encodes_spaces() {
+        let url = UrlBuilder::new(BASE)
+            .segment("BRK B")
+            .query("name", "BRK B")
+            .build();
+        assert_eq!(url, "https://example.com/proxy/BRK%20B?name=BRK%20B");
+    }
+
+    #[test]
+    fn encodes_every_byte_of_non_ascii_characters() {
+        let url = UrlBuilder::new(BASE)
+            .segment("€uro")
+            .query("name", "é")
+            .build();
+        assert_eq!(url, "https://example.com/proxy/%E2%82%ACuro?name=%C3%A9");
+    }
+
+    #[test]
+    fn keeps_the_unreserved_characters() {
+        let url = UrlBuilder::new(BASE)
+            .segment("AZaz09-._~")
+            .query("id", "AZaz09-._~")
+            .build();
+        assert_eq!(url, "https://example.com/proxy/AZaz09-._~?id=AZaz09-._~");
+    }
+
+    #[test]
+    fn separates_segments_with_a_single_slash() {
+        let url = UrlBuilder::new("https://example.com/proxy/")
+            .segment("cfd")
+            .segment("XAU")
+            .segment("USD")
+            .build();
+        assert_eq!(url, "https://example.com/proxy/cfd/XAU/USD");
+    }
+
+    #[test]
+    fn joins_a_segment_list_with_commas_and_encodes_every_value() {
+        let url = UrlBuilder::new(BASE)
+            .segment_list(&["BTC", "ETH,USD", "a/b"])
+            .build();
+        assert_eq!(url, "https://example.com/proxy/BTC,ETH%2CUSD,a%2Fb");
+    }
+
+    #[test]
+    fn joins_a_query_list_with_commas_and_encodes_every_value() {
+        let url = UrlBuilder::new(BASE)
+            .query_list("ids", &["So1", "EP,j", "a b"])
+            .build();
+        assert_eq!(url, "https://example.com/proxy?ids=So1,EP%2Cj,a%20b");
+    }
+
+    #[test]
+    fn builds_an_empty_list() {
+        let url = UrlBuilder::new(BASE).query_list("ids", &[]).build();
+        assert_eq!(url, "https://example.com/proxy?ids=");
+    }
+
+    #[test]
+    fn separates_query_parameters_with_an_ampersand() {
+        let url = UrlBuilder::new(BASE)
+            .query("type", "MINI")
+            .query("symbol", "BTCUSDT")
+            .build();
+        assert_eq!(url, "https://example.com/proxy?type=MINI&symbol=BTCUSDT");
+    }
+
+    #[test]
+    fn appends_to_the_query_of_the_base_url() {
+        let url = UrlBuilder::new("https://example.com/proxy?key=value")
+            .query("symbol", "BTC/USDT")
+            .build();
+        assert_eq!(url, "https://example.com/proxy?key=value&symbol=BTC%2FUSDT");
+    }
+
+    #[test]
+    fn keeps_the_base_url_as_is() {
+        let url = UrlBuilder::new("https://example.com/a b?c=d e").build();
+        assert_eq!(url, "https://example.com/a b?c=d e");
+    }
+}
@@ -23,7 +23,11 @@ enum OracleProgram {
         str_format: bool,
     },
     EvmPriceFeed,
-    GenericDxfeed,
+    GenericDxfeed {
+        /// Build the verification mode in, rather than only selecting it with a "verify:" input prefix.
+        #[arg(long, default_value_t = false)]
+        verification: bool,
+    },
     MultiPriceFeed,
     SingleCommodityPrice,
     SingleEquityPrice,
@@ -41,7 +45,7 @@ impl OracleProgram {
             OracleProgram::BlocksizeVwap => "blocksize-vwap",
             OracleProgram::CaplightEodMarketPrice { str_format: _ } => "caplight-eod-market-price",
             OracleProgram::EvmPriceFeed => "evm-price-feed",
-            OracleProgram::GenericDxfeed => "generic-dxfeed",
+            OracleProgram::GenericDxfeed { verification: _ } => "generic-dxfeed",
             OracleProgram::MultiPriceFeed => "multi-price-feed",
             OracleProgram::SingleCommodityPrice => "single-commodity-price",
             OracleProgram::SingleEquityPrice => "single-equity-price",
@@ -69,40 +73,52 @@ enum DxfeedSymbol {
 #[derive(Subcommand)]
 enum PostableOracleProgram {
     JupPriceFeed {
+        /// Comma-separated list of token mint addresses to fetch prices for,
+        /// optionally followed by the decimals and options (e.g., So11111111111111111111111111111111111111112-8-vsToken=<mint>)
         symbol: String,
     },
     BlocksizeBidask {
+        /// Comma-separated list of pairs, optionally followed by the fields (e.g., ETHUSD,BTCUSD-agg_bid_price)
         symbol: String,
     },
     BlocksizeVwap {
+        /// Comma-separated list of pairs, optionally followed by the fields (e.g., ETHUSD,BTCUSD-price)
         pair: String,
     },
     CaplightEodMarketPrice {
         #[arg(long, default_value_t = false)]
         str_result: bool,
-        /// The project ID to fetch prices for.
+        /// The project ID to fetch prices for, several IDs are separated by a semicolon.
         project_id: String,
     },
     GenericDxfeed {
+        /// Ship the signed proxy responses to the tally to be verified there.
+        #[arg(long, default_value_t = false)]
+        verify: bool,
         /// The asset type to fetch prices for (either "commodity", "equity", or an "fx" pair).
         asset_type: DxfeedSymbol,
-        /// The symbol to fetch prices for (e.g., AAPL, XAU, etc.)
+        /// The symbol to fetch prices for (e.g., AAPL, XAU, etc.), optionally followed by the price
+        /// fields (e.g., AAPL-bid,ask,mid)
         symbol: String,
     },
     SingleCommodityPrice {
-        /// A singular commodity symbol to fetch prices for (e.g., XAU, BRN, etc.)
+        /// A singular commodity symbol to fetch prices for (e.g., XAU, BRN, etc.), optionally followed
+        /// by the price fields (e.g., XAU-bid,ask,mid)
         symbol: String,
     },
     SingleEquityPrice {
-        /// A singular equity symbol to fetch prices for (e.g., AAPL/GOOGL/etc.)
+        /// A singular equity symbol to fetch prices for (e.g., AAPL/GOOGL/etc.), optionally followed
+        /// by the price fields (e.g., AAPL-bid,ask,mid)
         symbol: String,
     },
     SingleEquityPriceVerification {
-        /// A singular equity symbol to fetch prices for (e.g., AAPL/GOOGL/etc.)
+        /// A singular equity symbol to fetch prices for (e.g., AAPL/GOOGL/etc.), optionally followed
+        /// by the price fields (e.g., AAPL-bid,ask,mid)
         symbol: String,
     },
     MultiPriceFeed {
-        /// A price pair of symbols to fetch prices for (e.g., BTC-USDT, ETH-USD)
+        /// A price pair of symbols to fetch prices for (e.g., BTC-USDT, ETH-USD),
+        /// optionally followed by the aggregation mode (e.g., BTC-USDT,vwap)
         symbols: String,
     },
     SinglePriceFeed {
@@ -114,7 +130,10 @@ enum PostableOracleProgram {
         symbols: String,
     },
     EvmPriceFeed {
-        /// A list of price pair of symbols to fetch prices for (e.g., BTC-USDT, ETH-USD)
+        /// Report the observation time, decimals and number of reveals alongside the prices.
+        #[arg(long, default_value_t = false)]
+        timestamped: bool,
+        /// A JSON list of price pairs with the decimals to report them in (e.g., [["BTC-USDT",6],["ETH-USD",8]])
         symbols: String,
     },
     UsRates {
@@ -273,8 +292,11 @@ fn try_main() -> Result<()> {
                 OracleProgram::BlocksizeBidask,
                 OracleProgram::BlocksizeVwap,
                 OracleProgram::CaplightEodMarketPrice { str_format: false },
-                OracleProgram::GenericDxfeed,
+                OracleProgram::GenericDxfeed {
+                    verification: false,
+                },
                 OracleProgram::EvmPriceFeed,
+                OracleProgram::JupPriceFeed,
                 OracleProgram::MultiPriceFeed,
                 OracleProgram::SingleCommodityPrice,
                 OracleProgram::SingleEquityPrice,
@@ -315,6 +337,12 @@ fn compile_op(
             )
             .run()?;
         }
+    } else if let OracleProgram::GenericDxfeed { verification: true } = oracle_program {
+        cmd!(
+            sh,
+            "cargo build --target wasm32-wasip1 --release -p {program_name} --no-default-features --features {seda_network},verification"
+        )
+        .run()?;
     } else {
         cmd!(
             sh,
@@ -435,24 +463,33 @@ impl PostDataRequest {
                 str_result,
                 project_id,
             } => post_caplight_eod_market_price(cmd, &project_id, str_result),
-            PostableOracleProgram::GenericDxfeed { asset_type, symbol } => {
-                post_equity_or_commodity_price(cmd, asset_type, &symbol)
-            }
+            PostableOracleProgram::GenericDxfeed {
+                verify,
+                asset_type,
+                symbol,
+            } => post_equity_or_commodity_price(cmd, asset_type, &symbol, verify),
             PostableOracleProgram::SingleCommodityPrice { symbol } => {
                 post_single_commodity_price(cmd, &symbol)
             }
-            PostableOracleProgram::SingleEquityPrice { symbol }
-            | PostableOracleProgram::SingleEquityPriceVerification { symbol } => {
+            PostableOracleProgram::SingleEquityPrice { symbol } => {
                 post_single_equity_price(cmd, &symbol)
             }
+            PostableOracleProgram::SingleEquityPriceVerification { symbol } => {
+                post_single_equity_price_verification(cmd, &symbol)
+            }
             PostableOracleProgram::MultiPriceFeed { symbols } => {
                 post_multi_price_feed(cmd, &symbols)
             }
-            PostableOracleProgram::SinglePriceFeed { symbols }
-            | PostableOracleProgram::SinglePriceFeedVerification { symbols } => {
+            PostableOracleProgram::SinglePriceFeed { symbols } => {
                 post_single_price_feed(cmd, &symbols)
             }
-            PostableOracleProgram::EvmPriceFeed { symbols } => post_evm_price_feed(cmd, &symbols),
+            PostableOracleProgram::SinglePriceFeedVerification { symbols } => {
+                post_single_price_feed_verification(cmd, &symbols)
+            }
+            PostableOracleProgram::EvmPriceFeed {
+                timestamped,
+                symbols,
+            } => post_evm_price_feed(cmd, timestamped, &symbols),
             PostableOracleProgram::UsRates { symbols } => us_rates(cmd, &symbols),
         }
     }
@@ -468,7 +505,11 @@ fn post_blocksize_bidask(cmd: Cmd<'_>, symbol: &str) -> std::result::Result<(),
 }
 
 fn post_jup_price_feed(cmd: Cmd<'_>, id: &str) -> std::result::Result<(), anyhow::Error> {
-    cmd.arg("--exec-inputs").arg(id).run()?;
+    cmd.arg("--exec-inputs")
+        .arg(id)
+        .arg("--decode-abi")
+        .arg("uint256[]")
+        .run()?;
     Ok(())
 }
 
@@ -486,12 +527,29 @@ fn post_caplight_eod_market_price(
     symbol: &str,
     str_result: bool,
 ) -> std::result::Result<(), anyhow::Error> {
-    let cmd = cmd.arg("--exec-inputs").arg(symbol);
+    let mut cmd = cmd.arg("--exec-inputs").arg(symbol);
 
-    let cmd = if !str_result {
-        cmd.arg("--decode-abi").arg("uint256")
-    } else {
+    // The tally only accepts the prices as of the historical date it is given, if any.
+    if let Some(date) = symbol.split(',').find(|option| option.starts_with("date=")) {
+        cmd = cmd.arg("--tally-inputs").arg(date);
+    }
+
+    // The "valuation" option returns the full valuation payload as a tuple instead of the price.
+    let full_valuation = symbol.split(',').any(|option| option == "valuation");
+    // Several project Ids separated by a semicolon return one price per project Id.
+    let batch = symbol
+        .split(',')
+        .next()
+        .is_some_and(|ids| ids.contains(';'));
+    let cmd = if str_result {
         cmd
+    } else if batch {
+        cmd.arg("--decode-abi").arg("(uint256[],bool[])")
+    } else if full_valuation {
+        cmd.arg("--decode-abi")
+            .arg("(uint256,uint256,uint256,uint32,uint32,int256)")
+    } else {
+        cmd.arg("--decode-abi").arg("uint256")
     };
     cmd.run()?;
 
@@ -502,6 +560,7 @@ fn post_equity_or_commodity_price(
     cmd: Cmd<'_>,
     asset_type: DxfeedSymbol,
     symbol: &str,
+    verify: bool,
 ) -> std::result::Result<(), anyhow::Error> {
     let asset_type = match asset_type {
         DxfeedSymbol::Commodity => "cfd",
@@ -512,14 +571,63 @@ fn post_equity_or_commodity_price(
         DxfeedSymbol::UslfT => "uslf_t",
     };
     let exec_input = format!("{asset_type}/{symbol}");
-    cmd.arg("--exec-inputs")
-        .arg(exec_input)
-        .arg("--decode-abi")
-        .arg("uint256")
-        .run()?;
+    let abi = dxfeed_price_abi(&exec_input);
+    // The verification mode is selected by prefixing the inputs, and the tally checks the responses against
+    // the request of its inputs.
+    if verify {
+        let tally_input = dxfeed_verify_tally_inputs(&exec_input);
+        cmd.arg("--exec-inputs")
+            .arg(format!("verify:{exec_input}"))
+            .arg("--tally-inputs")
+            .arg(tally_input)
+    } else {
+        cmd.arg("--exec-inputs").arg(exec_input)
+    }
+    .arg("--decode-abi")
+    .arg(abi)
+    .run()?;
     Ok(())
 }
 
+/// The tally inputs of a dxFeed verification request, repeating the symbol, fields and decimals of the
+/// execution inputs (e.g., "cfd/XAU/USD:4-bid,ask" is "verify:symbol=cfd/XAU/USD,fields=bid;ask,decimals=4").
+fn dxfeed_verify_tally_inputs(exec_input: &str) -> String {
+    let (symbol, fields) = match exec_input.split_once('-') {
+        Some((symbol, fields)) => (symbol, Some(fields)),
+        None => (exec_input, None),
+    };
+    let (symbol, decimals) = match symbol.split_once(':') {
+        Some((symbol, decimals)) => (symbol, Some(decimals)),
+        None => (symbol, None),
+    };
+
+    let mut tally_input = format!("verify:symbol={symbol}");
+    if let Some(fields) = fields {
+        tally_input.push_str(&format!(",fields={}", fields.replace(',', ";")));
+    }
+    if let Some(decimals) = decimals {
+        tally_input.push_str(&format!(",decimals={decimals}"));
+    }
+    tally_input
+}
+
+/// The ABI type of a dxFeed price result: a `uint256` for a single price field, a `uint256[]` when
+/// several fields are requested after a hyphen (e.g., "AAPL-bid,ask,mid"), or a `(uint256[],uint8[])`
+/// with the decimals of every symbol when several symbols are batched (e.g., "equity/AAPL,fx/EUR").
+/// The session of the last trade (e.g., "equity/AAPL-session") is a `(uint256,uint64,bool,uint256)`.
+fn dxfeed_price_abi(exec_input: &str) -> &'static str {
+    let (symbols, fields) = exec_input.split_once('-').unwrap_or((exec_input, ""));
+    if fields == "session" {
+        "(uint256,uint64,bool,uint256)"
+    } else if symbols.contains(',') {
+        "(uint256[],uint8[])"
+    } else if fields.contains(',') {
+        "uint256[]"
+    } else {
+        "uint256"
+    }
+}
+
 fn post_single_commodity_price(
     cmd: Cmd<'_>,
     symbol: &str,
@@ -527,7 +635,7 @@ fn post_single_commodity_price(
     cmd.arg("--exec-inputs")
         .arg(symbol)
         .arg("--decode-abi")
-        .arg("uint256")
+        .arg(dxfeed_price_abi(symbol))
         .run()?;
     Ok(())
 }
@@ -536,7 +644,27 @@ fn post_single_equity_price(cmd: Cmd<'_>, symbol: &str) -> std::result::Result<(
     cmd.arg("--exec-inputs")
         .arg(symbol)
         .arg("--decode-abi")
-        .arg("uint256")
+        .arg(dxfeed_price_abi(symbol))
+        .run()?;
+    Ok(())
+}
+
+/// Post a single equity price verification data request, passing the symbol and fields to the tally so it
+/// can check the signed responses answer them.
+fn post_single_equity_price_verification(
+    cmd: Cmd<'_>,
+    symbol: &str,
+) -> std::result::Result<(), anyhow::Error> {
+    let tally_input = match symbol.split_once('-') {
+        Some((ticker, fields)) => format!("symbol={ticker},fields={}", fields.replace(',', ";")),
+        None => format!("symbol={symbol}"),
+    };
+    cmd.arg("--exec-inputs")
+        .arg(symbol)
+        .arg("--tally-inputs")
+        .arg(tally_input)
+        .arg("--decode-abi")
+        .arg(dxfeed_price_abi(symbol))
         .run()?;
     Ok(())
 }
@@ -551,6 +679,22 @@ fn post_single_price_feed(cmd: Cmd<'_>, symbols: &str) -> std::result::Result<()
     Ok(())
 }
 
+/// Post a single price feed verification data request, passing the symbols to the tally as well so it
+/// can check the signed responses answer them.
+fn post_single_price_feed_verification(
+    cmd: Cmd<'_>,
+    symbols: &str,
+) -> std::result::Result<(), anyhow::Error> {
+    cmd.arg("--exec-inputs")
+        .arg(symbols)
+        .arg("--tally-inputs")
+        .arg(format!("symbol={}", symbols.replace(',', ";")))
+        .arg("--decode-abi")
+        .arg("uint256[]")
+        .run()?;
+    Ok(())
+}
+
 /// Post a multi price feed data request with the specified symbols.
 fn post_multi_price_feed(cmd: Cmd<'_>, symbols: &str) -> std::result::Result<(), anyhow::Error> {
     cmd.arg("--exec-inputs")
@@ -562,14 +706,26 @@ fn post_multi_price_feed(cmd: Cmd<'_>, symbols: &str) -> std::result::Result<(),
 }
 
 /// Post a evm price feed data request with the specified symbols.
-fn post_evm_price_feed(cmd: Cmd<'_>, symbols: &str) -> std::result::Result<(), anyhow::Error> {
-    cmd.arg("--exec-inputs")
+fn post_evm_price_feed(
+    cmd: Cmd<'_>,
+    timestamped: bool,
+    symbols: &str,
+) -> std::result::Result<(), anyhow::Error> {
+    let cmd = cmd
+        .arg("--exec-inputs")
         .arg(symbols)
-        .arg("--decode-abi")
-        .arg("int256[]")
         .arg("--encode-exec-inputs")
-        .arg("string[]")
-        .run()?;
+        .arg("(string,uint8)[]");
+
+    if timestamped {
+        cmd.arg("--tally-inputs")
+            .arg("timestamped")
+            .arg("--decode-abi")
+            .arg("(int256[],bool[],uint64,uint8,uint16)")
+            .run()?;
+    } else {
+        cmd.arg("--decode-abi").arg("(int256[],bool[])").run()?;
+    }
     Ok(())
 }
 